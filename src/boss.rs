@@ -0,0 +1,302 @@
+use raylib::prelude::*;
+
+use crate::{
+    rect_center, Collider, CollisionType, EntityId, Shape, LAYER_ENEMY_SHIP, LAYER_PLAYER, LAYER_PLAYER_PROJECTILE,
+    SCREEN_WIDTH,
+};
+
+pub const BOSS_SCORE: usize = 5000;
+pub const BOSS_PART_SCORE: usize = 300;
+const BOSS_WIDTH: f32 = 260.;
+const BOSS_HEIGHT: f32 = 110.;
+// Height the boss settles at after flying in
+const BOSS_ALTITUDE: f32 = 160.;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartKind {
+    // Destroying it kills the boss
+    Core,
+    // Soaks up shots meant for what is behind it
+    Armor,
+    // Fires aimed shots while it lasts
+    Turret,
+}
+
+#[derive(Debug, Clone)]
+pub struct BossPart {
+    pub kind: PartKind,
+    pub collider: Collider,
+    pub health: u32,
+    max_health: u32,
+}
+
+impl BossPart {
+    fn new(kind: PartKind, shape: CollisionType, offset: Vector2, health: u32) -> Self {
+        Self {
+            kind,
+            collider: Collider::new(shape).with_offset(offset),
+            health,
+            max_health: health,
+        }
+    }
+
+    // Furthest any point of the part gets from the boss center
+    fn reach(&self) -> f32 {
+        let farthest = |points: &[Vector2]| points.iter().map(|p| p.length()).fold(0., f32::max);
+        let extent = match &self.collider.shape {
+            CollisionType::Circle(radius) => *radius,
+            CollisionType::Rectangle(width, height) => Vector2::new(*width, *height).length() / 2.,
+            CollisionType::Triangle(a, b, c) => farthest(&[*a, *b, *c]),
+            CollisionType::Polygon(points) => farthest(points),
+        };
+        self.collider.offset.length() + extent
+    }
+
+    // Parts darken as they take damage
+    pub fn color(&self) -> Color {
+        let wear = self.health as f32 / self.max_health as f32;
+        match self.kind {
+            PartKind::Core => Color::RED,
+            PartKind::Armor => Color::LIGHTGRAY,
+            PartKind::Turret => Color::ORANGE,
+        }.alpha(0.35 + 0.65 * wear)
+    }
+}
+
+// Attack pattern, picked from what is left of the boss
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BossPhase {
+    // Turrets take turns firing at the player
+    Turrets,
+    // Turrets are gone, the core fires wide fans
+    Barrage,
+    // Core is badly hurt, spirals bullets while sweeping faster
+    Frenzy,
+}
+
+pub enum BossHit {
+    Damaged,
+    PartDestroyed,
+    Destroyed,
+}
+
+#[derive(Debug, Clone)]
+pub struct Boss {
+    pub id: EntityId,
+    // Bounds of the whole body, the parts are placed relative to its center
+    pub rect: Rectangle,
+    // Circle around all parts, tested before the parts themselves, and the layers they
+    // share
+    pub collider: Collider,
+    // Parts still standing, destroyed ones are removed
+    pub parts: Vec<BossPart>,
+    // Where the player is, kept up to date by the world every frame
+    pub target: Vector2,
+    pub velocity: Vector2,
+    time: f32,
+    fire_cooldown: f32,
+    // Alternates which turret fires next
+    volley: usize,
+}
+
+impl Boss {
+    pub fn new(id: EntityId) -> Self {
+        let parts = vec![
+            BossPart::new(PartKind::Core, CollisionType::Circle(24.), Vector2::new(0., -8.), 60),
+            // Belly plate shielding the core from straight shots
+            BossPart::new(PartKind::Armor, CollisionType::Rectangle(90., 18.), Vector2::new(0., 35.), 25),
+            BossPart::new(PartKind::Armor, CollisionType::Rectangle(80., 30.), Vector2::new(-90., 0.), 25),
+            BossPart::new(PartKind::Armor, CollisionType::Rectangle(80., 30.), Vector2::new(90., 0.), 25),
+            BossPart::new(PartKind::Turret, CollisionType::Circle(14.), Vector2::new(-90., 32.), 12),
+            BossPart::new(PartKind::Turret, CollisionType::Circle(14.), Vector2::new(90., 32.), 12),
+        ];
+        Self {
+            id,
+            rect: Rectangle::new(SCREEN_WIDTH!() / 2. - BOSS_WIDTH / 2., -BOSS_HEIGHT, BOSS_WIDTH, BOSS_HEIGHT),
+            collider: Collider::new(CollisionType::Circle(parts.iter().map(BossPart::reach).fold(0., f32::max)))
+                .with_layer(LAYER_ENEMY_SHIP, LAYER_PLAYER | LAYER_PLAYER_PROJECTILE),
+            parts,
+            target: Vector2::zero(),
+            velocity: Vector2::zero(),
+            time: 0.,
+            fire_cooldown: 2.,
+            volley: 0,
+        }
+    }
+
+    // Every part placed in the world, in the same order as `parts`
+    pub fn hitboxes_at(&self, offset: Vector2) -> Vec<Shape> {
+        let center = rect_center(&self.rect) + offset;
+        self.parts.iter().map(|p| p.collider.shape_at(center, 0.)).collect()
+    }
+
+    // Remaining health of the core as a fraction, for the health bar. The other parts only
+    // stand in the way, the fight is over when the core goes.
+    pub fn health(&self) -> f32 {
+        self.parts.iter()
+            .find(|p| p.kind == PartKind::Core)
+            .map_or(0., |core| core.health as f32 / core.max_health as f32)
+    }
+
+    pub fn phase(&self) -> BossPhase {
+        let core = self.parts.iter().find(|p| p.kind == PartKind::Core);
+        if core.is_some_and(|c| c.health * 3 <= c.max_health) {
+            BossPhase::Frenzy
+        } else if self.parts.iter().any(|p| p.kind == PartKind::Turret) {
+            BossPhase::Turrets
+        } else {
+            BossPhase::Barrage
+        }
+    }
+
+    pub fn hit(&mut self, part: usize, damage: u32) -> BossHit {
+        let Some(p) = self.parts.get_mut(part) else {
+            return BossHit::Damaged;
+        };
+        p.health = p.health.saturating_sub(damage);
+        if p.health > 0 {
+            return BossHit::Damaged;
+        }
+        if p.kind == PartKind::Core {
+            return BossHit::Destroyed;
+        }
+        self.parts.remove(part);
+        BossHit::PartDestroyed
+    }
+
+    // Fly in, then sweep side to side, faster once in a frenzy
+    pub fn update(&mut self, delta_time: f32) {
+        self.time += delta_time;
+        let rate = if self.phase() == BossPhase::Frenzy { 0.9 } else { 0.4 };
+        let reach = SCREEN_WIDTH!() / 2. - BOSS_WIDTH / 2. - 20.;
+        let goal = Vector2::new(
+            SCREEN_WIDTH!() / 2. + (self.time * rate).sin() * reach,
+            BOSS_ALTITUDE,
+        );
+        self.velocity = (goal - rect_center(&self.rect)) * 2.;
+        self.rect.x += self.velocity.x * delta_time;
+        self.rect.y += self.velocity.y * delta_time;
+        self.fire_cooldown -= delta_time;
+    }
+
+    // Shots fired this frame as start point and heading in degrees from straight up
+    pub fn attack(&mut self) -> Vec<(Vector2, f32)> {
+        if self.fire_cooldown > 0. || self.rect.y < 0. {
+            return vec![];
+        }
+        let center = rect_center(&self.rect);
+        let aim_from = |from: Vector2| {
+            let aim = self.target - from;
+            aim.x.atan2(-aim.y).to_degrees()
+        };
+        let mut shots = vec![];
+        match self.phase() {
+            BossPhase::Turrets => {
+                self.fire_cooldown = 0.6;
+                let turrets: Vec<Vector2> = self.parts.iter()
+                    .filter(|p| p.kind == PartKind::Turret)
+                    .map(|p| center + p.collider.offset)
+                    .collect();
+                if !turrets.is_empty() {
+                    let from = turrets[self.volley % turrets.len()];
+                    shots.push((from, aim_from(from)));
+                }
+            }
+            BossPhase::Barrage => {
+                self.fire_cooldown = 1.4;
+                let aim = aim_from(center);
+                for k in -3..=3 {
+                    shots.push((center, aim + k as f32 * 15.));
+                }
+            }
+            BossPhase::Frenzy => {
+                self.fire_cooldown = 0.1;
+                // Two arms rotating through the full circle
+                let angle = self.time * 220.;
+                shots.push((center, angle));
+                shots.push((center, angle + 180.));
+                if self.volley.is_multiple_of(8) {
+                    shots.push((center, aim_from(center)));
+                }
+            }
+        }
+        self.volley += 1;
+        shots
+    }
+
+    pub fn draw(&self, d: &mut RaylibDrawHandle) {
+        let center = rect_center(&self.rect);
+        // Hull outline behind the parts
+        d.draw_triangle_lines(
+            center + Vector2::new(-BOSS_WIDTH / 2., -BOSS_HEIGHT / 2.),
+            center + Vector2::new(0., BOSS_HEIGHT / 2.),
+            center + Vector2::new(BOSS_WIDTH / 2., -BOSS_HEIGHT / 2.),
+            Color::DARKGRAY,
+        );
+        for (part, shape) in self.parts.iter().zip(self.hitboxes_at(Vector2::zero())) {
+            let color = part.color();
+            match shape {
+                Shape::Circle(c) => {
+                    if part.kind == PartKind::Core {
+                        d.draw_circle_v(c.center, c.radius * (0.8 + 0.1 * (self.time * 6.).sin()), color);
+                    }
+                    d.draw_circle_lines(c.center.x as i32, c.center.y as i32, c.radius, color);
+                    if part.kind == PartKind::Turret {
+                        let barrel = (self.target - c.center).normalized() * (c.radius + 8.);
+                        d.draw_line_ex(c.center, c.center + barrel, 3., color);
+                    }
+                }
+                Shape::Polygon(points) => {
+                    for k in 0..points.len() {
+                        d.draw_line_ex(points[k], points[(k + 1) % points.len()], 2., color);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bounds_cover_every_part() {
+        let boss = Boss::new(1);
+        let center = rect_center(&boss.rect);
+        let CollisionType::Circle(radius) = boss.collider.shape else {
+            panic!("bounds are a circle");
+        };
+        for shape in boss.hitboxes_at(Vector2::zero()) {
+            let farthest = match shape {
+                Shape::Circle(c) => c.center.distance_to(center) + c.radius,
+                Shape::Polygon(points) => points.iter().map(|p| p.distance_to(center)).fold(0., f32::max),
+            };
+            assert!(farthest <= radius + 0.01, "{farthest} is outside {radius}");
+        }
+    }
+
+    #[test]
+    fn losing_the_core_destroys_the_boss() {
+        let mut boss = Boss::new(1);
+        let armor = boss.parts.iter().position(|p| p.kind == PartKind::Armor).unwrap();
+        assert!(matches!(boss.hit(armor, 24), BossHit::Damaged));
+        assert!(matches!(boss.hit(armor, 1), BossHit::PartDestroyed));
+        let core = boss.parts.iter().position(|p| p.kind == PartKind::Core).unwrap();
+        assert!(matches!(boss.hit(core, 1000), BossHit::Destroyed));
+    }
+
+    #[test]
+    fn health_bar_follows_the_core() {
+        let mut boss = Boss::new(1);
+        let turret = boss.parts.iter().position(|p| p.kind == PartKind::Turret).unwrap();
+        boss.hit(turret, 1000);
+        assert_eq!(boss.health(), 1.);
+
+        let core = boss.parts.iter().position(|p| p.kind == PartKind::Core).unwrap();
+        boss.hit(core, 30);
+        assert_eq!(boss.health(), 0.5);
+        boss.hit(core, 30);
+        assert_eq!(boss.health(), 0.);
+    }
+}
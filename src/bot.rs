@@ -0,0 +1,507 @@
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, ErrorKind, Write};
+use std::iter::Peekable;
+use std::net::TcpListener;
+use std::str::Chars;
+
+use raylib::prelude::*;
+
+use crate::{clamp_controls, Action, Entity, Input, PowerUp, World, SCREEN_HEIGHT, SCREEN_WIDTH, TICK, WEAPONS};
+
+// Remote control for bots and tooling. Every tick the game writes the world as one line of
+// JSON and waits for one line with what the bot wants to do before it steps, over a local
+// TCP socket or the game's own stdin and stdout. The protocol is in docs/bot.md.
+pub const BOT_PORT: u16 = 7979;
+
+// Just enough JSON for the commands bots send
+#[derive(Debug, Clone, PartialEq)]
+enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn parse(text: &str) -> Result<Self, String> {
+        let mut chars = text.chars().peekable();
+        let value = Self::value(&mut chars)?;
+        skip_space(&mut chars);
+        match chars.next() {
+            None => Ok(value),
+            Some(c) => Err(format!("unexpected '{c}' after the value")),
+        }
+    }
+
+    fn value(chars: &mut Peekable<Chars>) -> Result<Self, String> {
+        skip_space(chars);
+        match chars.peek().copied() {
+            Some('{') => {
+                chars.next();
+                let mut fields = vec![];
+                skip_space(chars);
+                if chars.next_if_eq(&'}').is_some() {
+                    return Ok(Self::Object(fields));
+                }
+                loop {
+                    skip_space(chars);
+                    let Self::String(key) = Self::value(chars)? else {
+                        return Err("object keys have to be strings".to_string());
+                    };
+                    skip_space(chars);
+                    if chars.next() != Some(':') {
+                        return Err(format!("expected ':' after \"{key}\""));
+                    }
+                    fields.push((key, Self::value(chars)?));
+                    skip_space(chars);
+                    match chars.next() {
+                        Some(',') => continue,
+                        Some('}') => return Ok(Self::Object(fields)),
+                        _ => return Err("expected ',' or '}' in an object".to_string()),
+                    }
+                }
+            }
+            Some('[') => {
+                chars.next();
+                let mut items = vec![];
+                skip_space(chars);
+                if chars.next_if_eq(&']').is_some() {
+                    return Ok(Self::Array(items));
+                }
+                loop {
+                    items.push(Self::value(chars)?);
+                    skip_space(chars);
+                    match chars.next() {
+                        Some(',') => continue,
+                        Some(']') => return Ok(Self::Array(items)),
+                        _ => return Err("expected ',' or ']' in an array".to_string()),
+                    }
+                }
+            }
+            Some('"') => {
+                chars.next();
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => return Ok(Self::String(text)),
+                        Some('\\') => match chars.next() {
+                            Some('n') => text.push('\n'),
+                            Some('t') => text.push('\t'),
+                            Some('r') => text.push('\r'),
+                            Some('b') => text.push('\u{8}'),
+                            Some('f') => text.push('\u{c}'),
+                            Some('u') => {
+                                let code: String = chars.by_ref().take(4).collect();
+                                let c = u32::from_str_radix(&code, 16).ok().and_then(char::from_u32);
+                                text.push(c.ok_or(format!("bad escape \\u{code}"))?);
+                            }
+                            Some(c @ ('"' | '\\' | '/')) => text.push(c),
+                            Some(c) => return Err(format!("bad escape \\{c}")),
+                            None => return Err("unterminated string".to_string()),
+                        },
+                        Some(c) => text.push(c),
+                        None => return Err("unterminated string".to_string()),
+                    }
+                }
+            }
+            Some(c) if c == '-' || c.is_ascii_digit() => {
+                let mut number = String::new();
+                while let Some(c) = chars.next_if(|c| c.is_ascii_digit() || "+-.eE".contains(*c)) {
+                    number.push(c);
+                }
+                match number.parse::<f64>() {
+                    Ok(value) if value.is_finite() => Ok(Self::Number(value)),
+                    _ => Err(format!("bad number {number}")),
+                }
+            }
+            Some(_) => {
+                let mut word = String::new();
+                while let Some(c) = chars.next_if(char::is_ascii_alphabetic) {
+                    word.push(c);
+                }
+                match word.as_str() {
+                    "true" => Ok(Self::Bool(true)),
+                    "false" => Ok(Self::Bool(false)),
+                    "null" => Ok(Self::Null),
+                    _ => Err(format!("unexpected '{word}'")),
+                }
+            }
+            None => Err("unexpected end of line".to_string()),
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Self::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    // `[x, y]`
+    fn as_vector(&self) -> Option<Vector2> {
+        match self {
+            Self::Array(items) => match items.as_slice() {
+                [Self::Number(x), Self::Number(y)] => Some(Vector2::new(*x as f32, *y as f32)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+fn skip_space(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+// `text` as a JSON string, quotes included
+fn quote(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// Top left corner, size and velocity, how every moving thing is described to the bot
+fn body(out: &mut String, rect: &Rectangle, velocity: Vector2) {
+    let _ = write!(
+        out,
+        "\"x\":{:.2},\"y\":{:.2},\"width\":{:.2},\"height\":{:.2},\"vx\":{:.2},\"vy\":{:.2}",
+        rect.x, rect.y, rect.width, rect.height, velocity.x, velocity.y
+    );
+}
+
+// One line of JSON with everything a bot gets to see of the world
+pub fn observe(world: &World, tick: u32, over: bool, error: Option<&str>) -> String {
+    let mut out = String::new();
+    let _ = write!(out, "{{\"tick\":{tick},\"wave\":{},\"over\":{over}", world.waves.wave.number);
+    if let Some(error) = error {
+        let _ = write!(out, ",\"error\":{}", quote(error));
+    }
+    let scores: Vec<String> = world.scoring.iter().map(|s| s.total().to_string()).collect();
+    let _ = write!(out, ",\"scores\":[{}]", scores.join(","));
+
+    let mut groups: [(&str, Vec<String>); 6] = [
+        ("players", vec![]),
+        ("asteroids", vec![]),
+        ("ships", vec![]),
+        ("bosses", vec![]),
+        ("shots", vec![]),
+        ("pickups", vec![]),
+    ];
+    for (id, entity) in &world.entities {
+        let mut item = format!("{{\"id\":{id},");
+        let group = match entity {
+            Entity::Player(p) => {
+                body(&mut item, &p.rect, entity.velocity());
+                let _ = write!(
+                    item,
+                    ",\"slot\":{},\"lives\":{},\"shield\":{},\"weapon\":{},\"fire_cooldown\":{:.3},\"missiles\":{},\"bombs\":{}",
+                    p.slot, p.lives, p.effects.has(PowerUp::Shield), quote(WEAPONS[p.weapon].name),
+                    p.fire_cooldown.max(0.), p.missiles, p.bombs
+                );
+                0
+            }
+            Entity::Enemy(a) => {
+                body(&mut item, &a.rect, entity.velocity());
+                1
+            }
+            Entity::EnemyShip(s) => {
+                body(&mut item, &s.rect, entity.velocity());
+                2
+            }
+            Entity::Boss(b) => {
+                body(&mut item, &b.rect, entity.velocity());
+                let _ = write!(item, ",\"health\":{:.3}", b.health());
+                3
+            }
+            Entity::Projectile(l) => {
+                body(&mut item, &l.rect, entity.velocity());
+                let _ = write!(item, ",\"hostile\":{}", l.is_hostile());
+                4
+            }
+            Entity::Pickup(p) => {
+                body(&mut item, &p.rect, entity.velocity());
+                let _ = write!(item, ",\"power_up\":{}", quote(p.power_up.name()));
+                5
+            }
+            _ => continue,
+        };
+        item.push('}');
+        groups[group].1.push(item);
+    }
+    for (name, items) in groups {
+        let _ = write!(out, ",\"{name}\":[{}]", items.join(","));
+    }
+    out.push('}');
+    out
+}
+
+type Link = (Box<dyn BufRead>, Box<dyn Write>);
+
+// The game's end of the bot connection
+pub struct Bot {
+    listener: Option<TcpListener>,
+    link: Option<Link>,
+    // Actions held on the last tick, holding one that was not counts as pressing it
+    held: HashSet<Action>,
+    // What was wrong with the last command, reported with the next state
+    error: Option<String>,
+    // Ticks the bot played so far
+    tick: u32,
+}
+
+impl Bot {
+    pub fn stdio() -> Self {
+        let link: Link = (Box::new(BufReader::new(io::stdin())), Box::new(io::stdout()));
+        let mut bot = Self { listener: None, link: Some(link), held: HashSet::new(), error: None, tick: 0 };
+        bot.greet();
+        bot
+    }
+
+    // Waits for a bot to connect to this port
+    pub fn listen(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        listener.set_nonblocking(true)?;
+        Ok(Self { listener: Some(listener), link: None, held: HashSet::new(), error: None, tick: 0 })
+    }
+
+    pub fn port(&self) -> Option<u16> {
+        self.listener.as_ref()?.local_addr().ok().map(|a| a.port())
+    }
+
+    // True once a bot is there
+    pub fn accept(&mut self) -> bool {
+        if self.link.is_some() {
+            return true;
+        }
+        let Some(Ok((stream, _))) = self.listener.as_ref().map(TcpListener::accept) else {
+            return false;
+        };
+        let reader = stream.try_clone().and_then(|reader| {
+            stream.set_nonblocking(false)?;
+            Ok(reader)
+        });
+        match reader {
+            Ok(reader) => {
+                self.link = Some((Box::new(BufReader::new(reader)), Box::new(stream)));
+                self.greet();
+                true
+            }
+            Err(err) => {
+                eprintln!("Could not talk to the bot: {err}");
+                false
+            }
+        }
+    }
+
+    // First line a bot gets, what it may send and the size of the playfield
+    fn greet(&mut self) {
+        let actions: Vec<String> = Action::GAMEPLAY.iter().map(|a| quote(a.name())).collect();
+        let hello = format!(
+            "{{\"game\":\"asteroids\",\"actions\":[{}],\"width\":{},\"height\":{},\"tick\":{TICK}}}",
+            actions.join(","), SCREEN_WIDTH!(), SCREEN_HEIGHT!()
+        );
+        if let Some((_, writer)) = self.link.as_mut()
+            && let Err(err) = writeln!(writer, "{hello}").and_then(|_| writer.flush())
+        {
+            eprintln!("Could not talk to the bot: {err}");
+            self.link = None;
+        }
+    }
+
+    // Sends the world and waits for the bot's command for the next tick. An error means
+    // the bot is gone.
+    pub fn exchange(&mut self, world: &World) -> io::Result<Input> {
+        let Some((reader, writer)) = self.link.as_mut() else {
+            return Err(ErrorKind::NotConnected.into());
+        };
+        writeln!(writer, "{}", observe(world, self.tick, false, self.error.as_deref()))?;
+        writer.flush()?;
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+        let command = Json::parse(line.trim()).and_then(|json| self.command(&json));
+        self.error = command.as_ref().err().cloned();
+        self.tick += 1;
+        Ok(command.unwrap_or_default())
+    }
+
+    // Last state with `"over": true`, then the game hangs up
+    pub fn finish(&mut self, world: &World) {
+        if let Some((_, mut writer)) = self.link.take() {
+            let sent = writeln!(writer, "{}", observe(world, self.tick, true, None)).and_then(|_| writer.flush());
+            if let Err(err) = sent {
+                eprintln!("Could not talk to the bot: {err}");
+            }
+        }
+    }
+
+    // `{"actions": ["fire", "move_left"], "move": [x, y], "target": [x, y]}`, every field
+    // optional. `move` overrides the direction the move actions give.
+    fn command(&mut self, json: &Json) -> Result<Input, String> {
+        let mut held = HashSet::new();
+        match json.get("actions") {
+            Some(Json::Array(items)) => {
+                for item in items {
+                    let Json::String(name) = item else {
+                        return Err("actions has to be a list of names".to_string());
+                    };
+                    let action = Action::parse(name).filter(|a| Action::GAMEPLAY.contains(a));
+                    held.insert(action.ok_or(format!("unknown action \"{name}\""))?);
+                }
+            }
+            None | Some(Json::Null) => (),
+            Some(_) => return Err("actions has to be a list of names".to_string()),
+        }
+
+        let mut input = Input::from_held(&held, &self.held);
+        if let Some(value) = json.get("move") {
+            input.movement = value.as_vector().ok_or("move has to be [x, y]")?;
+        }
+        let target = match json.get("target") {
+            Some(Json::Null) | None => None,
+            Some(value) => Some(value.as_vector().ok_or("target has to be [x, y]")?),
+        };
+        // Numbers past what an f32 holds end up infinite, the same checks as for network
+        // players keep them out of the world
+        (input.movement, input.target) = clamp_controls(input.movement, target)
+            .ok_or("move and target have to be finite")?;
+        self.held = held;
+        Ok(input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bot() -> Bot {
+        Bot { listener: None, link: None, held: HashSet::new(), error: None, tick: 0 }
+    }
+
+    fn command(bot: &mut Bot, line: &str) -> Result<Input, String> {
+        Json::parse(line).and_then(|json| bot.command(&json))
+    }
+
+    #[test]
+    fn parses_nested_objects_and_arrays() {
+        let json = Json::parse(r#" {"a": [1, true, null, {"b": "c"}], "d": {}, "e": []} "#).unwrap();
+        assert_eq!(
+            json,
+            Json::Object(vec![
+                ("a".to_string(), Json::Array(vec![
+                    Json::Number(1.),
+                    Json::Bool(true),
+                    Json::Null,
+                    Json::Object(vec![("b".to_string(), Json::String("c".to_string()))]),
+                ])),
+                ("d".to_string(), Json::Object(vec![])),
+                ("e".to_string(), Json::Array(vec![])),
+            ])
+        );
+        assert_eq!(json.get("d"), Some(&Json::Object(vec![])));
+        assert_eq!(json.get("missing"), None);
+    }
+
+    #[test]
+    fn parses_numbers() {
+        assert_eq!(Json::parse("-12.5"), Ok(Json::Number(-12.5)));
+        assert_eq!(Json::parse("3e2"), Ok(Json::Number(300.)));
+        assert_eq!(Json::parse("0"), Ok(Json::Number(0.)));
+        assert!(Json::parse("1e999").is_err());
+        assert!(Json::parse("1.2.3").is_err());
+        assert!(Json::parse("-").is_err());
+    }
+
+    #[test]
+    fn parses_string_escapes() {
+        let json = Json::parse(r#""a\"b\\c\/d\n\t\r\u00e9""#).unwrap();
+        assert_eq!(json, Json::String("a\"b\\c/d\n\t\r\u{e9}".to_string()));
+        assert!(Json::parse(r#""\x""#).is_err());
+        assert!(Json::parse(r#""\u12""#).is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        for line in ["", "{", "[1,", "[1 2]", "{\"a\" 1}", "{1: 2}", "\"open", "nope", "{} {}", "[1,]"] {
+            assert!(Json::parse(line).is_err(), "{line:?} parsed");
+        }
+    }
+
+    #[test]
+    fn quoted_strings_parse_back_the_same() {
+        for text in ["plain", "say \"hi\"", "back\\slash", "line\nbreak\ttab", "bell\u{7}", "é"] {
+            let quoted = quote(text);
+            assert!(!quoted[1..quoted.len() - 1].chars().any(char::is_control));
+            assert_eq!(Json::parse(&quoted), Ok(Json::String(text.to_string())));
+        }
+    }
+
+    #[test]
+    fn error_from_a_bot_stays_valid_json() {
+        let mut bot = bot();
+        let error = command(&mut bot, r#"{"actions": ["fire\"}, \"x\": [\n"]}"#).unwrap_err();
+        let line = observe(&World::new(0), 0, false, Some(&error));
+        let json = Json::parse(&line).unwrap();
+        assert_eq!(json.get("error"), Some(&Json::String(error)));
+    }
+
+    #[test]
+    fn holding_an_action_presses_it_once() {
+        let mut bot = bot();
+        let first = command(&mut bot, r#"{"actions": ["fire", "move_left"]}"#).unwrap();
+        assert!(first.down(Action::Fire) && first.pressed(Action::Fire));
+        assert!(first.movement.x < 0.);
+
+        let second = command(&mut bot, r#"{"actions": ["fire"]}"#).unwrap();
+        assert!(second.down(Action::Fire) && !second.pressed(Action::Fire));
+        assert!(!second.down(Action::MoveLeft));
+
+        let released = command(&mut bot, r#"{}"#).unwrap();
+        assert!(!released.down(Action::Fire));
+        let again = command(&mut bot, r#"{"actions": ["fire"]}"#).unwrap();
+        assert!(again.pressed(Action::Fire));
+    }
+
+    #[test]
+    fn move_overrides_the_move_actions() {
+        let mut bot = bot();
+        let input = command(&mut bot, r#"{"actions": ["move_left"], "move": [3, -0.5], "target": [10, 20]}"#).unwrap();
+        assert_eq!(input.movement, Vector2::new(1., -0.5));
+        assert_eq!(input.target, Some(Vector2::new(10., 20.)));
+    }
+
+    #[test]
+    fn bad_commands_are_rejected() {
+        let mut bot = bot();
+        assert!(command(&mut bot, r#"{"actions": ["dance"]}"#).is_err());
+        assert!(command(&mut bot, r#"{"actions": ["pause"]}"#).is_err());
+        assert!(command(&mut bot, r#"{"actions": "fire"}"#).is_err());
+        assert!(command(&mut bot, r#"{"move": [1]}"#).is_err());
+        assert!(command(&mut bot, r#"{"target": "here"}"#).is_err());
+        assert!(command(&mut bot, r#"{"target": [1e39, 0]}"#).is_err());
+        assert!(command(&mut bot, r#"{"move": [0, -1e39]}"#).is_err());
+        let input = command(&mut bot, r#"{"target": [-20, 1e9]}"#).unwrap();
+        assert_eq!(input.target, Some(Vector2::new(0., SCREEN_HEIGHT!())));
+        // A rejected command does not count as holding anything
+        let input = command(&mut bot, r#"{"actions": ["fire"]}"#).unwrap();
+        assert!(input.pressed(Action::Fire));
+    }
+}
@@ -1,12 +1,90 @@
 use raylib::prelude::*;
 
-#[derive(Debug)]
+use crate::Transformable;
+
+// Collider shapes in local space, centered on the owning entity
+#[derive(Debug, Clone)]
 pub enum CollisionType {
-    Circle,
-    Rectangle,
-    Triangle,
+    Circle(f32),
+    Rectangle(f32, f32),
+    Triangle(Vector2, Vector2, Vector2),
+    // Convex outline, clockwise or counter-clockwise
+    Polygon(Vec<Vector2>),
+}
+
+// Collision layers, one bit per kind of collider
+pub const LAYER_PLAYER: u32 = 1 << 0;
+pub const LAYER_ASTEROID: u32 = 1 << 1;
+pub const LAYER_PLAYER_PROJECTILE: u32 = 1 << 2;
+pub const LAYER_PICKUP: u32 = 1 << 3;
+pub const LAYER_ENEMY_SHIP: u32 = 1 << 4;
+pub const LAYER_ENEMY_PROJECTILE: u32 = 1 << 5;
+pub const LAYER_SHOCKWAVE: u32 = 1 << 6;
+
+#[derive(Debug, Clone)]
+pub struct Collider {
+    pub shape: CollisionType,
+    // Shape center relative to the entity center, before rotation
+    pub offset: Vector2,
+    // Layers this collider is on
+    pub layer: u32,
+    // Layers this collider collides with
+    pub mask: u32,
+}
+
+impl Collider {
+    pub fn new(shape: CollisionType) -> Self {
+        Self { shape, offset: Vector2::zero(), layer: 0, mask: 0 }
+    }
+
+    pub fn with_offset(mut self, offset: Vector2) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub fn with_layer(mut self, layer: u32, mask: u32) -> Self {
+        self.layer = layer;
+        self.mask = mask;
+        self
+    }
+
+    // Broad filter run before any shape test, either side may ask for the contact
+    pub fn can_collide(&self, other: &Collider) -> bool {
+        self.layer & other.mask != 0 || other.layer & self.mask != 0
+    }
+
+    // Place the collider in the world, `rotation` is in degrees around `position`
+    pub fn shape_at(&self, position: Vector2, rotation: f32) -> Shape {
+        let angle = rotation.to_radians();
+        let place = |p: Vector2| position + (self.offset + p).rotated(angle);
+        match &self.shape {
+            CollisionType::Circle(radius) => Shape::Circle(Circle {
+                center: place(Vector2::zero()),
+                radius: *radius,
+            }),
+            CollisionType::Rectangle(width, height) => {
+                let (w, h) = (width / 2., height / 2.);
+                Shape::Polygon(vec![
+                    place(Vector2::new(-w, -h)),
+                    place(Vector2::new(w, -h)),
+                    place(Vector2::new(w, h)),
+                    place(Vector2::new(-w, h)),
+                ])
+            }
+            CollisionType::Triangle(a, b, c) => Shape::Polygon(vec![place(*a), place(*b), place(*c)]),
+            CollisionType::Polygon(points) => Shape::Polygon(points.iter().map(|p| place(*p)).collect()),
+        }
+    }
 }
 
+// Collider placed in world space, what the narrow phase works on
+#[derive(Clone)]
+pub enum Shape {
+    Circle(Circle),
+    Polygon(Vec<Vector2>),
+}
+
+#[derive(Clone)]
 pub struct Circle {
     pub center: Vector2,
     pub radius: f32,    
@@ -21,28 +99,29 @@ impl Circle {
     }
 }
 
-pub struct Triangle {
-    pub a: Vector2,
-    pub b: Vector2,
-    pub c: Vector2
+// Contact manifold of two overlapping shapes
+#[derive(Debug, Clone, Copy)]
+pub struct Contact {
+    // Unit vector pointing from the first shape towards the second
+    pub normal: Vector2,
+    // How far the shapes overlap along the normal
+    pub depth: f32,
+    pub point: Vector2,
+    // Fraction of the step at which the shapes first touched, 1 for overlap tests
+    pub time: f32,
+    // Index of the touching part on each side, always 0 for single colliders
+    pub parts: [usize; 2],
 }
 
-impl Triangle {
-    pub fn from_rect(rect: &Rectangle) -> Self {
-        Self {
-            a: Vector2::new(rect.x, rect.y+rect.height),
-            b: Vector2::new(rect.x+rect.width, rect.y+rect.height),
-            c: Vector2::new(rect.x+rect.width/2., rect.y)        
-        }
+impl Contact {
+    // Same contact seen from the second shape
+    pub fn flipped(self) -> Self {
+        Self { normal: -self.normal, parts: [self.parts[1], self.parts[0]], ..self }
     }
+}
 
-    pub fn edges(&self) -> [(Vector2, Vector2); 3] {
-        [
-            (self.a, self.b),
-            (self.b, self.c),
-            (self.c, self.a),
-        ]
-    }
+pub fn rect_center(rect: &Rectangle) -> Vector2 {
+    Vector2::new(rect.x + rect.width / 2., rect.y + rect.height / 2.)
 }
 
 // Helper function to calculate distance squared between points
@@ -52,40 +131,79 @@ fn distance_squared(a: Vector2, b: Vector2) -> f32 {
     dx * dx + dy * dy
 }
 
-// Helper function to calculate distance from point to line segment
-fn point_to_line_segment_distance(p: Vector2, a: Vector2, b: Vector2) -> f32 {
+// Helper function to find the point on a line segment closest to a point
+fn closest_point_on_segment(p: Vector2, a: Vector2, b: Vector2) -> Vector2 {
     let l2 = distance_squared(a, b);
     if l2 == 0.0 {
-        return distance_squared(p, a).sqrt();
+        return a;
     }
     
     let t = ((p.x - a.x) * (b.x - a.x) + (p.y - a.y) * (b.y - a.y)) / l2;
     let t = t.clamp(0.0, 1.0);
     
-    let projection = Vector2 {
+    Vector2 {
         x: a.x + t * (b.x - a.x),
         y: a.y + t * (b.y - a.y),
-    };
-    
-    distance_squared(p, projection).sqrt()
+    }
 }
 
-// Check if point is inside triangle (using barycentric coordinates)
-fn point_in_triangle(p: Vector2, tri: &Triangle) -> bool {
+// Check if point is inside a convex polygon (same side of every edge)
+fn point_in_polygon(p: Vector2, poly: &[Vector2]) -> bool {
     fn sign(a: Vector2, b: Vector2, c: Vector2) -> f32 {
         (a.x - c.x) * (b.y - c.y) - (b.x - c.x) * (a.y - c.y)
     }
     
-    let d1 = sign(p, tri.a, tri.b);
-    let d2 = sign(p, tri.b, tri.c);
-    let d3 = sign(p, tri.c, tri.a);
-    
-    let has_neg = (d1 < 0.0) || (d2 < 0.0) || (d3 < 0.0);
-    let has_pos = (d1 > 0.0) || (d2 > 0.0) || (d3 > 0.0);
+    let mut has_neg = false;
+    let mut has_pos = false;
+    for k in 0..poly.len() {
+        let d = sign(p, poly[k], poly[(k + 1) % poly.len()]);
+        has_neg |= d < 0.0;
+        has_pos |= d > 0.0;
+    }
     
     !(has_neg && has_pos)
 }
 
+// Average of the polygon vertices, good enough to tell sides apart
+fn centroid(poly: &[Vector2]) -> Vector2 {
+    let sum = poly.iter().fold(Vector2::zero(), |acc, p| acc + *p);
+    sum / poly.len() as f32
+}
+
+// Convex hull of a point cloud (monotone chain), collinear points are dropped
+pub fn convex_hull(points: &[Vector2]) -> Vec<Vector2> {
+    fn cross(o: Vector2, a: Vector2, b: Vector2) -> f32 {
+        (a.x - o.x) * (b.y - o.y) - (a.y - o.y) * (b.x - o.x)
+    }
+
+    let mut sorted = points.to_vec();
+    sorted.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+    sorted.dedup();
+    if sorted.len() < 3 {
+        return sorted;
+    }
+
+    let mut hull: Vec<Vector2> = Vec::with_capacity(sorted.len() * 2);
+    // Lower hull, then upper hull walking back
+    for pass in 0..2 {
+        let start = hull.len();
+        let iter: Box<dyn Iterator<Item = &Vector2>> = if pass == 0 {
+            Box::new(sorted.iter())
+        } else {
+            Box::new(sorted.iter().rev())
+        };
+        for p in iter {
+            while hull.len() >= start + 2 && cross(hull[hull.len() - 2], hull[hull.len() - 1], *p) <= 0. {
+                hull.pop();
+            }
+            hull.push(*p);
+        }
+        // The last point is the first point of the other half
+        hull.pop();
+    }
+    hull
+}
+
 // Helper function to project a polygon onto an axis
 fn project(poly: &[Vector2], axis: Vector2) -> (f32, f32) {
     let mut min = axis.x * poly[0].x + axis.y * poly[0].y;
@@ -121,139 +239,507 @@ fn perpendicular(p1: Vector2, p2: Vector2) -> Vector2 {
     }
 }
 
-fn rect_to_poly(rect: &Rectangle) -> [Vector2; 4] {
-    [
-        Vector2 {
-            x: rect.x,
-            y: rect.y,
-        },
-        Vector2 {
-            x: rect.x + rect.width,
-            y: rect.y,
-        },
-        Vector2 {
-            x: rect.x + rect.width,
-            y: rect.y + rect.height,
-        },
-        Vector2 {
-            x: rect.x,
-            y: rect.y + rect.height,
-        },
-    ]
-}
+// Shared SAT test for two convex polygons. Tracks the axis with the smallest overlap,
+// which is the direction the shapes have to be pushed apart in.
+fn collide_polygons(a: &[Vector2], b: &[Vector2]) -> Option<Contact> {
+    let mut depth = f32::INFINITY;
+    let mut normal = Vector2::zero();
 
-pub fn check_collision_rect_triangle(rect: &Rectangle, tri: &Triangle) -> bool {
-    let rect_poly = rect_to_poly(rect);
-    let tri_poly = [tri.a, tri.b, tri.c];
-    
-    // Check rectangle axes
-    let rect_edges = [
-        (rect_poly[0], rect_poly[1]),
-        (rect_poly[1], rect_poly[2]),
-    ];
-    
-    for edge in &rect_edges {
-        let axis = perpendicular(edge.0, edge.1);
-        let axis_normalized = {
-            let len = (axis.x * axis.x + axis.y * axis.y).sqrt();
-            Vector2 {
-                x: axis.x / len,
-                y: axis.y / len,
+    for poly in [a, b] {
+        for k in 0..poly.len() {
+            let axis = perpendicular(poly[k], poly[(k + 1) % poly.len()]);
+            let len = axis.length();
+            if len == 0. {
+                continue;
+            }
+            let axis = axis / len;
+
+            let a_proj = project(a, axis);
+            let b_proj = project(b, axis);
+            if !overlaps(a_proj, b_proj) {
+                return None;
+            }
+
+            let overlap = (a_proj.1 - b_proj.0).min(b_proj.1 - a_proj.0);
+            if overlap < depth {
+                depth = overlap;
+                normal = axis;
             }
+        }
+    }
+
+    // Orient the normal from `a` towards `b`
+    if (centroid(b) - centroid(a)).dot(normal) < 0. {
+        normal = -normal;
+    }
+
+    // The vertex of `b` reaching deepest into `a` is taken as the contact point
+    let point = *b.iter()
+        .min_by(|p, q| p.dot(normal).total_cmp(&q.dot(normal)))
+        .unwrap();
+
+    Some(Contact { normal, depth, point, time: 1., parts: [0, 0] })
+}
+
+// Closest point on the polygon outline
+fn poly_closest_point(p: Vector2, poly: &[Vector2]) -> Vector2 {
+    let mut closest = poly[0];
+    let mut closest_distance = f32::INFINITY;
+    for k in 0..poly.len() {
+        let (a, b) = (poly[k], poly[(k + 1) % poly.len()]);
+        let point = closest_point_on_segment(p, a, b);
+        let distance = distance_squared(p, point);
+        if distance < closest_distance {
+            closest_distance = distance;
+            closest = point;
+        }
+    }
+    closest
+}
+
+fn collide_circle_polygon(circle: &Circle, poly: &[Vector2]) -> Option<Contact> {
+    let closest = poly_closest_point(circle.center, poly);
+    let closest_distance = distance_squared(circle.center, closest).sqrt();
+
+    if point_in_polygon(circle.center, poly) {
+        // Center is inside, the circle has to be pushed out through the nearest edge
+        let normal = if closest_distance > 0. {
+            (circle.center - closest) / closest_distance
+        } else {
+            (centroid(poly) - circle.center).normalized()
         };
-        
-        let rect_proj = project(&rect_poly, axis_normalized);
-        let tri_proj = project(&tri_poly, axis_normalized);
-        
-        if !overlaps(rect_proj, tri_proj) {
-            return false;
+        return Some(Contact {
+            normal,
+            depth: circle.radius + closest_distance,
+            point: closest,
+            time: 1.,
+            parts: [0, 0],
+        });
+    }
+
+    if closest_distance > circle.radius {
+        return None;
+    }
+
+    Some(Contact {
+        normal: (closest - circle.center) / closest_distance,
+        depth: circle.radius - closest_distance,
+        point: closest,
+        time: 1.,
+        parts: [0, 0],
+    })
+}
+
+pub fn collide_circles(a: &Circle, b: &Circle) -> Option<Contact> {
+    let delta = b.center - a.center;
+    let distance = delta.length();
+    let depth = a.radius + b.radius - distance;
+    if depth < 0. {
+        return None;
+    }
+    let normal = if distance > 0. { delta / distance } else { Vector2::new(0., 1.) };
+    Some(Contact {
+        normal,
+        depth,
+        point: a.center + normal * (a.radius - depth / 2.),
+        time: 1.,
+        parts: [0, 0],
+    })
+}
+
+// Test any pair of shapes, the contact normal points from `a` towards `b`
+pub fn collide_shapes(a: &Shape, b: &Shape) -> Option<Contact> {
+    match (a, b) {
+        (Shape::Circle(c1), Shape::Circle(c2)) => collide_circles(c1, c2),
+        (Shape::Circle(c), Shape::Polygon(poly)) => collide_circle_polygon(c, poly),
+        (Shape::Polygon(poly), Shape::Circle(c)) => collide_circle_polygon(c, poly).map(Contact::flipped),
+        (Shape::Polygon(p1), Shape::Polygon(p2)) => collide_polygons(p1, p2),
+    }
+}
+
+// Earliest t in [0, 1] at which a point moving from `origin` by `motion` is within `radius` of `center`
+fn ray_circle(origin: Vector2, motion: Vector2, center: Vector2, radius: f32) -> Option<f32> {
+    let m = origin - center;
+    let a = motion.dot(motion);
+    let c = m.dot(m) - radius * radius;
+    if c <= 0. {
+        return Some(0.);
+    }
+    if a == 0. {
+        return None;
+    }
+    let b = m.dot(motion);
+    let discriminant = b * b - a * c;
+    if b > 0. || discriminant < 0. {
+        return None;
+    }
+    let t = (-b - discriminant.sqrt()) / a;
+    (t <= 1.).then_some(t)
+}
+
+// Earliest t in [0, 1] at which a moving point crosses the segment `a`-`b`
+fn ray_segment(origin: Vector2, motion: Vector2, a: Vector2, b: Vector2) -> Option<f32> {
+    let edge = b - a;
+    let denom = motion.x * edge.y - motion.y * edge.x;
+    if denom == 0. {
+        return None;
+    }
+    let diff = a - origin;
+    let t = (diff.x * edge.y - diff.y * edge.x) / denom;
+    let u = (diff.x * motion.y - diff.y * motion.x) / denom;
+    ((0. ..=1.).contains(&t) && (0. ..=1.).contains(&u)).then_some(t)
+}
+
+// Sweep a circle against a static convex polygon, which is a ray cast against the
+// polygon grown by the radius: edges pushed outwards plus a disc at every vertex
+fn sweep_circle_polygon(circle: &Circle, motion: Vector2, poly: &[Vector2]) -> Option<(f32, Vector2)> {
+    if collide_circle_polygon(circle, poly).is_some() {
+        return Some((0., circle.center));
+    }
+
+    let center = centroid(poly);
+    let mut time: Option<f32> = None;
+    let mut earliest = |t: Option<f32>| {
+        if let Some(t) = t {
+            time = Some(time.map_or(t, |time| time.min(t)));
+        }
+    };
+    for k in 0..poly.len() {
+        let (a, b) = (poly[k], poly[(k + 1) % poly.len()]);
+        let mut normal = perpendicular(a, b).normalized();
+        if normal.dot(a - center) < 0. {
+            normal = -normal;
         }
+        let offset = normal * circle.radius;
+        earliest(ray_segment(circle.center, motion, a + offset, b + offset));
+        earliest(ray_circle(circle.center, motion, a, circle.radius));
     }
-    
-    // Check triangle axes
-    for edge in tri.edges() {
-        let axis = perpendicular(edge.0, edge.1);
-        let axis_normalized = {
-            let len = (axis.x * axis.x + axis.y * axis.y).sqrt();
-            Vector2 {
-                x: axis.x / len,
-                y: axis.y / len,
+    time.map(|t| (t, circle.center + motion * t))
+}
+
+// Sweep `b` moving by `motion` against a static `a`, both convex. Along every edge normal
+// the projections overlap for a window of time, the shapes touch while all windows are
+// open. Gives the time of impact and the axis that closed last, oriented from `a` to `b`.
+fn sweep_polygons(a: &[Vector2], b: &[Vector2], motion: Vector2) -> Option<(f32, Vector2)> {
+    let (mut enter, mut exit) = (f32::NEG_INFINITY, f32::INFINITY);
+    let mut normal = Vector2::zero();
+    for poly in [a, b] {
+        for k in 0..poly.len() {
+            let axis = perpendicular(poly[k], poly[(k + 1) % poly.len()]);
+            let len = axis.length();
+            if len == 0. {
+                continue;
             }
-        };
-        
-        let rect_proj = project(&rect_poly, axis_normalized);
-        let tri_proj = project(&tri_poly, axis_normalized);
-        
-        if !overlaps(rect_proj, tri_proj) {
-            return false;
+            let axis = axis / len;
+            let (a_min, a_max) = project(a, axis);
+            let (b_min, b_max) = project(b, axis);
+            let speed = motion.dot(axis);
+            if speed == 0. {
+                if !overlaps((a_min, a_max), (b_min, b_max)) {
+                    return None;
+                }
+                continue;
+            }
+            let (t1, t2) = ((a_min - b_max) / speed, (a_max - b_min) / speed);
+            let (axis_enter, axis_exit) = (t1.min(t2), t1.max(t2));
+            if axis_enter > enter {
+                enter = axis_enter;
+                // `b` comes in from the side it is moving away from
+                normal = if speed < 0. { axis } else { -axis };
+            }
+            exit = exit.min(axis_exit);
         }
     }
-    
-    true
+    if enter > exit || enter > 1. || exit < 0. {
+        return None;
+    }
+    Some((enter.max(0.), normal))
 }
 
-pub fn check_collision_circle_triangle(circle: &Circle, tri: &Triangle) -> bool {
-    // Check if circle center is inside the triangle
-    if point_in_triangle(circle.center, tri) {
-        return true;
+// Swept test for shapes moving linearly by `a_motion` and `b_motion` over the step,
+// starting from the given positions. Catches fast movers that an overlap test at the
+// end of the step would tunnel through.
+pub fn sweep_shapes(a: &Shape, a_motion: Vector2, b: &Shape, b_motion: Vector2) -> Option<Contact> {
+    // Work in the frame of `a`, only the relative motion matters
+    let motion = b_motion - a_motion;
+    match (a, b) {
+        (Shape::Circle(c1), Shape::Circle(c2)) => {
+            let t = ray_circle(c2.center, motion, c1.center, c1.radius + c2.radius)?;
+            let c1 = Circle { center: c1.center + a_motion * t, radius: c1.radius };
+            let c2 = Circle { center: c2.center + b_motion * t, radius: c2.radius };
+            collide_circles(&c1, &c2).map(|contact| Contact { time: t, ..contact })
+        }
+        (Shape::Polygon(poly), Shape::Circle(c)) => {
+            let (t, center) = sweep_circle_polygon(c, motion, poly)?;
+            let point = poly_closest_point(center, poly);
+            let normal = (center - point).normalized();
+            Some(Contact {
+                normal,
+                depth: (c.radius - center.distance_to(point)).max(0.),
+                point: point + a_motion * t,
+                time: t,
+                parts: [0, 0],
+            })
+        }
+        (Shape::Circle(_), Shape::Polygon(_)) => {
+            sweep_shapes(b, b_motion, a, a_motion).map(Contact::flipped)
+        }
+        (Shape::Polygon(p1), Shape::Polygon(p2)) => {
+            let (t, normal) = sweep_polygons(p1, p2, motion)?;
+            // Where the shapes are at the time of impact, touching or already overlapping
+            let p1: Vec<Vector2> = p1.iter().map(|p| *p + a_motion * t).collect();
+            let p2: Vec<Vector2> = p2.iter().map(|p| *p + b_motion * t).collect();
+            let contact = collide_polygons(&p1, &p2).unwrap_or(Contact {
+                normal,
+                depth: 0.,
+                point: poly_closest_point(centroid(&p2), &p1),
+                time: t,
+                parts: [0, 0],
+            });
+            Some(Contact { time: t, ..contact })
+        }
     }
-    
-    // Check distance to each edge
-    for edge in tri.edges() {
-        let distance = point_to_line_segment_distance(circle.center, edge.0, edge.1);
-        if distance <= circle.radius {
-            return true;
+}
+
+// Bodies made of several shapes moving as one, like the plates and turrets of a boss.
+// Single colliders are just compounds of one part. The contact records which part on
+// each side was touched.
+
+// Deepest overlap between any two parts
+pub fn collide_compound(a: &[Shape], b: &[Shape]) -> Option<Contact> {
+    let mut deepest: Option<Contact> = None;
+    for (i, part_a) in a.iter().enumerate() {
+        for (j, part_b) in b.iter().enumerate() {
+            if let Some(contact) = collide_shapes(part_a, part_b)
+                && deepest.is_none_or(|d| contact.depth > d.depth)
+            {
+                deepest = Some(Contact { parts: [i, j], ..contact });
+            }
         }
     }
-    
-    // Check distance to each vertex
-    let radius_sq = circle.radius * circle.radius;
-    if distance_squared(circle.center, tri.a) <= radius_sq ||
-       distance_squared(circle.center, tri.b) <= radius_sq ||
-       distance_squared(circle.center, tri.c) <= radius_sq {
-        return true;
+    deepest
+}
+
+// Earliest swept hit between any two parts
+pub fn sweep_compound(a: &[Shape], a_motion: Vector2, b: &[Shape], b_motion: Vector2) -> Option<Contact> {
+    let mut earliest: Option<Contact> = None;
+    for (i, part_a) in a.iter().enumerate() {
+        for (j, part_b) in b.iter().enumerate() {
+            if let Some(contact) = sweep_shapes(part_a, a_motion, part_b, b_motion)
+                && earliest.is_none_or(|e| contact.time < e.time)
+            {
+                earliest = Some(Contact { parts: [i, j], ..contact });
+            }
+        }
     }
-    
-    false
+    earliest
 }
 
-pub fn elastic_collision_1d(m1: f32, v1: f32, m2: f32, v2: f32) -> (f32, f32) {
-    if m1 == m2 {
-        return (v2, v1);
+// Fraction of the overlap corrected per step and the overlap left alone,
+// keeps resting contacts from jittering
+const POSITION_CORRECTION: f32 = 0.8;
+const PENETRATION_SLOP: f32 = 0.5;
+
+// Resolve a contact between two spinning discs: an impulse along the contact normal
+// scaled by `elasticity`, a friction impulse along the tangent that trades linear speed
+// for spin, and a positional correction so the bodies stop overlapping.
+// Rotation velocities are in degrees per second.
+pub fn colliding_circles_2d<A: Transformable, B: Transformable>(
+    a: &mut A, b: &mut B, contact: &Contact, elasticity: f32, friction: f32,
+) {
+    let r1 = Circle::from_rect(a.rect()).radius;
+    let r2 = Circle::from_rect(b.rect()).radius;
+    let (m1, m2) = (*a.mass(), *b.mass());
+    let normal = contact.normal;
+    let penetration = contact.depth;
+    let tangent = Vector2::new(-normal.y, normal.x);
+
+    // Moments of inertia (assuming solid discs, k=1/2)
+    let i1 = 0.5 * m1 * r1 * r1;
+    let i2 = 0.5 * m2 * r2 * r2;
+    let inv_m1 = 1. / m1;
+    let inv_m2 = 1. / m2;
+
+    // Push the bodies apart proportionally to their inverse masses
+    let correction = normal * ((penetration - PENETRATION_SLOP).max(0.) * POSITION_CORRECTION / (inv_m1 + inv_m2));
+    a.rect_mut().x -= correction.x * inv_m1;
+    a.rect_mut().y -= correction.y * inv_m1;
+    b.rect_mut().x += correction.x * inv_m2;
+    b.rect_mut().y += correction.y * inv_m2;
+
+    let w1 = a.rot_vel().to_radians();
+    let w2 = b.rot_vel().to_radians();
+
+    // Velocities of the surfaces at the contact point, the contact sits at
+    // r1 * normal from the first center and -r2 * normal from the second
+    let v1 = *a.vel() + tangent * (w1 * r1);
+    let v2 = *b.vel() - tangent * (w2 * r2);
+    let v_rel = v2 - v1;
+
+    let vn = v_rel.dot(normal);
+    if vn > 0. {
+        // Already separating
+        return;
     }
-    let total_mass = m1 + m2;
-    let v1_final = ((m1 - m2) * v1 + 2.0 * m2 * v2) / total_mass;
-    let v2_final = ((m2 - m1) * v2 + 2.0 * m1 * v1) / total_mass;
-    (v1_final, v2_final)
+    let jn = -(1. + elasticity) * vn / (inv_m1 + inv_m2);
+
+    // Friction impulse, limited by the Coulomb cone
+    let vt = v_rel.dot(tangent);
+    let k_t = inv_m1 + inv_m2 + r1 * r1 / i1 + r2 * r2 / i2;
+    let jt = (-vt / k_t).clamp(-friction * jn, friction * jn);
+
+    let impulse = normal * jn + tangent * jt;
+    *a.vel_mut() -= impulse * inv_m1;
+    *b.vel_mut() += impulse * inv_m2;
+    *a.rot_vel_mut() = (w1 - jt * r1 / i1).to_degrees();
+    *b.rot_vel_mut() = (w2 - jt * r2 / i2).to_degrees();
 }
 
-#[allow(clippy::too_many_arguments, dead_code)]
-fn colliding_circles_1d(
-    m1: f32, v1: f32, r1: f32, ω1: f32,
-    m2: f32, v2: f32, r2: f32, ω2: f32,
-    elasticity: f32
-) -> (f32, f32, f32, f32) {
-    // Moments of inertia (assuming solid spheres, k=2/5)
-    let i1 = 0.4 * m1 * r1 * r1;
-    let i2 = 0.4 * m2 * r2 * r2;
-    
-    // Effective masses accounting for rotation
-    let m_eff1 = 1.0 / (1.0/m1 + r1*r1/i1);
-    let m_eff2 = 1.0 / (1.0/m2 + r2*r2/i2);
-    
-    // Relative velocity at contact point
-    let v_rel = (v1 - r1 * ω1) - (v2 + r2 * ω2);
-    
-    // Impulse magnitude
-    let j = -(1.0 + elasticity) * v_rel / (1.0/m_eff1 + 1.0/m_eff2);
-    
-    // New linear velocities
-    let v1_new = v1 + j / m1;
-    let v2_new = v2 - j / m2;
-    
-    // New angular velocities
-    let ω1_new = ω1 - j * r1 / i1;
-    let ω2_new = ω2 + j * r2 / i2;
-    
-    (v1_new, ω1_new, v2_new, ω2_new)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(center: Vector2, half: f32) -> Vec<Vector2> {
+        vec![
+            center + Vector2::new(-half, -half),
+            center + Vector2::new(half, -half),
+            center + Vector2::new(half, half),
+            center + Vector2::new(-half, half),
+        ]
+    }
+
+    // Thin upright shot like a lazer, `center` is the middle of the rectangle
+    fn shot(center: Vector2) -> Vec<Vector2> {
+        let collider = Collider::new(CollisionType::Rectangle(2., 45.));
+        match collider.shape_at(center, 0.) {
+            Shape::Polygon(points) => points,
+            Shape::Circle(_) => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn convex_hull_keeps_only_the_outline() {
+        let mut points = square(Vector2::new(0., 0.), 10.);
+        // Inside, on an edge and repeated points all go
+        points.extend([Vector2::new(1., 2.), Vector2::new(-3., 0.), Vector2::new(0., 10.), Vector2::new(10., 10.)]);
+        let hull = convex_hull(&points);
+        assert_eq!(hull.len(), 4);
+        for corner in square(Vector2::new(0., 0.), 10.) {
+            assert!(hull.contains(&corner), "{corner:?} missing from {hull:?}");
+        }
+        // Every turn goes the same way
+        for k in 0..hull.len() {
+            let (a, b, c) = (hull[k], hull[(k + 1) % hull.len()], hull[(k + 2) % hull.len()]);
+            assert!((b - a).x * (c - b).y - (b - a).y * (c - b).x > 0.);
+        }
+    }
+
+    #[test]
+    fn convex_hull_of_too_few_points_is_the_points() {
+        assert!(convex_hull(&[]).is_empty());
+        let two = [Vector2::new(1., 1.), Vector2::new(0., 0.), Vector2::new(1., 1.)];
+        assert_eq!(convex_hull(&two), [Vector2::new(0., 0.), Vector2::new(1., 1.)]);
+    }
+
+    #[test]
+    fn layers_collide_when_either_side_asks() {
+        let collider = |layer, mask| Collider::new(CollisionType::Circle(1.)).with_layer(layer, mask);
+        let player = collider(LAYER_PLAYER, LAYER_ASTEROID);
+        let rock = collider(LAYER_ASTEROID, 0);
+        let shot = collider(LAYER_PLAYER_PROJECTILE, LAYER_ASTEROID);
+        assert!(player.can_collide(&rock) && rock.can_collide(&player));
+        assert!(shot.can_collide(&rock));
+        assert!(!shot.can_collide(&player) && !player.can_collide(&shot));
+        assert!(!rock.can_collide(&rock));
+    }
+
+    #[test]
+    fn overlapping_squares_push_apart_along_the_shallow_axis() {
+        let a = square(Vector2::new(0., 0.), 10.);
+        let b = square(Vector2::new(16., 3.), 10.);
+        let contact = collide_polygons(&a, &b).unwrap();
+        assert!((contact.depth - 4.).abs() < 1e-4, "{}", contact.depth);
+        assert!((contact.normal - Vector2::new(1., 0.)).length() < 1e-4, "{:?}", contact.normal);
+        // Flipping the pair flips the normal
+        let back = collide_polygons(&b, &a).unwrap();
+        assert!((back.normal + contact.normal).length() < 1e-4);
+    }
+
+    #[test]
+    fn separated_polygons_do_not_touch() {
+        let a = square(Vector2::new(0., 0.), 10.);
+        // Diagonal neighbour, only the edge normals of a rotated shape tell them apart
+        let diamond = vec![Vector2::new(25., 0.), Vector2::new(35., 10.), Vector2::new(25., 20.), Vector2::new(15., 10.)];
+        assert!(collide_polygons(&a, &square(Vector2::new(21., 0.), 10.)).is_none());
+        assert!(collide_polygons(&a, &diamond).is_none());
+    }
+
+    #[test]
+    fn circle_against_polygon_points_out_of_the_polygon() {
+        let poly = Shape::Polygon(square(Vector2::new(0., 0.), 10.));
+        let circle = Shape::Circle(Circle { center: Vector2::new(0., 14.), radius: 5. });
+        let contact = collide_shapes(&poly, &circle).unwrap();
+        assert!((contact.depth - 1.).abs() < 1e-4);
+        assert!((contact.normal - Vector2::new(0., 1.)).length() < 1e-4);
+        let flipped = collide_shapes(&circle, &poly).unwrap();
+        assert!((flipped.normal + contact.normal).length() < 1e-4);
+
+        // Center inside the polygon still comes out through the nearest edge
+        let inside = Shape::Circle(Circle { center: Vector2::new(0., 8.), radius: 5. });
+        let contact = collide_shapes(&poly, &inside).unwrap();
+        assert!((contact.depth - 7.).abs() < 1e-4, "{}", contact.depth);
+
+        let apart = Shape::Circle(Circle { center: Vector2::new(0., 16.), radius: 5. });
+        assert!(collide_shapes(&poly, &apart).is_none());
+    }
+
+    #[test]
+    fn circles_touch_within_their_radii() {
+        let a = Circle { center: Vector2::new(0., 0.), radius: 10. };
+        let b = Circle { center: Vector2::new(0., 15.), radius: 10. };
+        let contact = collide_circles(&a, &b).unwrap();
+        assert!((contact.depth - 5.).abs() < 1e-4);
+        assert_eq!(contact.normal, Vector2::new(0., 1.));
+        assert!(collide_circles(&a, &Circle { center: Vector2::new(0., 21.), radius: 10. }).is_none());
+    }
+
+    #[test]
+    fn polygon_sweep_catches_a_shot_passing_through() {
+        let rock = Shape::Polygon(square(Vector2::new(0., 0.), 20.));
+        // Starts below the rock and ends well above it, 150 px in one step
+        let start = Shape::Polygon(shot(Vector2::new(0., 80.)));
+        let end = Shape::Polygon(shot(Vector2::new(0., -70.)));
+        assert!(collide_shapes(&rock, &end).is_none());
+
+        let contact = sweep_shapes(&rock, Vector2::zero(), &start, Vector2::new(0., -150.)).unwrap();
+        // The tip starts 22.5 below the center and reaches the rock's edge at y = 20 after 37.5 px
+        assert!((contact.time - 37.5 / 150.).abs() < 1e-3, "{}", contact.time);
+        assert!(contact.normal.y > 0.9, "{:?}", contact.normal);
+    }
+
+    #[test]
+    fn polygon_sweep_is_the_same_from_either_side() {
+        let rock = Shape::Polygon(square(Vector2::new(0., 0.), 20.));
+        let start = Shape::Polygon(shot(Vector2::new(0., 80.)));
+        let a = sweep_shapes(&rock, Vector2::zero(), &start, Vector2::new(0., -150.)).unwrap();
+        let b = sweep_shapes(&start, Vector2::new(0., -150.), &rock, Vector2::zero()).unwrap();
+        assert!((a.time - b.time).abs() < 1e-4);
+        assert!((a.normal + b.normal).length() < 1e-4);
+    }
+
+    #[test]
+    fn polygon_sweep_misses_a_shot_passing_beside() {
+        let rock = Shape::Polygon(square(Vector2::new(0., 0.), 20.));
+        let start = Shape::Polygon(shot(Vector2::new(30., 80.)));
+        assert!(sweep_shapes(&rock, Vector2::zero(), &start, Vector2::new(0., -150.)).is_none());
+    }
+
+    #[test]
+    fn polygon_sweep_follows_the_relative_motion() {
+        // The rock moves down onto a shot that stays put
+        let rock = Shape::Polygon(square(Vector2::new(0., -100.), 20.));
+        let still = Shape::Polygon(shot(Vector2::new(0., 0.)));
+        let contact = sweep_shapes(&rock, Vector2::new(0., 200.), &still, Vector2::zero()).unwrap();
+        assert!(contact.time > 0. && contact.time < 0.5, "{}", contact.time);
+    }
 }
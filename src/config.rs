@@ -0,0 +1,126 @@
+use std::fs;
+
+// Game settings, read from a plain text file next to the game with one `key = value`
+// per line. Lines starting with `#` are comments. A missing file, unknown keys and bad
+// values are not fatal, whatever is not set keeps its default.
+const CONFIG_FILE: &str = "asteroids.cfg";
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    // Smart bombs the player starts with
+    pub starting_bombs: u32,
+    // Fraction of the analog stick's tilt that is ignored
+    pub gamepad_deadzone: f32,
+    // Steer with the mouse or touch instead of only keys and sticks
+    pub pointer_controls: bool,
+    // 2 for local co-op
+    pub players: usize,
+    // Ships each player has before they are out
+    pub lives: u32,
+    // Players compete on score instead of playing as a team
+    pub versus: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            starting_bombs: 3,
+            gamepad_deadzone: 0.2,
+            pointer_controls: false,
+            players: 1,
+            lives: 3,
+            versus: false,
+        }
+    }
+}
+
+impl Config {
+    pub fn load() -> Self {
+        let mut config = Self::default();
+        let Ok(text) = fs::read_to_string(CONFIG_FILE) else {
+            return config;
+        };
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                eprintln!("{CONFIG_FILE}:{}: expected `key = value`", number + 1);
+                continue;
+            };
+            if let Err(err) = config.set(key.trim(), value.trim()) {
+                eprintln!("{CONFIG_FILE}:{}: {err}", number + 1);
+            }
+        }
+        config
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let number = |value: &str| value.parse::<u32>().map_err(|_| format!("`{value}` is not a number"));
+        let fraction = |value: &str| {
+            value.parse::<f32>()
+                .ok()
+                .filter(|v| (0. ..1.).contains(v))
+                .ok_or_else(|| format!("`{value}` is not a number from 0 to 1"))
+        };
+        let flag = |value: &str| value.parse::<bool>().map_err(|_| format!("`{value}` is not true or false"));
+        match key {
+            "starting_bombs" => self.starting_bombs = number(value)?,
+            "gamepad_deadzone" => self.gamepad_deadzone = fraction(value)?,
+            "pointer_controls" => self.pointer_controls = flag(value)?,
+            "players" => {
+                self.players = match value {
+                    "1" => 1,
+                    "2" => 2,
+                    _ => return Err(format!("`{value}` is not 1 or 2")),
+                }
+            }
+            "lives" => self.lives = number(value)?.max(1),
+            "versus" => self.versus = flag(value)?,
+            _ => return Err(format!("unknown setting `{key}`")),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_parse_into_place() {
+        let mut config = Config::default();
+        config.set("starting_bombs", "5").unwrap();
+        config.set("gamepad_deadzone", "0.35").unwrap();
+        config.set("pointer_controls", "true").unwrap();
+        config.set("players", "2").unwrap();
+        config.set("versus", "true").unwrap();
+        assert_eq!(config.starting_bombs, 5);
+        assert_eq!(config.gamepad_deadzone, 0.35);
+        assert!(config.pointer_controls);
+        assert_eq!(config.players, 2);
+        assert!(config.versus);
+
+        // Nobody starts out of the game
+        config.set("lives", "0").unwrap();
+        assert_eq!(config.lives, 1);
+    }
+
+    #[test]
+    fn bad_values_leave_the_default() {
+        let mut config = Config::default();
+        assert!(config.set("starting_bombs", "-1").is_err());
+        assert!(config.set("gamepad_deadzone", "1").is_err());
+        assert!(config.set("gamepad_deadzone", "NaN").is_err());
+        assert!(config.set("pointer_controls", "yes").is_err());
+        assert!(config.set("players", "3").is_err());
+        assert_eq!(config.set("volume", "11").unwrap_err(), "unknown setting `volume`");
+
+        let default = Config::default();
+        assert_eq!(config.starting_bombs, default.starting_bombs);
+        assert_eq!(config.gamepad_deadzone, default.gamepad_deadzone);
+        assert_eq!(config.pointer_controls, default.pointer_controls);
+        assert_eq!(config.players, default.players);
+    }
+}
@@ -1,99 +1,298 @@
 use rand::*;
 use raylib::prelude::*;
 
-use crate::{check_collision_circle_triangle, Circle, Event, Textures, Triangle, SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::{
+    collide_compound, collide_shapes, convex_hull, rect_center, sweep_compound, sweep_shapes, Boss, BossHit, Collider, CollisionType, Contact, Effects,
+    Event, Homing, Input, Look, NetEntity, NetPart, PartKind, Pose, PowerUp, MISSILE_CAPACITY,
+    ProjectileKind, ProjectileSpec, Shape, BOSS_PART_SCORE, BOSS_SCORE, Textures, Transformable, LAYER_ASTEROID,
+    LAYER_ENEMY_PROJECTILE, LAYER_ENEMY_SHIP, LAYER_PICKUP, LAYER_PLAYER, LAYER_PLAYER_PROJECTILE, LAYER_SHOCKWAVE,
+    SCREEN_HEIGHT, SCREEN_WIDTH,
+};
 
 
 pub type EntityId = usize;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Entity {
     Player(Box<Player>),
     Enemy(Box<Asteroid>),
+    EnemyShip(Box<Ship>),
+    Boss(Box<Boss>),
     Projectile(Box<Lazer>),
+    Pickup(Box<Pickup>),
+    Explosion(Box<Explosion>),
+    Shockwave(Box<Shockwave>),
     Star(Box<Star>),
-    Indestructible,
 }
 
 impl Entity {
-    pub fn check_collision(&self, other: &Entity) -> bool {
-        match (self, other) {
-            (Self::Player(e1), Self::Enemy(e2)) | (Self::Enemy(e2), Self::Player(e1)) => {
-                check_collision_circle_triangle(
-                    &Circle::from_rect(&e2.rect),
-                    &Triangle::from_rect(&e1.rect),
-                )
-            }
-            (Self::Projectile(e1), Self::Enemy(e2)) | (Self::Enemy(e2), Self::Projectile(e1)) => {
-                let circle = Circle::from_rect(&e2.rect);
-                e1.rect.check_collision_circle_rec(circle.center, circle.radius)
-            }
-            (Self::Enemy(e1), Self::Enemy(e2)) => {
-                let c1 = Circle::from_rect(&e1.rect);
-                let c2 = Circle::from_rect(&e2.rect);
-                check_collision_circles(c1.center, c1.radius, c2.center, c2.radius)
+    // Contact normal points from `self` towards `other`. Runs after the entities
+    // moved for the frame, fast movers are swept back over the last `delta_time`.
+    pub fn check_collision(&self, other: &Entity, delta_time: f32) -> Option<Contact> {
+        let (c1, c2) = (self.collider()?, other.collider()?);
+        if !c1.can_collide(c2) {
+            return None;
+        }
+        let fast = self.is_fast() || other.is_fast();
+        let (m1, m2) = match fast {
+            true => (self.velocity() * delta_time, other.velocity() * delta_time),
+            false => (Vector2::zero(), Vector2::zero()),
+        };
+        // Bodies made of parts are first tested with the circle around them all, most
+        // pairs end there
+        if (self.is_compound() || other.is_compound()) && !self.bounds_meet(other, m1, m2) {
+            return None;
+        }
+        if fast {
+            let start1 = self.hitboxes_at(-m1);
+            let start2 = other.hitboxes_at(-m2);
+            if let Some(contact) = sweep_compound(&start1, m1, &start2, m2) {
+                return Some(contact);
             }
-            _ => false,
         }
+        collide_compound(&self.hitboxes_at(Vector2::zero()), &other.hitboxes_at(Vector2::zero()))
+    }
+
+    // Projectiles can cover more than their own length in a slow frame
+    pub fn is_fast(&self) -> bool {
+        matches!(self, Self::Projectile(_))
+    }
+
+    fn is_compound(&self) -> bool {
+        matches!(self, Self::Boss(_))
+    }
+
+    // One shape around the whole entity shifted by `offset`, the hitbox itself unless it
+    // is made of parts
+    fn bounds_at(&self, offset: Vector2) -> Option<Shape> {
+        match self {
+            Self::Boss(e) => Some(e.collider.shape_at(rect_center(&e.rect) + offset, 0.)),
+            _ => self.hitbox_at(offset),
+        }
+    }
+
+    // Whether the bounds touch anywhere along the step, each having moved by its motion
+    fn bounds_meet(&self, other: &Entity, m1: Vector2, m2: Vector2) -> bool {
+        let (Some(start1), Some(start2)) = (self.bounds_at(-m1), other.bounds_at(-m2)) else {
+            return false;
+        };
+        let (Some(end1), Some(end2)) = (self.bounds_at(Vector2::zero()), other.bounds_at(Vector2::zero())) else {
+            return false;
+        };
+        sweep_shapes(&start1, m1, &start2, m2).is_some() || collide_shapes(&end1, &end2).is_some()
+    }
+
+    pub fn velocity(&self) -> Vector2 {
+        match self {
+            Self::Player(e) => e.velocity,
+            Self::Enemy(e) => e.velocity,
+            Self::EnemyShip(e) => e.velocity,
+            Self::Boss(e) => e.velocity,
+            Self::Projectile(e) => e.direction() * e.speed,
+            Self::Pickup(e) => e.velocity,
+            _ => Vector2::zero(),
+        }
+    }
+
+    pub fn collider(&self) -> Option<&Collider> {
+        match self {
+            Self::Player(e) => Some(&e.collider),
+            Self::Enemy(e) => Some(&e.collider),
+            Self::EnemyShip(e) => Some(&e.collider),
+            Self::Boss(e) => Some(&e.collider),
+            Self::Projectile(e) => Some(&e.collider),
+            Self::Pickup(e) => Some(&e.collider),
+            Self::Shockwave(e) => Some(&e.collider),
+            _ => None,
+        }
+    }
+
+    // Collider placed at the entity's current position
+    pub fn hitbox(&self) -> Option<Shape> {
+        self.hitbox_at(Vector2::zero())
     }
 
-    pub fn on_collision(&mut self, other: &mut Entity, world: &mut crate::World) -> Vec<Event> {
+    // Every part of the collider shifted by `offset`, one shape for all but compound bodies
+    pub fn hitboxes_at(&self, offset: Vector2) -> Vec<Shape> {
+        match self {
+            Self::Boss(e) => e.hitboxes_at(offset),
+            _ => self.hitbox_at(offset).into_iter().collect(),
+        }
+    }
+
+    // Collider placed at the entity's position shifted by `offset`
+    pub fn hitbox_at(&self, offset: Vector2) -> Option<Shape> {
+        match self {
+            Self::Player(e) => Some(e.collider.shape_at(rect_center(&e.rect) + offset, 0.)),
+            Self::Enemy(e) => Some(e.collider.shape_at(rect_center(&e.rect) + offset, e.rotation)),
+            Self::EnemyShip(e) => Some(e.collider.shape_at(rect_center(&e.rect) + offset, 0.)),
+            Self::Projectile(e) => Some(e.collider.shape_at(rect_center(&e.rect) + offset, e.angle)),
+            Self::Pickup(e) => Some(e.collider.shape_at(rect_center(&e.rect) + offset, 0.)),
+            Self::Shockwave(e) => Some(e.collider.shape_at(e.center + offset, 0.)),
+            _ => None,
+        }
+    }
+
+    pub fn on_collision(&mut self, other: &mut Entity, contact: &Contact, world: &mut crate::World) -> Vec<Event> {
         let mut events = vec![];
         match (self, other) {
-            (Self::Player(_e1), Self::Enemy(_e2)) | (Self::Enemy(_e2), Self::Player(_e1)) => {
-                events.push(Event::GameOver);
+            (Self::Player(e1), Self::Enemy(e2)) | (Self::Enemy(e2), Self::Player(e1)) => {
+                world.touch(e2.id);
+                if !e1.hit(&mut events) {
+                    return events;
+                }
+                // Gone without fragments, they would spawn right on top of the ship
+                events.push(Event::EntityDestroyed(e2.id));
+            }
+            (Self::Player(e1), Self::EnemyShip(e2)) | (Self::EnemyShip(e2), Self::Player(e1)) => {
+                world.touch(e2.id);
+                if !e1.hit(&mut events) {
+                    return events;
+                }
+                events.push(Event::EntityDestroyed(e2.id));
+            }
+            // Only enemy shots are on the player's layers
+            (Self::Player(e1), Self::Projectile(e2)) | (Self::Projectile(e2), Self::Player(e1)) => {
+                world.touch(e2.id);
+                if !e1.hit(&mut events) {
+                    return events;
+                }
+                events.push(Event::EntityDestroyed(e2.id));
+            }
+            // The boss cannot be rammed, not even with a shield
+            (Self::Player(e1), Self::Boss(_)) | (Self::Boss(_), Self::Player(e1)) => {
+                if e1.is_respawning() {
+                    return events;
+                }
+                events.push(Event::PlayerHit(e1.slot));
+            }
+            (Self::Projectile(e1), Self::Boss(e2)) => hit_boss(e2, contact.parts[1], e1, &mut events),
+            (Self::Boss(e2), Self::Projectile(e1)) => hit_boss(e2, contact.parts[0], e1, &mut events),
+            (Self::Player(e1), Self::Pickup(e2)) | (Self::Pickup(e2), Self::Player(e1)) => {
+                match e2.power_up {
+                    PowerUp::Bomb => e1.bombs += 1,
+                    power_up => e1.effects.add(power_up),
+                }
+                events.push(Event::EntityDestroyed(e2.id));
+            }
+            // Everything the wave reaches is hit once, big asteroids only get shoved aside
+            (Self::Shockwave(e1), other) | (other, Self::Shockwave(e1)) => {
+                let id = match other {
+                    Self::Enemy(e2) => e2.id,
+                    Self::EnemyShip(e2) => e2.id,
+                    Self::Boss(e2) => e2.id,
+                    Self::Projectile(e2) => e2.id,
+                    _ => return events,
+                };
+                if e1.hit.contains(&id) {
+                    return events;
+                }
+                e1.hit.push(id);
+                match other {
+                    Self::Enemy(e2) if e2.size > BOMB_CRUSH_SIZE => {
+                        let away = (rect_center(&e2.rect) - e1.center).normalized();
+                        e2.velocity += away * BOMB_PUSH;
+                    }
+                    Self::Enemy(e2) => {
+                        events.push(Event::ScoreIncreased(e1.owner, e2.score()));
+                        events.push(Event::AsteroidDestroyed(e2.id, e1.center));
+                    }
+                    Self::EnemyShip(e2) => {
+                        if !e2.damage(BOMB_DAMAGE) {
+                            return events;
+                        }
+                        events.push(Event::ScoreIncreased(e1.owner, SHIP_SCORE));
+                        events.push(Event::EntityDestroyed(e2.id));
+                    }
+                    Self::Boss(e2) => {
+                        // The shockwave is a single part, whichever side it is on has index 0
+                        let part = contact.parts[0].max(contact.parts[1]);
+                        match e2.hit(part, BOMB_DAMAGE) {
+                            BossHit::Damaged => (),
+                            BossHit::PartDestroyed => events.push(Event::ScoreIncreased(e1.owner, BOSS_PART_SCORE)),
+                            BossHit::Destroyed => {
+                                events.push(Event::ScoreIncreased(e1.owner, BOSS_SCORE));
+                                events.push(Event::EntityDestroyed(e2.id));
+                            }
+                        }
+                    }
+                    // Only enemy shots are on the shockwave's layers
+                    Self::Projectile(e2) => events.push(Event::EntityDestroyed(e2.id)),
+                    _ => (),
+                }
+            }
+            (Self::Projectile(e1), Self::Enemy(_)) | (Self::Enemy(_), Self::Projectile(e1)) if e1.homing.is_some() => {
+                e1.detonate(contact.point, &mut events);
             }
             (Self::Projectile(e1), Self::Enemy(e2)) | (Self::Enemy(e2), Self::Projectile(e1)) => {
-                events.push(Event::ScoreIncreased);
-                events.push(Event::NumberOfAsteroidsIncreased);
-                events.push(Event::ResetAsteroid(e2.id));
-                events.push(Event::EntityDestroyed(e1.id));
+                if !e1.first_hit(e2.id) {
+                    return events;
+                }
+                events.push(Event::ScoreIncreased(e1.owner, e2.score()));
+                events.push(Event::AsteroidDestroyed(e2.id, contact.point));
+                if !e1.piercing {
+                    events.push(Event::EntityDestroyed(e1.id));
+                }
+            }
+            (Self::Projectile(e1), Self::EnemyShip(_)) | (Self::EnemyShip(_), Self::Projectile(e1)) if e1.homing.is_some() => {
+                e1.detonate(contact.point, &mut events);
+            }
+            (Self::Projectile(e1), Self::EnemyShip(e2)) | (Self::EnemyShip(e2), Self::Projectile(e1)) => {
+                if !e1.first_hit(e2.id) {
+                    return events;
+                }
+                if e2.damage(e1.damage) {
+                    events.push(Event::ScoreIncreased(e1.owner, SHIP_SCORE));
+                    events.push(Event::EntityDestroyed(e2.id));
+                }
+                if !e1.piercing {
+                    events.push(Event::EntityDestroyed(e1.id));
+                }
             }
             (Self::Enemy(e1), Self::Enemy(e2)) => {
-                let (v1, v2) = crate::elastic_collision_1d(e1.mass, e1.velocity.y, e2.mass, e2.velocity.y);
-                e1.velocity = Vector2::new(0., v1);
-                e2.velocity = Vector2::new(0., v2);
+                crate::colliding_circles_2d(e1.as_mut(), e2.as_mut(), contact, ASTEROID_RESTITUTION, ASTEROID_FRICTION);
             }
             _ => (),               
         };
         events
     }
     
-    pub fn update(&mut self, rl: &mut RaylibHandle, delta_time: f32) {
+    // `inputs` holds what each player slot asked for this frame
+    pub fn update(&mut self, inputs: &[Input], delta_time: f32) {
         match self {
             Self::Player(e) => {
-                // Handle horizontal movement (A/D or Left/Right)
-                if rl.is_key_down(KeyboardKey::KEY_RIGHT) || rl.is_key_down(KeyboardKey::KEY_D) {
-                    e.velocity.x += e.acceleration * delta_time;
-                } 
-                else if rl.is_key_down(KeyboardKey::KEY_LEFT) || rl.is_key_down(KeyboardKey::KEY_A) {
-                    e.velocity.x -= e.acceleration * delta_time;
-                }
-
-                // Handle vertical movement (W/S or Up/Down)
-                if rl.is_key_down(KeyboardKey::KEY_UP) || rl.is_key_down(KeyboardKey::KEY_W) {
-                    e.velocity.y -= e.acceleration * delta_time;
-                } 
-                else if rl.is_key_down(KeyboardKey::KEY_DOWN) || rl.is_key_down(KeyboardKey::KEY_S) {
-                    e.velocity.y += e.acceleration * delta_time;
-                }
+                let input = &inputs[e.slot];
+                // Accelerate along the requested direction, a half tilted stick pushes half as hard.
+                // Without key or stick input the ship heads for the pointer, easing off over the
+                // last stretch so it settles there instead of overshooting.
+                let movement = match input.target {
+                    Some(target) if input.movement == Vector2::zero() => {
+                        let center = Vector2::new(e.rect.x + e.rect.width / 2., e.rect.y + e.rect.height / 2.);
+                        let offset = target - center;
+                        let distance = offset.length();
+                        if distance < POINTER_DEADZONE {
+                            Vector2::zero()
+                        } else {
+                            offset / distance * (distance / POINTER_SLOWDOWN).min(1.)
+                        }
+                    }
+                    _ => input.movement,
+                };
+                e.velocity += movement * e.acceleration * delta_time;
 
-                // Clamp velocity to max speed (using vector length for diagonal movement)
+                // Clamp velocity to max speed (using vector length for diagonal movement),
+                // scaled down while the stick is only partly tilted
+                let tilt = movement.length().min(1.);
+                let max_velocity = if tilt > 0. { e.max_velocity * tilt } else { e.max_velocity };
                 let velocity_magnitude = (e.velocity.x * e.velocity.x + e.velocity.y * e.velocity.y).sqrt();
-                if velocity_magnitude > e.max_velocity {
-                    e.velocity.x = (e.velocity.x / velocity_magnitude) * e.max_velocity;
-                    e.velocity.y = (e.velocity.y / velocity_magnitude) * e.max_velocity;
+                if velocity_magnitude > max_velocity {
+                    e.velocity.x = (e.velocity.x / velocity_magnitude) * max_velocity;
+                    e.velocity.y = (e.velocity.y / velocity_magnitude) * max_velocity;
                 }
 
                 // Apply friction (only if no input is pressed)
-                let is_moving_horizontally = rl.is_key_down(KeyboardKey::KEY_LEFT) || 
-                                             rl.is_key_down(KeyboardKey::KEY_RIGHT) || 
-                                             rl.is_key_down(KeyboardKey::KEY_A) || 
-                                             rl.is_key_down(KeyboardKey::KEY_D);
-                let is_moving_vertically = rl.is_key_down(KeyboardKey::KEY_UP) || 
-                                           rl.is_key_down(KeyboardKey::KEY_DOWN) || 
-                                           rl.is_key_down(KeyboardKey::KEY_W) || 
-                                           rl.is_key_down(KeyboardKey::KEY_S);
+                let is_moving_horizontally = movement.x != 0.;
+                let is_moving_vertically = movement.y != 0.;
 
                 if !is_moving_horizontally {
                     e.velocity.x *= (1.0 - e.friction * delta_time).max(0.0);
@@ -102,6 +301,10 @@ impl Entity {
                     e.velocity.y *= (1.0 - e.friction * delta_time).max(0.0);
                 }
 
+                e.effects.update(delta_time);
+                e.respawn_timer = (e.respawn_timer - delta_time).max(0.);
+                e.fire_cooldown = (e.fire_cooldown - delta_time).max(0.);
+
                 // Update position
                 e.rect.x += e.velocity.x * delta_time;
                 e.rect.y += e.velocity.y * delta_time;
@@ -113,14 +316,31 @@ impl Entity {
             Self::Enemy(e) => {
                 e.rect.x += e.velocity.x * delta_time;
                 e.rect.y += e.velocity.y * delta_time;
-                e.rotation = (e.rotation + e.rotation_velocity) % 360. * delta_time;
-                // Reset e when it goes off screen
-                if e.rect.y > SCREEN_HEIGHT!() {
-                    e.reset();
-                }
+                e.rotation = (e.rotation + e.rotation_velocity * delta_time) % 360.;
+            }
+            Self::EnemyShip(e) => {
+                e.steer(delta_time);
+                e.rect.x += e.velocity.x * delta_time;
+                e.rect.y += e.velocity.y * delta_time;
+                e.fire_cooldown -= delta_time;
             }
+            Self::Boss(e) => e.update(delta_time),
             Self::Projectile(e) => {
-                e.rect.y -= e.speed as f32 * delta_time;
+                e.steer(delta_time);
+                let motion = e.direction() * e.speed * delta_time;
+                e.rect.x += motion.x;
+                e.rect.y += motion.y;
+            }
+            Self::Pickup(e) => {
+                e.rect.x += e.velocity.x * delta_time;
+                e.rect.y += e.velocity.y * delta_time;
+            }
+            Self::Explosion(e) => {
+                e.age += delta_time;
+            }
+            Self::Shockwave(e) => {
+                e.radius = (e.radius + BOMB_SPEED * delta_time).min(BOMB_RADIUS);
+                e.collider.shape = CollisionType::Circle(e.radius);
             }
             Self::Star(e) => {
                 e.pos.y += e.velocity * delta_time;
@@ -129,7 +349,6 @@ impl Entity {
                     e.pos.y = -1.;
                 }
             }
-            _ => ()
         }    
     }
 
@@ -137,44 +356,194 @@ impl Entity {
         matches!(self, Self::Player(_) | Self::Enemy(_) | Self::Projectile(_))
     }
     
+    // What a network client needs to draw the entity, stars are left to each side
+    pub fn net_state(&self) -> Option<NetEntity> {
+        let local = |collider: &Collider| match collider.shape_at(Vector2::zero(), 0.) {
+            Shape::Polygon(points) => points,
+            Shape::Circle(_) => vec![],
+        };
+        let (look, position, angle, value) = match self {
+            Self::Player(e) => {
+                let look = Look::Player {
+                    slot: e.slot as u8,
+                    size: Vector2::new(e.rect.width, e.rect.height),
+                    shield: e.effects.has(PowerUp::Shield),
+                    hidden: e.is_blinked_out(),
+                };
+                (look, rect_center(&e.rect), 0., 0.)
+            }
+            Self::Enemy(e) => {
+                let look = Look::Asteroid { outline: local(&e.collider), color: e.color };
+                (look, rect_center(&e.rect), e.rotation, 0.)
+            }
+            Self::EnemyShip(e) => (Look::Ship { outline: local(&e.collider), color: e.color }, rect_center(&e.rect), 0., 0.),
+            Self::Boss(e) => {
+                let center = rect_center(&e.rect);
+                let parts = e.parts.iter()
+                    .zip(e.hitboxes_at(-center))
+                    .map(|(part, shape)| NetPart {
+                        core: part.kind == PartKind::Core,
+                        turret: part.kind == PartKind::Turret,
+                        color: part.color(),
+                        shape: shape.into(),
+                    })
+                    .collect();
+                let aim = e.target - center;
+                (Look::Boss { parts }, center, aim.x.atan2(-aim.y).to_degrees(), 0.)
+            }
+            Self::Projectile(e) => {
+                let look = Look::Shot {
+                    missile: e.kind == ProjectileKind::Missile,
+                    width: e.rect.width,
+                    length: e.rect.height,
+                    color: e.color,
+                };
+                (look, rect_center(&e.rect), e.angle, 0.)
+            }
+            Self::Pickup(e) => {
+                let look = Look::Pickup { power_up: e.power_up, radius: e.rect.width / 2. };
+                (look, rect_center(&e.rect), 0., 0.)
+            }
+            Self::Explosion(e) => (Look::Explosion { radius: e.radius }, e.center, 0., e.age / EXPLOSION_DURATION),
+            Self::Shockwave(e) => (Look::Shockwave, e.center, 0., e.radius),
+            Self::Star(_) => return None,
+        };
+        Some(NetEntity { look, pose: Pose { position, angle, value } })
+    }
+
     pub fn draw(&self, d: &mut RaylibDrawHandle, textures: &Textures) {
         match self {
             Self::Player(e) => {
+                if e.is_blinked_out() {
+                    return;
+                }
                 d.draw_texture_pro(
                     textures.get("player").unwrap(),
                     Rectangle::new(0., 0., 40., 40.),
-                    Rectangle::new(e.rect.x, e.rect.y, e.rect.width, e.rect.height + 40.),
+                    Rectangle::new(e.rect.x, e.rect.y, e.rect.width, e.rect.height + PLAYER_SPRITE_OVERHANG),
                     Vector2::zero(),
-                    0., Color::WHITE
+                    0., PLAYER_TINTS[e.slot]
                 );
-                // let tri = Triangle::from_rect(&e.rect);
-                // d.draw_triangle(tri.a, tri.b, tri.c, Color::GOLD);
+                if e.effects.has(PowerUp::Shield) {
+                    let center = rect_center(&e.rect);
+                    d.draw_circle_lines(center.x as i32, center.y as i32 + (PLAYER_SPRITE_OVERHANG / 2.) as i32, e.rect.width, PowerUp::Shield.color());
+                }
+                // if let Some(Shape::Polygon(points)) = self.hitbox() {
+                //     for k in 0..points.len() {
+                //         d.draw_line_v(points[k], points[(k + 1) % points.len()], Color::GOLD);
+                //     }
+                // }
             }
             Self::Enemy(e) => {
-                d.draw_texture_pro(
-                    textures.get("asteroid").unwrap(),
-                    Rectangle::new(0., 0., 23., 23.),
-                    Rectangle::new(e.rect.x + e.rect.width / 2., e.rect.y + e.rect.height / 2., e.rect.width, e.rect.height),
-                    Vector2::new(e.rect.width / 2., e.rect.height / 2.),
-                    e.rotation, e.color
-                );
-                // d.draw_rectangle_rec(e.rect, e.color);
+                // Drawn straight from the collider outline so hits match the picture
+                if let Some(Shape::Polygon(points)) = self.hitbox() {
+                    for k in 0..points.len() {
+                        d.draw_line_ex(points[k], points[(k + 1) % points.len()], 2., e.color);
+                    }
+                }
             }
+            Self::EnemyShip(e) => {
+                if let Some(Shape::Polygon(points)) = self.hitbox() {
+                    d.draw_triangle_lines(points[0], points[1], points[2], e.color);
+                }
+                let center = rect_center(&e.rect);
+                d.draw_circle_v(center - Vector2::new(0., e.rect.height / 6.), e.rect.width / 8., e.color);
+            }
+            Self::Boss(e) => e.draw(d),
             Self::Projectile(e) => {
-                d.draw_rectangle_rec(e.rect, e.color);
+                let (w, h) = (e.rect.width, e.rect.height);
+                d.draw_rectangle_pro(
+                    Rectangle::new(e.rect.x + w / 2., e.rect.y + h / 2., w, h),
+                    Vector2::new(w / 2., h / 2.),
+                    e.angle, e.color
+                );
+                if e.kind == ProjectileKind::Missile {
+                    // Exhaust flame behind the tail
+                    let tail = rect_center(&e.rect) - e.direction() * (h / 2. + 3.);
+                    d.draw_circle_v(tail, w / 2., Color::ORANGE);
+                }
+            }
+            Self::Explosion(e) => {
+                let t = e.age / EXPLOSION_DURATION;
+                let color = Color::ORANGE.alpha(1. - t);
+                d.draw_circle_v(e.center, e.radius * t, Color::YELLOW.alpha((1. - t) * 0.4));
+                d.draw_circle_lines(e.center.x as i32, e.center.y as i32, e.radius * t, color);
+            }
+            Self::Shockwave(e) => {
+                let fade = 1. - e.radius / BOMB_RADIUS;
+                d.draw_ring(e.center, (e.radius - 12.).max(0.), e.radius, 0., 360., 64, Color::PINK.alpha(fade));
+            }
+            Self::Pickup(e) => {
+                let center = rect_center(&e.rect);
+                let color = e.power_up.color();
+                d.draw_circle_lines(center.x as i32, center.y as i32, e.rect.width / 2., color);
+                d.draw_text(e.power_up.symbol(), center.x as i32 - 5, center.y as i32 - 9, 20, color);
             }
             Self::Star(e) => {
                 d.draw_pixel_v(e.pos, e.color);
             }
-            _ => ()
         }
     }
 }
 
 
-#[derive(Debug)]
+fn hit_boss(boss: &mut Boss, part: usize, lazer: &mut Lazer, events: &mut Vec<Event>) {
+    if !lazer.first_hit(boss.id) {
+        return;
+    }
+    if lazer.homing.is_some() {
+        lazer.detonate(rect_center(&lazer.rect), events);
+        return;
+    }
+    match boss.hit(part, lazer.damage) {
+        BossHit::Damaged => (),
+        BossHit::PartDestroyed => events.push(Event::ScoreIncreased(lazer.owner, BOSS_PART_SCORE)),
+        BossHit::Destroyed => {
+            events.push(Event::ScoreIncreased(lazer.owner, BOSS_SCORE));
+            events.push(Event::EntityDestroyed(boss.id));
+        }
+    }
+    if !lazer.piercing {
+        events.push(Event::EntityDestroyed(lazer.id));
+    }
+}
+
+
+// The ship sprite is drawn this much taller than the player rect
+pub const PLAYER_SPRITE_OVERHANG: f32 = 40.;
+// Ship colors by player slot
+pub const PLAYER_TINTS: [Color; 2] = [Color::WHITE, Color::SKYBLUE];
+// Seconds a ship that lost a life cannot be hit, and how often it blinks meanwhile
+const RESPAWN_GRACE: f32 = 2.;
+// Untouchable for a moment after the shield pops, so whatever else is touching the ship
+// does not take a life right away
+const SHIELD_GRACE: f32 = 0.5;
+const RESPAWN_BLINK_RATE: f32 = 10.;
+
+// Pointer control stops pushing this close to the pointer and pushes less than fully
+// when it is within the slowdown distance
+const POINTER_DEADZONE: f32 = 4.;
+const POINTER_SLOWDOWN: f32 = 100.;
+
+#[derive(Debug, Clone)]
 pub struct Player {
+    // Index into the per player inputs, scores and tints
+    pub slot: usize,
+    pub lives: u32,
+    // Seconds left of the grace period after losing a life or the shield
+    respawn_timer: f32,
+    // Where the ship starts out and comes back after losing a life
+    spawn: Vector2,
     pub rect: Rectangle,
+    pub collider: Collider,
+    pub effects: Effects,
+    // Index into `WEAPONS`
+    pub weapon: usize,
+    // Homing missiles left for the secondary weapon
+    pub missiles: u32,
+    pub bombs: u32,
+    // Seconds until the weapon can fire again
+    pub fire_cooldown: f32,
     acceleration: f32,
     friction: f32,
     max_velocity: f32,
@@ -182,44 +551,381 @@ pub struct Player {
 }
 
 impl Player {
-    pub fn new() -> Self {
+    // `outline` is the sprite's traced hull normalized to the image size,
+    // without one the hitbox falls back to a triangle filling the rect. `spawn_x` is the
+    // horizontal center of the ship at the start.
+    pub fn new(slot: usize, spawn_x: f32, outline: Option<&[Vector2]>, bombs: u32, lives: u32) -> Self {
         let width = 60.;
         let height = 60.;
+        let collider = match outline {
+            Some(points) => {
+                // Scale the outline onto the drawn sprite, centered on the sprite
+                let sprite_height = height + PLAYER_SPRITE_OVERHANG;
+                let points = points.iter()
+                    .map(|p| Vector2::new((p.x - 0.5) * width, (p.y - 0.5) * sprite_height))
+                    .collect();
+                Collider::new(CollisionType::Polygon(points))
+                    .with_offset(Vector2::new(0., PLAYER_SPRITE_OVERHANG / 2.))
+            }
+            // Nose at the top center, wings at the bottom corners of the rect
+            None => Collider::new(CollisionType::Triangle(
+                Vector2::new(-width / 2., height / 2.),
+                Vector2::new(width / 2., height / 2.),
+                Vector2::new(0., -height / 2.),
+            )),
+        };
+        let spawn = Vector2::new(spawn_x - width/2., SCREEN_HEIGHT!() - height - 50.);
         Self{
-            rect: Rectangle::new(
-                SCREEN_WIDTH!()/2. - width/2.,
-                SCREEN_HEIGHT!() - height - 50.,
-                width, height),
+            slot,
+            lives,
+            respawn_timer: 0.,
+            spawn,
+            rect: Rectangle::new(spawn.x, spawn.y, width, height),
+            collider: collider.with_layer(
+                LAYER_PLAYER,
+                LAYER_ASTEROID | LAYER_PICKUP | LAYER_ENEMY_SHIP | LAYER_ENEMY_PROJECTILE,
+            ),
+            effects: Effects::default(),
+            weapon: 0,
+            missiles: MISSILE_CAPACITY,
+            bombs,
+            fire_cooldown: 0.,
             acceleration: 1000.,
             friction: 10.,
             max_velocity: 500.,
             velocity: Vector2::new(0., 0.),
         }
     }
+
+    pub fn is_respawning(&self) -> bool {
+        self.respawn_timer > 0.
+    }
+
+    // Blinks while respawning
+    fn is_blinked_out(&self) -> bool {
+        self.is_respawning() && (self.respawn_timer * RESPAWN_BLINK_RATE) as u32 % 2 == 1
+    }
+
+    // A hazard touched the ship. Returns true when the shield took the hit and the hazard
+    // should go, otherwise the hit costs a life. Respawning ships are passed through.
+    fn hit(&mut self, events: &mut Vec<Event>) -> bool {
+        if self.is_respawning() {
+            return false;
+        }
+        if self.effects.consume(PowerUp::Shield) {
+            self.respawn_timer = SHIELD_GRACE;
+            return true;
+        }
+        events.push(Event::PlayerHit(self.slot));
+        false
+    }
+
+    // Lost a life with lives to spare: back to the spawn point, briefly untouchable
+    pub fn respawn(&mut self) {
+        self.rect.x = self.spawn.x;
+        self.rect.y = self.spawn.y;
+        self.velocity = Vector2::zero();
+        self.effects = Effects::default();
+        self.respawn_timer = RESPAWN_GRACE;
+    }
 }
 
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Lazer {
     pub id: EntityId,
     pub rect: Rectangle,
+    pub collider: Collider,
+    kind: ProjectileKind,
     color: Color,
-    speed: i32,
+    speed: f32,
+    // Heading in degrees, clockwise from straight up
+    angle: f32,
+    damage: u32,
+    piercing: bool,
+    // Player slot that fired it and gets the points
+    pub owner: usize,
+    pub homing: Option<Homing>,
+    // Where a homing missile is steering to, kept up to date by the world every frame
+    pub aim: Option<Vector2>,
+    // Everything it hit on the way, a piercing shot that leaves the screen afterwards is no
+    // miss
+    pub hit: Vec<EntityId>,
 }
 
 impl Lazer {
-    pub fn new(id: EntityId, x: f32, y: f32) -> Self {
+    // `muzzle` is where the tail of the shot starts out
+    pub fn new(id: EntityId, muzzle: Vector2, angle: f32, spec: &ProjectileSpec, damage: u32, piercing: bool) -> Self {
+        let (width, length) = (spec.width, spec.length);
+        Self {
+            id,
+            rect: Rectangle::new(muzzle.x - width / 2., muzzle.y - length, width, length),
+            collider: Collider::new(CollisionType::Rectangle(width, length))
+                .with_layer(LAYER_PLAYER_PROJECTILE, LAYER_ASTEROID | LAYER_ENEMY_SHIP),
+            kind: spec.kind,
+            color: if piercing { PowerUp::Piercing.color() } else { spec.color },
+            speed: spec.speed,
+            angle,
+            damage,
+            piercing,
+            owner: 0,
+            homing: None,
+            aim: None,
+            hit: vec![],
+        }
+    }
+
+    pub fn owned_by(mut self, owner: usize) -> Self {
+        self.owner = owner;
+        self
+    }
+
+    // Steers towards `aim` and explodes instead of hitting only what it touches
+    pub fn homing(mut self, homing: Homing) -> Self {
+        self.homing = Some(homing);
+        self
+    }
+
+    pub fn is_hostile(&self) -> bool {
+        self.collider.layer == LAYER_ENEMY_PROJECTILE
+    }
+
+    // True the first time it touches `id`. A piercing shot overlaps what it passes through
+    // for several ticks but only hurts it once.
+    fn first_hit(&mut self, id: EntityId) -> bool {
+        if self.hit.contains(&id) {
+            return false;
+        }
+        self.hit.push(id);
+        true
+    }
+
+    // A homing missile with no fuel left is due to explode
+    pub fn is_spent(&self) -> bool {
+        self.homing.is_some_and(|h| h.fuel <= 0.)
+    }
+
+    pub fn detonate(&self, at: Vector2, events: &mut Vec<Event>) {
+        if let Some(homing) = self.homing {
+            events.push(Event::Explosion(self.owner, at, homing.blast_radius, homing.blast_damage));
+        }
+        events.push(Event::EntityDestroyed(self.id));
+    }
+
+    // Turn towards the aim point, limited by the turn rate, and burn fuel
+    fn steer(&mut self, delta_time: f32) {
+        let Some(homing) = self.homing.as_mut() else {
+            return;
+        };
+        homing.fuel -= delta_time;
+        let Some(aim) = self.aim else {
+            return;
+        };
+        let to_aim = aim - rect_center(&self.rect);
+        let wanted = to_aim.x.atan2(-to_aim.y).to_degrees();
+        // Shortest way round, in -180..180
+        let turn = (wanted - self.angle + 540.).rem_euclid(360.) - 180.;
+        let max_turn = homing.turn_rate * delta_time;
+        self.angle += turn.clamp(-max_turn, max_turn);
+    }
+
+    // Fired by an enemy, only hurts the player
+    pub fn hostile(mut self) -> Self {
+        self.collider = self.collider.with_layer(LAYER_ENEMY_PROJECTILE, LAYER_PLAYER);
+        self
+    }
+
+    fn direction(&self) -> Vector2 {
+        Vector2::new(0., -1.).rotated(self.angle.to_radians())
+    }
+}
+
+
+// Kills are worth more than any asteroid
+pub const SHIP_SCORE: usize = 500;
+const SHIP_HEALTH: u32 = 4;
+const SHIP_MAX_SPEED: f32 = 260.;
+const SHIP_DIVE_SPEED: f32 = 420.;
+// How quickly ships turn their velocity towards where they want to be
+const SHIP_STEERING: f32 = 3.;
+// Seconds between two shots of the same ship
+const SHIP_FIRE_INTERVAL: std::ops::Range<f32> = 1.2..2.5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShipBehavior {
+    // Sweeps left and right across its row
+    Strafe,
+    // Waits at its slot for a moment, then dives through where the player was
+    Dive,
+    // Holds its slot in the formation, bobbing a little
+    Formation,
+}
+
+#[derive(Debug, Clone)]
+pub struct Ship {
+    pub id: EntityId,
+    pub rect: Rectangle,
+    pub collider: Collider,
+    pub behavior: ShipBehavior,
+    // Where the player is, kept up to date by the world every frame
+    pub target: Vector2,
+    pub fire_cooldown: f32,
+    // Home position the behaviors move around
+    anchor: Vector2,
+    health: u32,
+    velocity: Vector2,
+    // Seconds since spawning
+    time: f32,
+    dive_delay: f32,
+    dive_direction: Option<Vector2>,
+    color: Color,
+}
+
+impl Ship {
+    // Spawns above the top edge, straight over its anchor
+    pub fn new(id: EntityId, behavior: ShipBehavior, anchor: Vector2, rng: &mut impl Rng) -> Self {
+        let (width, height) = (40., 30.);
         Self {
             id,
-            rect: Rectangle::new(x, y - 45., 2., 45.),
+            rect: Rectangle::new(anchor.x - width / 2., -height, width, height),
+            // Nose pointing down at the player
+            collider: Collider::new(CollisionType::Triangle(
+                Vector2::new(-width / 2., -height / 2.),
+                Vector2::new(width / 2., -height / 2.),
+                Vector2::new(0., height / 2.),
+            )).with_layer(LAYER_ENEMY_SHIP, LAYER_PLAYER | LAYER_PLAYER_PROJECTILE),
+            behavior,
+            target: anchor,
+            fire_cooldown: rng.random_range(SHIP_FIRE_INTERVAL),
+            anchor,
+            health: SHIP_HEALTH,
+            velocity: Vector2::zero(),
+            time: 0.,
+            dive_delay: rng.random_range(2.0..5.),
+            dive_direction: None,
             color: Color::RED,
-            speed: 1100,
+        }
+    }
+
+    // Returns whether the hit destroyed the ship
+    pub fn damage(&mut self, amount: u32) -> bool {
+        self.health = self.health.saturating_sub(amount);
+        self.health == 0
+    }
+
+    // Ready to shoot and far enough on screen for the player to see it coming
+    pub fn can_fire(&self) -> bool {
+        self.fire_cooldown <= 0. && self.rect.y > 0.
+    }
+
+    pub fn reload(&mut self, rng: &mut impl Rng) {
+        self.fire_cooldown = rng.random_range(SHIP_FIRE_INTERVAL);
+    }
+
+    fn steer(&mut self, delta_time: f32) {
+        self.time += delta_time;
+        let position = rect_center(&self.rect);
+        let seek = |goal: Vector2| (goal - position) * SHIP_STEERING;
+        let desired = match self.behavior {
+            ShipBehavior::Strafe => {
+                let reach = (SCREEN_WIDTH!() / 2. - self.rect.width).max(0.);
+                let x = SCREEN_WIDTH!() / 2. + (self.time * 0.8 + self.anchor.x).sin() * reach;
+                seek(Vector2::new(x, self.anchor.y))
+            }
+            ShipBehavior::Dive => {
+                if self.dive_direction.is_none() && self.time > self.dive_delay {
+                    // Always heading down so the ship leaves through the bottom edge
+                    let aim = self.target - position;
+                    self.dive_direction = Some(Vector2::new(aim.x, aim.y.max(aim.x.abs() * 0.3 + 1.)).normalized());
+                }
+                match self.dive_direction {
+                    Some(direction) => direction * SHIP_DIVE_SPEED,
+                    None => seek(self.anchor),
+                }
+            }
+            ShipBehavior::Formation => {
+                seek(self.anchor + Vector2::new((self.time * 2.).sin() * 20., 0.))
+            }
+        };
+        self.velocity += (desired - self.velocity) * (SHIP_STEERING * delta_time).min(1.);
+        let max_speed = if self.dive_direction.is_some() { SHIP_DIVE_SPEED } else { SHIP_MAX_SPEED };
+        if self.velocity.length() > max_speed {
+            self.velocity = self.velocity.normalized() * max_speed;
         }
     }
 }
 
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+pub struct Pickup {
+    pub id: EntityId,
+    pub rect: Rectangle,
+    pub collider: Collider,
+    pub power_up: PowerUp,
+    velocity: Vector2,
+}
+
+impl Pickup {
+    pub fn new(id: EntityId, center: Vector2, power_up: PowerUp) -> Self {
+        let size = 24.;
+        Self {
+            id,
+            rect: Rectangle::new(center.x - size / 2., center.y - size / 2., size, size),
+            collider: Collider::new(CollisionType::Circle(size / 2.))
+                .with_layer(LAYER_PICKUP, LAYER_PLAYER),
+            power_up,
+            velocity: Vector2::new(0., 120.),
+        }
+    }
+}
+
+
+// Smart bomb shockwave: how far and fast it spreads, what it does to what it reaches.
+// Asteroids bigger than the crush size are pushed away instead of destroyed.
+pub const BOMB_RADIUS: f32 = 900.;
+const BOMB_SPEED: f32 = 1200.;
+const BOMB_DAMAGE: u32 = 3;
+const BOMB_CRUSH_SIZE: f32 = 30.;
+const BOMB_PUSH: f32 = 400.;
+
+#[derive(Debug, Clone)]
+pub struct Shockwave {
+    pub center: Vector2,
+    pub radius: f32,
+    pub collider: Collider,
+    // Player slot that dropped the bomb and gets the points
+    owner: usize,
+    // Entities already hit, each is only hit once
+    hit: Vec<EntityId>,
+}
+
+impl Shockwave {
+    pub fn new(center: Vector2, owner: usize) -> Self {
+        Self {
+            center,
+            owner,
+            radius: 0.,
+            collider: Collider::new(CollisionType::Circle(0.))
+                .with_layer(LAYER_SHOCKWAVE, LAYER_ASTEROID | LAYER_ENEMY_SHIP | LAYER_ENEMY_PROJECTILE),
+            hit: vec![],
+        }
+    }
+}
+
+
+// Seconds the blast ring of an explosion stays on screen
+pub const EXPLOSION_DURATION: f32 = 0.35;
+
+// Expanding ring left by an explosion, the damage is dealt when it appears
+#[derive(Debug, Clone)]
+pub struct Explosion {
+    pub center: Vector2,
+    pub radius: f32,
+    pub age: f32,
+}
+
+
+#[derive(Debug, Clone)]
 pub struct Star {
     pos: Vector2,
     velocity: f32,
@@ -241,11 +947,29 @@ impl Star {
     }
 }
 
+// Background of every window, many slow stars and a few faster ones in front of them
+pub fn star_field() -> Vec<Entity> {
+    let slow = (0..5000).map(|_| Star::new(0.5));
+    let fast = (0..1000).map(|_| Star::new(1.));
+    slow.chain(fast).map(|star| Entity::Star(Box::new(star))).collect()
+}
+
 
-#[derive(Debug)]
+// Fragments smaller than this are destroyed outright instead of splitting
+pub const ASTEROID_MIN_SIZE: f32 = 10.;
+// Number of outline vertices and how far they may dent inwards, as a fraction of the radius
+const ASTEROID_VERTICES: std::ops::RangeInclusive<usize> = 8..=12;
+const ASTEROID_JAGGEDNESS: f32 = 0.35;
+// Bounciness and surface grip of asteroid-asteroid contacts
+const ASTEROID_RESTITUTION: f32 = 0.8;
+const ASTEROID_FRICTION: f32 = 0.3;
+
+#[derive(Debug, Clone)]
 pub struct Asteroid {
     pub id: EntityId,
     pub rect: Rectangle,
+    pub size: f32,
+    pub collider: Collider,
     velocity: Vector2,
     mass: f32,
     rotation: f32,
@@ -254,47 +978,228 @@ pub struct Asteroid {
 }
 
 impl Asteroid {
-    pub fn new(id: EntityId) -> Asteroid {
-        let x = rand::rng().random_range(0.0..SCREEN_WIDTH!());
-        let y = rand::rng().random_range(-SCREEN_HEIGHT!()..0.0);
-        let size = rand::rng().random_range(10.0..40.);
+    // Spawns just above the top edge at a random column
+    pub fn new(id: EntityId, size: f32, speed: f32, rng: &mut impl Rng) -> Asteroid {
+        let x = rng.random_range(0.0..SCREEN_WIDTH!() - size);
+        let y = -size;
         let width = size; 
         let height = size;
         Self {
             id,
             rect: Rectangle::new(x, y, width, height),
-            velocity: Vector2::new(0., rand::rng().random_range(200.0..300.)),
+            size,
+            collider: Self::collider(size, rng),
+            velocity: Vector2::new(0., speed),
             mass: 100. / 40. * size * 100.,
             rotation: 0.,
-            rotation_velocity: rand::rng().random_range(-50.0..50.0),
+            rotation_velocity: rng.random_range(-50.0..50.0),
             color: Color {
-                r: rand::rng().random_range(200..255),
-                g: rand::rng().random_range(235..255),
-                b: rand::rng().random_range(245..255),
+                r: rng.random_range(200..255),
+                g: rng.random_range(235..255),
+                b: rng.random_range(245..255),
                 a: 255
             }
         }
     }
 
-    pub fn reset(&mut self) {
-        let x = rand::rng().random_range(0.0..SCREEN_WIDTH!());
-        let y = rand::rng().random_range(-SCREEN_HEIGHT!()..0.0);
-        let size = rand::rng().random_range(10.0..40.);
-        let width = size; 
-        let height = size;
-        *self = Self {
-            id: self.id,
-            rect: Rectangle::new(x, y, width, height),
-            velocity: Vector2::new(0., rand::rng().random_range(200.0..300.)),
-            mass: 100. / 40. * size * 100.,
-            rotation: 0.,
-            rotation_velocity: rand::rng().random_range(-50.0..50.0),
-            color: Color {
-                r: rand::rng().random_range(200..255),
-                g: rand::rng().random_range(235..255),
-                b: rand::rng().random_range(245..255),
-                a: 255
-            }
-        };
+    // Random lumpy outline that fits the size, kept convex for the SAT tests
+    fn collider(size: f32, rng: &mut impl Rng) -> Collider {
+        let count = rng.random_range(ASTEROID_VERTICES);
+        let radius = size / 2.;
+        let step = std::f32::consts::TAU / count as f32;
+        let points: Vec<Vector2> = (0..count)
+            .map(|i| {
+                let angle = (i as f32 + rng.random_range(-0.3..0.3)) * step;
+                let r = radius * rng.random_range(1. - ASTEROID_JAGGEDNESS..=1.);
+                Vector2::new(angle.cos() * r, angle.sin() * r)
+            })
+            .collect();
+        Collider::new(CollisionType::Polygon(convex_hull(&points)))
+            .with_layer(LAYER_ASTEROID, LAYER_PLAYER | LAYER_PLAYER_PROJECTILE | LAYER_ASTEROID)
+    }
+
+    // Smaller asteroids are harder to hit, so they are worth more
+    pub fn score(&self) -> usize {
+        ((4000. / self.size / 10.).round() as usize) * 10
+    }
+
+    // Break the asteroid into two or three fragments flying away from the impact point.
+    // Fragment ids are assigned by the world when they are spawned.
+    pub fn split(&self, impact: Vector2, rng: &mut impl Rng) -> Vec<Asteroid> {
+        let count = rng.random_range(2..=3);
+        let sizes: Vec<f32> = (0..count)
+            .map(|_| self.size * rng.random_range(0.45..0.65))
+            .collect();
+        let total_size: f32 = sizes.iter().sum();
+        let center = rect_center(&self.rect);
+
+        let mut away = center - impact;
+        if away.length() == 0. {
+            away = Vector2::new(0., 1.);
+        }
+        let away = away.normalized();
+        let spread = 120f32.to_radians();
+
+        sizes.into_iter()
+            .enumerate()
+            .filter(|(_, size)| *size >= ASTEROID_MIN_SIZE)
+            .map(|(i, size)| {
+                // Fan the fragments out evenly around the impact direction
+                let t = if count > 1 { i as f32 / (count - 1) as f32 - 0.5 } else { 0. };
+                let direction = away.rotated(t * spread + rng.random_range(-0.2..0.2));
+                let speed = rng.random_range(60.0..140.);
+                Asteroid {
+                    id: 0,
+                    rect: Rectangle::new(center.x - size / 2., center.y - size / 2., size, size),
+                    size,
+                    collider: Self::collider(size, rng),
+                    velocity: self.velocity + direction * speed,
+                    mass: self.mass * size / total_size,
+                    rotation: self.rotation,
+                    rotation_velocity: self.rotation_velocity + rng.random_range(-50.0..50.0),
+                    color: self.color,
+                }
+            })
+            .collect()
+    }
+}
+
+impl Transformable for Asteroid {
+    fn rect(&self) -> &Rectangle { &self.rect }
+    fn rect_mut(&mut self) -> &mut Rectangle { &mut self.rect }
+    fn vel(&self) -> &Vector2 { &self.velocity }
+    fn vel_mut(&mut self) -> &mut Vector2 { &mut self.velocity }
+    fn mass(&self) -> &f32 { &self.mass }
+    fn rot_vel(&self) -> &f32 { &self.rotation_velocity }
+    fn rot_vel_mut(&mut self) -> &mut f32 { &mut self.rotation_velocity }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::WEAPONS;
+
+    fn asteroid_at(center: Vector2, size: f32) -> Entity {
+        let mut asteroid = Asteroid::new(1, size, 0., &mut StdRng::seed_from_u64(1));
+        asteroid.rect.x = center.x - size / 2.;
+        asteroid.rect.y = center.y - size / 2.;
+        Entity::Enemy(Box::new(asteroid))
+    }
+
+    #[test]
+    fn shot_faster_than_an_asteroid_is_wide_still_hits() {
+        let asteroid = asteroid_at(Vector2::new(300., 300.), 40.);
+        let spec = WEAPONS[0].projectile;
+        // The tail ended up well above the rock, having come from well below it this tick
+        let lazer = Lazer::new(2, Vector2::new(300., 240.), 0., &spec, 1, false);
+        let travel = 40. + spec.length + 120.;
+        let delta_time = travel / spec.speed;
+        let lazer = Entity::Projectile(Box::new(lazer));
+        assert!(travel > 40. * 3.);
+        assert!(collide_compound(&lazer.hitboxes_at(Vector2::zero()), &asteroid.hitboxes_at(Vector2::zero())).is_none());
+
+        assert!(lazer.check_collision(&asteroid, delta_time).is_some());
+        assert!(asteroid.check_collision(&lazer, delta_time).is_some());
+    }
+
+    #[test]
+    fn asteroids_bounce_apart_and_pick_up_spin() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut a = Asteroid::new(1, 40., 0., &mut rng);
+        let mut b = Asteroid::new(2, 40., 0., &mut rng);
+        (a.rect.x, a.rect.y, a.velocity, a.rotation_velocity) = (100., 100., Vector2::new(50., 20.), 0.);
+        (b.rect.x, b.rect.y, b.velocity, b.rotation_velocity) = (135., 100., Vector2::new(-50., 0.), 0.);
+        let momentum = a.velocity * a.mass + b.velocity * b.mass;
+        let contact = Contact { normal: Vector2::new(1., 0.), depth: 5., point: Vector2::new(137.5, 120.), time: 1., parts: [0, 0] };
+
+        crate::colliding_circles_2d(&mut a, &mut b, &contact, ASTEROID_RESTITUTION, ASTEROID_FRICTION);
+        assert!(a.velocity.x < 0. && b.velocity.x > 0.);
+        assert!(b.rect.x - a.rect.x > 35.);
+        assert!((a.velocity * a.mass + b.velocity * b.mass - momentum).length() < 0.01);
+        // Sliding past each other turns some of it into spin
+        assert!(a.rotation_velocity != 0. && b.rotation_velocity != 0.);
+    }
+
+    #[test]
+    fn shot_beside_an_asteroid_misses() {
+        let asteroid = asteroid_at(Vector2::new(300., 300.), 40.);
+        let spec = WEAPONS[0].projectile;
+        let lazer = Entity::Projectile(Box::new(Lazer::new(2, Vector2::new(340., 240.), 0., &spec, 1, false)));
+        assert!(lazer.check_collision(&asteroid, 200. / spec.speed).is_none());
+    }
+
+    fn boss_at(center: Vector2) -> Entity {
+        let mut boss = Boss::new(1);
+        boss.rect.x = center.x - boss.rect.width / 2.;
+        boss.rect.y = center.y - boss.rect.height / 2.;
+        Entity::Boss(Box::new(boss))
+    }
+
+    #[test]
+    fn fast_shot_up_the_middle_hits_the_belly_armor() {
+        let boss = boss_at(Vector2::new(600., 200.));
+        let Entity::Boss(b) = &boss else { unreachable!() };
+        let spec = WEAPONS[0].projectile;
+        // Came from below the boss and ended up above it within one tick
+        let lazer = Entity::Projectile(Box::new(Lazer::new(2, Vector2::new(600., 100.), 0., &spec, 1, false)));
+        let contact = lazer.check_collision(&boss, 300. / spec.speed).unwrap();
+        assert_eq!(b.parts[contact.parts[1]].kind, PartKind::Armor);
+    }
+
+    #[test]
+    fn shot_past_the_boss_bounds_misses() {
+        let boss = boss_at(Vector2::new(600., 200.));
+        let spec = WEAPONS[0].projectile;
+        let lazer = Entity::Projectile(Box::new(Lazer::new(2, Vector2::new(900., 100.), 0., &spec, 1, false)));
+        assert!(lazer.check_collision(&boss, 300. / spec.speed).is_none());
+        assert!(boss.check_collision(&lazer, 300. / spec.speed).is_none());
+    }
+
+    fn touching() -> Contact {
+        Contact { normal: Vector2::new(0., 1.), depth: 1., point: Vector2::zero(), time: 1., parts: [0, 0] }
+    }
+
+    #[test]
+    fn bomb_pickup_adds_a_bomb_and_no_effect() {
+        let mut world = crate::World::new(0);
+        let mut player = Entity::Player(Box::new(Player::new(0, 600., None, 3, 3)));
+        let mut pickup = Entity::Pickup(Box::new(Pickup::new(2, Vector2::new(600., 700.), PowerUp::Bomb)));
+        let events = player.on_collision(&mut pickup, &touching(), &mut world);
+        assert!(events.iter().any(|e| matches!(e, Event::EntityDestroyed(2))));
+        let Entity::Player(p) = &player else { unreachable!() };
+        assert_eq!(p.bombs, 4);
+        assert_eq!(p.effects.iter().count(), 0);
+    }
+
+    #[test]
+    fn piercing_shot_hurts_a_ship_once_while_passing_through() {
+        let mut world = crate::World::new(0);
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut ship = Entity::EnemyShip(Box::new(Ship::new(1, ShipBehavior::Strafe, Vector2::new(300., 100.), &mut rng)));
+        let spec = WEAPONS[0].projectile;
+        let mut lazer = Entity::Projectile(Box::new(Lazer::new(2, Vector2::new(300., 120.), 0., &spec, SHIP_HEALTH - 1, true)));
+
+        for _ in 0..5 {
+            assert!(lazer.on_collision(&mut ship, &touching(), &mut world).is_empty());
+        }
+        // A second shot finishes it, the first one only took its damage once
+        let mut lazer = Entity::Projectile(Box::new(Lazer::new(3, Vector2::new(300., 120.), 0., &spec, 1, true)));
+        let events = lazer.on_collision(&mut ship, &touching(), &mut world);
+        assert!(events.iter().any(|e| matches!(e, Event::EntityDestroyed(1))));
+    }
+
+    #[test]
+    fn any_hit_breaks_an_asteroid() {
+        let mut world = crate::World::new(0);
+        let mut asteroid = asteroid_at(Vector2::new(300., 300.), 120.);
+        let spec = WEAPONS[0].projectile;
+        let mut lazer = Entity::Projectile(Box::new(Lazer::new(2, Vector2::new(300., 360.), 0., &spec, 1, true)));
+
+        let events = asteroid.on_collision(&mut lazer, &touching(), &mut world);
+        assert!(events.iter().any(|e| matches!(e, Event::AsteroidDestroyed(1, _))));
+        // Still overlapping on the next tick, but it already broke this rock
+        assert!(asteroid.on_collision(&mut lazer, &touching(), &mut world).is_empty());
     }
 }
@@ -0,0 +1,280 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::time::Instant;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use raylib::prelude::*;
+
+use crate::{hull, Action, Entity, Input, PowerUp, World, MISSILE_CAPACITY, SCREEN_HEIGHT, SCREEN_WIDTH, TICK};
+
+// Gym style wrapper around the simulation for training agents. There is no window and no
+// frame timing, every `step` is one tick of the game's own rules for a single player.
+
+// Velocities are divided by this to keep them around -1..1 like the positions
+const SPEED_SCALE: f32 = 500.;
+// Sprite whose cached hull gives the ship the same hitbox as in the game, found next to
+// the sources so training runs from any directory get it
+const PLAYER_SPRITE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/player.png");
+
+#[derive(Debug, Clone)]
+pub struct EnvConfig {
+    // Asteroids closest to the ship in the observation, missing ones read as zeros
+    pub nearest_asteroids: usize,
+    // Enemy ships, bosses and hostile shots closest to the ship
+    pub nearest_threats: usize,
+    // Velocity of the ship and of everything it sees
+    pub velocities: bool,
+    // Weapon cooldown, missiles, bombs and the power-up timers
+    pub cooldowns: bool,
+    // Taken off the reward for every life lost
+    pub life_penalty: f32,
+    // Episodes end after this many ticks even while the ship is still flying, 0 for never
+    pub max_ticks: u32,
+    // Ships the player has per episode
+    pub lives: u32,
+    // Smart bombs the ship starts every episode with
+    pub starting_bombs: u32,
+    // Collider outline of the ship, None for the plain triangle
+    pub hull: Option<PathBuf>,
+}
+
+impl Default for EnvConfig {
+    fn default() -> Self {
+        Self {
+            nearest_asteroids: 8,
+            nearest_threats: 4,
+            velocities: true,
+            cooldowns: true,
+            life_penalty: 100.,
+            max_ticks: 60 * 60 * 5,
+            lives: 3,
+            starting_bombs: 3,
+            hull: Some(hull::hull_path(PLAYER_SPRITE)),
+        }
+    }
+}
+
+pub struct Env {
+    config: EnvConfig,
+    world: World,
+    outline: Option<Vec<Vector2>>,
+    // Actions held on the last step, holding one that was not counts as pressing it
+    held: HashSet<Action>,
+    ticks: u32,
+    score: usize,
+    lives: u32,
+}
+
+impl Env {
+    pub fn new(config: EnvConfig) -> Self {
+        // Training on another hitbox than the game's is worth a warning
+        let outline = config.hull.as_deref().and_then(|path| match hull::load(path) {
+            Ok(points) => Some(points),
+            Err(err) => {
+                eprintln!("Could not load the ship's hull from {}, it gets a plain triangle: {err}", path.display());
+                None
+            }
+        });
+        let mut env = Self {
+            world: World::new(0),
+            outline,
+            held: HashSet::new(),
+            ticks: 0,
+            score: 0,
+            lives: config.lives,
+            config,
+        };
+        env.reset(0);
+        env
+    }
+
+    // Numbers in every observation: ship position and lives, its velocity, the cooldowns,
+    // then per asteroid and per threat whether it is there, its offset and radius and its
+    // velocity
+    pub fn observation_len(&self) -> usize {
+        let velocity = if self.config.velocities { 2 } else { 0 };
+        let cooldowns = if self.config.cooldowns { 3 + PowerUp::TIMED.len() } else { 0 };
+        let seen = (self.config.nearest_asteroids + self.config.nearest_threats) * (4 + velocity);
+        3 + velocity + cooldowns + seen
+    }
+
+    // Starts an episode, the same seed always plays out the same for the same actions
+    pub fn reset(&mut self, seed: u64) -> Vec<f32> {
+        self.world = World::new(seed);
+        self.world.new_player(1, self.outline.as_deref(), self.config.starting_bombs, self.config.lives);
+        self.held.clear();
+        self.ticks = 0;
+        self.score = 0;
+        self.lives = self.config.lives;
+        self.observe()
+    }
+
+    // One tick with these actions held. The reward is the score gained minus the penalty
+    // for any life lost, done is set once the ship is out or the tick limit is reached.
+    pub fn step(&mut self, actions: &[Action]) -> (Vec<f32>, f32, bool) {
+        if self.is_done() {
+            return (self.observe(), 0., true);
+        }
+        let held: HashSet<Action> = actions.iter().copied().collect();
+        let input = Input::from_held(&held, &self.held);
+        self.held = held;
+        self.world.step(&[input], TICK);
+        self.ticks += 1;
+
+        let score = self.world.scoring[0].total();
+        let lives = self.world.player(0).map_or(0, |p| p.lives);
+        let reward = score as f32 - self.score as f32 - self.lives.saturating_sub(lives) as f32 * self.config.life_penalty;
+        self.score = score;
+        self.lives = lives;
+        (self.observe(), reward, self.is_done())
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.world.is_over() || (self.config.max_ticks > 0 && self.ticks >= self.config.max_ticks)
+    }
+
+    // Current state, for drawing an episode or rewards of your own
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    // Positions are offsets from the ship in screen sizes, a missing ship reads as zeros
+    fn observe(&self) -> Vec<f32> {
+        let mut out = Vec::with_capacity(self.observation_len());
+        let size = Vector2::new(SCREEN_WIDTH!(), SCREEN_HEIGHT!());
+        let mut ship = None;
+        let mut asteroids = vec![];
+        let mut threats = vec![];
+        for entity in self.world.entities.values() {
+            let (rect, list) = match entity {
+                Entity::Player(p) if p.slot == 0 => {
+                    ship = Some((p, entity.velocity()));
+                    continue;
+                }
+                Entity::Enemy(a) => (a.rect, &mut asteroids),
+                Entity::EnemyShip(s) => (s.rect, &mut threats),
+                Entity::Boss(b) => (b.rect, &mut threats),
+                Entity::Projectile(l) if l.is_hostile() => (l.rect, &mut threats),
+                _ => continue,
+            };
+            list.push((rect, entity.velocity()));
+        }
+
+        let center = |rect: &Rectangle| Vector2::new(rect.x + rect.width / 2., rect.y + rect.height / 2.);
+        let origin = ship.map_or(size / 2., |(p, _)| center(&p.rect));
+        out.extend([origin.x / size.x, origin.y / size.y, ship.map_or(0., |(p, _)| p.lives as f32)]);
+        if self.config.velocities {
+            let velocity = ship.map_or(Vector2::zero(), |(_, v)| v) / SPEED_SCALE;
+            out.extend([velocity.x, velocity.y]);
+        }
+        if self.config.cooldowns {
+            match ship {
+                Some((p, _)) => {
+                    out.extend([
+                        p.fire_cooldown.max(0.),
+                        p.missiles as f32 / MISSILE_CAPACITY as f32,
+                        p.bombs as f32,
+                    ]);
+                    for power_up in PowerUp::TIMED {
+                        let left = p.effects.iter().find(|(active, _)| *active == power_up).map_or(0., |(_, t)| *t);
+                        out.push(power_up.duration().map_or(0., |duration| left / duration));
+                    }
+                }
+                None => out.extend([0.; 3 + PowerUp::TIMED.len()]),
+            }
+        }
+
+        for (mut list, nearest) in [(asteroids, self.config.nearest_asteroids), (threats, self.config.nearest_threats)] {
+            list.sort_by(|(a, _), (b, _)| {
+                let distance = |rect| (center(rect) - origin).length_sqr();
+                distance(a).total_cmp(&distance(b))
+            });
+            for i in 0..nearest {
+                let Some((rect, velocity)) = list.get(i) else {
+                    out.extend(std::iter::repeat_n(0., if self.config.velocities { 6 } else { 4 }));
+                    continue;
+                };
+                let offset = center(rect) - origin;
+                out.extend([1., offset.x / size.x, offset.y / size.y, rect.width.max(rect.height) / 2. / size.x]);
+                if self.config.velocities {
+                    out.extend([velocity.x / SPEED_SCALE, velocity.y / SPEED_SCALE]);
+                }
+            }
+        }
+        out
+    }
+}
+
+// Headless speed check: plays episodes with random actions for this many steps and
+// returns the steps per second
+pub fn run_benchmark(steps: u32) -> f64 {
+    let mut env = Env::new(EnvConfig::default());
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut actions = vec![];
+    let start = Instant::now();
+    for _ in 0..steps {
+        // Keep an action for a while, as an agent would, instead of flickering every tick
+        if rng.random_bool(0.1) {
+            actions = Action::GAMEPLAY.into_iter().filter(|_| rng.random_bool(0.3)).collect();
+        }
+        let (_, _, done) = env.step(&actions);
+        if done {
+            env.reset(rng.random());
+        }
+    }
+    steps as f64 / start.elapsed().as_secs_f64()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Observations and rewards of an episode played with random actions from `script`
+    fn play(seed: u64, script: u64, steps: u32) -> Vec<(Vec<f32>, f32)> {
+        let mut env = Env::new(EnvConfig::default());
+        let mut rng = StdRng::seed_from_u64(script);
+        let mut seen = vec![(env.reset(seed), 0.)];
+        let mut actions = vec![];
+        for _ in 0..steps {
+            if rng.random_bool(0.1) {
+                actions = Action::GAMEPLAY.into_iter().filter(|_| rng.random_bool(0.3)).collect();
+            }
+            let (observation, reward, done) = env.step(&actions);
+            seen.push((observation, reward));
+            if done {
+                break;
+            }
+        }
+        seen
+    }
+
+    #[test]
+    fn same_seed_and_actions_play_out_the_same() {
+        let first = play(7, 1, 2000);
+        assert_eq!(first, play(7, 1, 2000));
+        let len = Env::new(EnvConfig::default()).observation_len();
+        assert!(first.iter().all(|(observation, _)| observation.len() == len));
+    }
+
+    #[test]
+    fn other_seeds_play_out_differently() {
+        assert_ne!(play(7, 1, 300), play(8, 1, 300));
+    }
+
+    #[test]
+    fn observation_has_the_configured_length() {
+        let config = EnvConfig { nearest_asteroids: 3, nearest_threats: 1, velocities: false, cooldowns: false, ..EnvConfig::default() };
+        let mut env = Env::new(config);
+        assert_eq!(env.observation_len(), 3 + 4 * 4);
+        assert_eq!(env.reset(1).len(), env.observation_len());
+        assert_eq!(env.step(&[Action::Fire]).0.len(), env.observation_len());
+    }
+
+    #[test]
+    fn hull_is_found_from_any_directory() {
+        assert!(Env::new(EnvConfig::default()).outline.is_some());
+        assert!(Env::new(EnvConfig { hull: None, ..EnvConfig::default() }).outline.is_none());
+        let missing = EnvConfig { hull: Some(PathBuf::from("no/such.hull")), ..EnvConfig::default() };
+        assert!(Env::new(missing).outline.is_none());
+    }
+}
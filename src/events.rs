@@ -2,39 +2,53 @@
 
 use std::collections::HashMap;
 
+use raylib::prelude::Vector2;
+
 use crate::EntityId;
 
 #[derive(Clone, Copy, Hash, PartialEq, Eq)]
 pub enum EventType {
 	EntityDestroyed,
-	NumberOfAsteroidsIncreased,
-	ResetAsteroid,
+	AsteroidDestroyed,
 	ScoreIncreased,
-	GameOver,
+	Explosion,
+	PlayerHit,
 }
 
 pub enum Event {
 	EntityDestroyed(EntityId),
-	NumberOfAsteroidsIncreased,
-	ResetAsteroid(EntityId),
-	ScoreIncreased,
-	GameOver,
+	// Asteroid hit at the given point, splits into fragments or vanishes
+	AsteroidDestroyed(EntityId, Vector2),
+	// Player slot credited with the kill and its base points, before the combo multiplier
+	ScoreIncreased(usize, usize),
+	// Blast set off by a player slot at a point, with its radius and the damage dealt to
+	// everything inside
+	Explosion(usize, Vector2, f32, u32),
+	// Player slot hit by a hazard without a shield to take it
+	PlayerHit(usize),
 }
 
-type EventCallback = Box<dyn Fn(&Event, &mut crate::Game)>;
+// `C` is whatever the listeners get to change, the game for the game's own bus
+type EventCallback<C> = Box<dyn Fn(&Event, &mut C)>;
 
-pub struct EventBus {
-	listeners: HashMap<EventType, Vec<EventCallback>>,
+pub struct EventBus<C> {
+	listeners: HashMap<EventType, Vec<EventCallback<C>>>,
+}
+
+impl<C> Default for EventBus<C> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-impl EventBus {
+impl<C> EventBus<C> {
     pub fn new() -> Self {
         Self {
             listeners: HashMap::new(),
         }    
     }
     
-    pub fn subscribe(&mut self, event_type: EventType, callback: EventCallback) {
+    pub fn subscribe(&mut self, event_type: EventType, callback: EventCallback<C>) {
         if let Some(callbacks) = self.listeners.get_mut(&event_type){
         	callbacks.push(callback);    
         } else {
@@ -42,7 +56,7 @@ impl EventBus {
         }
     }    
 
-    pub fn publish(&mut self, event_type: EventType, event: Event, ctx: &mut crate::Game) {
+    pub fn publish(&mut self, event_type: EventType, event: Event, ctx: &mut C) {
     	for callback in self.listeners.get_mut(&event_type).unwrap() {
     		callback(&event, ctx);
     	}
@@ -0,0 +1,668 @@
+use std::collections::HashMap;
+use raylib::prelude::*;
+
+use crate::*;
+
+// Ticks a slow frame may catch up on before the game slows down instead
+const MAX_FRAME_TICKS: u32 = 4;
+
+struct Game {
+    rl: RaylibHandle,
+    rt: RaylibThread,
+    event_bus: EventBus<Game>,
+    world: World,
+    // Background only, kept out of the world so save states stay small
+    stars: Vec<Entity>,
+    textures: Textures,
+    hulls: Hulls,
+    camera: Camera2D,
+    paused: bool,
+    // One per local player
+    controls: Vec<Controls>,
+    // Open on top of the pause menu
+    controls_screen: Option<ControlsScreen>,
+    // What each player asked for this frame by slot, menus follow the first player
+    inputs: Vec<Input>,
+    // Inputs of the last frame if it was too short for a tick
+    carried: Vec<Input>,
+    // Frame time not yet simulated, less than a tick
+    accumulator: f32,
+    menu: Menu,
+    quit: bool,
+    highscores: HighScores,
+    config: Config,
+    over: bool,
+    // Online game run from this instance, remote players take the slots after the local ones
+    host: Option<Host>,
+    // Snapshots sent so far, numbers them for clients
+    tick: u32,
+    // Versus game against another instance with rollback, no pausing while it runs
+    peer: Option<Session<UdpTransport>>,
+    // Viewers watching this game
+    broadcast: Option<Broadcaster>,
+    // Plays the first slot one tick per frame, the game waits for it every tick
+    bot: Option<Bot>,
+}
+
+impl Game {
+    fn new() -> Self {
+        let (mut rl, rt) = raylib::init()
+            .title("Asteroids")
+            .size(SCREEN_WIDTH!() as i32, SCREEN_HEIGHT!() as i32)
+            .vsync()
+            .build();
+        rl.set_target_fps(60);
+        let config = Config::load();
+        // Pointer control would steer every ship to the same spot, it is for the first player only
+        let controls = (0..config.players)
+            .map(|slot| Controls::new(Keymap::load(slot), config.gamepad_deadzone, config.pointer_controls && slot == 0))
+            .collect();
+
+        Self {
+            rl, rt,
+            event_bus: EventBus::new(),
+            world: World::new(rand::random()),
+            stars: vec![],
+            textures: HashMap::new(),
+            hulls: HashMap::new(),
+            camera: Camera2D::default(),
+            highscores: HighScores::load(),
+            controls,
+            controls_screen: None,
+            inputs: vec![Input::default(); config.players],
+            carried: vec![],
+            accumulator: 0.,
+            menu: Menu::pause(config.players),
+            quit: false,
+            config,
+            paused: false,
+            over: false,
+            host: None,
+            tick: 0,
+            peer: None,
+            broadcast: None,
+            bot: None,
+        }    
+    }
+
+    fn toggle_fullscreen(&mut self) {
+        self.rl.toggle_fullscreen();
+    }
+
+    fn load_assets(&mut self) {
+        self.textures = load_textures(&mut self.rl, &self.rt);
+        for (name, filename) in SPRITES {
+            if let Some(hull) = hull::load_or_trace(filename) {
+                self.hulls.insert(name, hull);
+            }
+        }
+    }
+
+    fn setup(&mut self) {
+        self.stars = star_field();
+
+        for _ in 0..self.config.players {
+            let outline = self.hulls.get("player").map(Vec::as_slice);
+            self.world.new_player(self.config.players, outline, self.config.starting_bombs, self.config.lives);
+        }
+        self.share_keyboard();
+
+        if let Some(player) = self.world.player(0) {
+            self.camera.target = Vector2::new(player.rect.x + 20.0, player.rect.y + 20.0);
+            self.camera.offset = Vector2::new(SCREEN_WIDTH!()/2.0, SCREEN_HEIGHT!() - player.rect.height - 40.);
+            self.camera.rotation = 0.0;
+            self.camera.zoom = 0.5;    
+        };
+
+        self.event_bus.subscribe(EventType::EntityDestroyed, Box::new(|_e: &Event, _ctx: &mut Game|{
+            
+        }));
+    }
+    
+    // Co-op runs are scored as a team, versus runs by the winner
+    fn total_score(&self) -> usize {
+        if self.config.versus {
+            return self.world.scoring.iter().map(Scoring::total).max().unwrap_or(0);
+        }
+        self.world.scoring.iter().map(Scoring::total).sum()
+    }
+
+    // Slot with the best score
+    fn winner(&self) -> usize {
+        self.world.scoring.iter()
+            .enumerate()
+            .max_by_key(|(_, s)| s.total())
+            .map_or(0, |(slot, _)| slot)
+    }
+
+    // Both players on one keyboard: the first one flies without the keys the second one flies
+    // with, but still navigates menus with them
+    fn share_keyboard(&mut self) {
+        if let [first, second] = self.controls.as_mut_slice() {
+            first.ignored_keys = second.keymap.keys_for(&Action::GAMEPLAY);
+        }
+    }
+
+    fn poll_inputs(&mut self) {
+        let delta_time = self.rl.get_frame_time();
+        let gamepads: Vec<Option<i32>> = self.controls.iter().map(Controls::gamepad).collect();
+        self.inputs = self.controls.iter_mut()
+            .enumerate()
+            .map(|(slot, controls)| {
+                // Never the gamepad the other player has
+                let taken = gamepads.iter()
+                    .enumerate()
+                    .find(|(other, _)| *other != slot)
+                    .and_then(|(_, gamepad)| *gamepad);
+                controls.poll(&self.rl, delta_time, taken)
+            })
+            .collect();
+
+        if let Some(host) = self.host.as_mut() {
+            host.receive();
+            self.inputs.resize(self.config.players, Input::default());
+            for (slot, input) in host.inputs() {
+                if let Some(target) = self.inputs.get_mut(slot) {
+                    *target = input;
+                }
+            }
+            // A player who left is out, the others play on
+            for slot in host.departed() {
+                if let Some(&id) = self.world.player_ids.get(slot) {
+                    self.world.remove_entity(id);
+                }
+            }
+        }
+        for (input, earlier) in self.inputs.iter_mut().zip(std::mem::take(&mut self.carried)) {
+            input.carry(&earlier);
+        }
+    }
+
+    // Whole ticks due this frame
+    fn ticks(&mut self) -> u32 {
+        let most = MAX_FRAME_TICKS as f32 * TICK;
+        self.accumulator = (self.accumulator + self.rl.get_frame_time()).min(most);
+        let ticks = (self.accumulator / TICK) as u32;
+        self.accumulator -= ticks as f32 * TICK;
+        ticks
+    }
+
+    // Waits for a bot to connect, then for clients to join until the host confirms, or for
+    // the peer of a versus game to answer. False when the window was closed.
+    fn lobby(&mut self) -> bool {
+        while !self.rl.window_should_close() {
+            self.poll_inputs();
+            let lines = if let Some(bot) = self.bot.as_mut() && !bot.accept() {
+                [
+                    "BOT".to_string(),
+                    format!("waiting for a bot on port {}", bot.port().unwrap_or(BOT_PORT)),
+                    "It plays the first player once it connects".to_string(),
+                ]
+            } else if let Some(peer) = self.peer.as_mut() {
+                let rules = Rules {
+                    lives: self.config.lives,
+                    bombs: self.config.starting_bombs,
+                    outline: self.hulls.get("player").cloned(),
+                };
+                if let Some((seed, rules)) = peer.handshake(&rules) {
+                    // Both sides start from the same world and step it alike from here on
+                    self.world = World::new(seed);
+                    self.config.lives = rules.lives;
+                    self.config.starting_bombs = rules.bombs;
+                    match rules.outline {
+                        Some(outline) => self.hulls.insert("player", outline),
+                        None => self.hulls.remove("player"),
+                    };
+                    self.config.versus = true;
+                    self.set_players(2);
+                    return true;
+                }
+                [
+                    "VERSUS".to_string(),
+                    format!("waiting for {}", peer.transport().peer()),
+                    "Start the other side with the ports swapped".to_string(),
+                ]
+            } else if let Some(host) = self.host.as_mut() {
+                if self.inputs[0].pressed(Action::Confirm) {
+                    host.start();
+                    let players = host.players();
+                    self.set_players(players);
+                    return true;
+                }
+                [
+                    format!("HOSTING ON PORT {}", host.port()),
+                    format!("players: {}/{}", host.players(), PLAYER_TINTS.len()),
+                    "Press ENTER or A to start".to_string(),
+                ]
+            } else {
+                return true;
+            };
+            let mut d = self.rl.begin_drawing(&self.rt);
+            d.clear_background(Color::BLACK);
+            for (row, line) in lines.iter().enumerate() {
+                let size = if row == 0 { 40 } else { 30 };
+                d.draw_text(
+                    line,
+                    (SCREEN_WIDTH!()/2. - 220.) as i32,
+                    (SCREEN_HEIGHT!()/2. + row as f32 * 50.) as i32,
+                    size, Color::WHITE
+                );
+            }
+        }
+        false
+    }
+
+    // Online and versus games have one local player, everyone else plays from their own machine
+    fn set_players(&mut self, players: usize) {
+        self.config.players = players;
+        self.controls.truncate(1);
+        self.inputs = vec![Input::default(); players];
+        self.menu = Menu::pause(1);
+    }
+
+    // To the clients of an online game and to the spectators
+    fn send_snapshot(&mut self) {
+        if self.host.is_none() && self.broadcast.is_none() {
+            return;
+        }
+        self.tick += 1;
+        let time = self.rl.get_time() as f32;
+        let snapshot = Snapshot::capture(self.tick, time, &self.world, self.over, self.config.versus);
+        if let Some(broadcast) = self.broadcast.as_mut() {
+            broadcast.send(snapshot.clone());
+        }
+        if let Some(host) = self.host.as_mut() {
+            host.send(snapshot);
+        }
+    }
+
+    // Any player pressed it this frame
+    fn pressed(&self, action: Action) -> bool {
+        self.inputs.iter().any(|input| input.pressed(action))
+    }
+
+    fn game_over(&mut self) {
+        self.over = true;
+        if let Some(mut bot) = self.bot.take() {
+            bot.finish(&self.world);
+        }
+        self.highscores.add(self.total_score(), self.world.waves.wave.number, self.config.players);
+        if let Err(err) = self.highscores.save() {
+            eprintln!("Could not save high scores: {err}");
+        }
+    }
+
+    fn update(&mut self) {
+        if self.pressed(Action::Fullscreen) {
+            if !self.paused && self.peer.is_none() {
+                self.paused = true;                
+            }
+
+            self.toggle_fullscreen();
+        }
+
+        if self.over {
+            // The peer may still need our last inputs to see the same ending
+            if let Some(peer) = self.peer.as_mut() {
+                peer.poll();
+            }
+            return;
+        }
+
+        if let Some(screen) = self.controls_screen.as_mut() {
+            let controls = &mut self.controls[screen.slot];
+            let captured = screen.capturing.and_then(|_| controls.capture(&mut self.rl));
+            if screen.update(&self.inputs[0], &mut controls.keymap, captured) {
+                if let Err(err) = controls.keymap.save() {
+                    eprintln!("Could not save the controls: {err}");
+                }
+                self.controls_screen = None;
+                self.share_keyboard();
+            }
+            return;
+        }
+
+        if self.pressed(Action::Pause) && self.peer.is_none() {
+            self.paused = !self.paused;
+            self.menu.selected = 0;
+        } else if self.paused {
+            match self.menu.navigate(&self.inputs[0]) {
+                Some(MenuItem::Resume) => self.paused = false,
+                Some(MenuItem::Controls(slot)) => self.controls_screen = Some(ControlsScreen::new(slot)),
+                Some(MenuItem::Quit) => self.quit = true,
+                None => (),
+            }
+        }
+
+        if self.paused {
+            return;
+        }
+
+        let delta_time = self.rl.get_frame_time();
+        for star in self.stars.iter_mut() {
+            star.update(&[], delta_time);
+        }
+        // A bot gets exactly one tick per frame, however long it took to answer
+        let ticks = if self.bot.is_some() { 1 } else { self.ticks() };
+        for tick in 0..ticks {
+            let mut inputs: Vec<Input> = if tick == 0 {
+                self.inputs.clone()
+            } else {
+                self.inputs.iter().map(Input::held).collect()
+            };
+            if let Some(bot) = self.bot.as_mut() {
+                match bot.exchange(&self.world) {
+                    Ok(input) => inputs[0] = input,
+                    Err(err) => {
+                        // The keyboard takes over again
+                        eprintln!("The bot left: {err}");
+                        self.bot = None;
+                    }
+                }
+            }
+            match self.peer.as_mut() {
+                Some(peer) => {
+                    if !peer.advance(&mut self.world, &inputs[0]) {
+                        break;
+                    }
+                }
+                None => self.world.step(&inputs, TICK),
+            }
+        }
+        // Presses from a frame too short for a tick are kept for the next one
+        self.carried = if ticks == 0 { self.inputs.clone() } else { vec![] };
+
+        let settled = self.peer.as_ref().is_none_or(Session::is_settled);
+        if self.world.is_over() && settled {
+            self.game_over();
+        }
+        if self.peer.as_ref().is_some_and(Session::is_lost) {
+            self.game_over();
+        }
+    }
+
+    fn draw(&mut self) {
+        let total_score = self.total_score();
+        let winner = self.winner();
+        let left = self.peer.as_ref().is_some_and(Session::is_lost);
+        // Only the players at this machine have controls here, in a versus game that is the
+        // one in the peer session's slot
+        let local = self.peer.as_ref().map_or(0, Session::slot);
+        let live = self.broadcast.as_ref().map(|b| format!("LIVE :{}  {} watching", b.port(), b.viewers()));
+        let mut d = self.rl.begin_drawing(&self.rt);
+        d.clear_background(Color::BLACK);
+
+        for star in &self.stars {
+            star.draw(&mut d, &self.textures);
+        }
+        for (_, entity) in self.world.entities.iter() {
+            entity.draw(&mut d, &self.textures);
+        }
+
+        // {
+        //     let mut m = d.begin_mode2D(self.camera);
+
+        //     for (_, entity) in self.world.entities.iter().filter(|(_, e)| e.is_camera_affected()) {
+        //         entity.draw(&mut m, &self.textures);
+        //     }
+        // }
+
+        // Draw UI, the second player's panel is on the right
+        for (slot, scoring) in self.world.scoring.iter().enumerate() {
+            let x = if slot == 0 { 35 } else { SCREEN_WIDTH!() as i32 - 420 };
+            let keymap = self.controls.get(slot.wrapping_sub(local)).map(|c| &c.keymap);
+            Self::draw_player_hud(&mut d, x, scoring, self.world.player(slot), keymap);
+        }
+        d.draw_text(&format!("wave: {}", self.world.waves.wave.number), 35, 30, 20, Color::WHITE);
+        d.draw_fps(35, 50);
+
+        let boss = self.world.entities.values().find_map(|e| match e {
+            Entity::Boss(boss) => Some(boss),
+            _ => None,
+        });
+        if let Some(boss) = boss {
+            let bar = Rectangle::new(SCREEN_WIDTH!() / 2. - 200., 20., 400., 14.);
+            d.draw_rectangle_rec(Rectangle { width: bar.width * boss.health(), ..bar }, Color::RED);
+            d.draw_rectangle_lines_ex(bar, 2., Color::WHITE);
+        }
+
+        if !self.over && let Some(banner) = self.world.waves.banner() {
+            d.draw_text(
+                &banner,
+                (SCREEN_WIDTH!()/2. - 90.) as i32,
+                (SCREEN_HEIGHT!()/2. - 100.) as i32,
+                40, Color::WHITE
+            );
+        }
+
+        if self.over {
+            let title = match self.world.scoring.len() {
+                _ if left => "YOUR OPPONENT LEFT".to_string(),
+                1 => format!("YOUR SCORE: {total_score}"),
+                _ if self.config.versus => format!("PLAYER {} WINS: {total_score}", winner + 1),
+                _ => format!("TEAM SCORE: {total_score}"),
+            };
+            d.draw_text(
+                &title, 
+                (SCREEN_WIDTH!()/2. - 180.) as i32, 
+                (SCREEN_HEIGHT!()/2.) as i32, 
+                40, Color::WHITE
+            );
+            d.draw_text(
+                "Press any key or START to exit", 
+                (SCREEN_WIDTH!()/2. - 180.) as i32, 
+                (SCREEN_HEIGHT!()/2. + 40.) as i32, 
+                30, Color::WHITE
+            );
+            d.draw_text(
+                "HIGH SCORES",
+                (SCREEN_WIDTH!()/2. - 180.) as i32,
+                (SCREEN_HEIGHT!()/2. + 100.) as i32,
+                20, Color::WHITE
+            );
+            for (rank, entry) in self.highscores.entries.iter().take(5).enumerate() {
+                let co_op = if entry.players > 1 { format!("  {}P", entry.players) } else { String::new() };
+                d.draw_text(
+                    &format!("{}. {:>8}   wave {}{co_op}", rank + 1, entry.score, entry.wave),
+                    (SCREEN_WIDTH!()/2. - 180.) as i32,
+                    (SCREEN_HEIGHT!()/2. + 125. + rank as f32 * 22.) as i32,
+                    20, Color::WHITE
+                );
+            }
+            let breakdown = self.world.scoring.iter().fold(Breakdown::default(), |sum, s| sum + s.breakdown);
+            let mut lines = vec![
+                ("kills".to_string(), breakdown.kills),
+                ("combo bonus".to_string(), breakdown.combo),
+                ("streak bonus".to_string(), breakdown.streak),
+                ("near misses".to_string(), breakdown.near_misses),
+            ];
+            if self.world.scoring.len() > 1 {
+                lines.extend(self.world.scoring.iter().enumerate().map(|(slot, s)| (format!("player {}", slot + 1), s.total())));
+            }
+            for (row, (label, points)) in lines.iter().enumerate() {
+                d.draw_text(
+                    &format!("{label:<14}{points:>8}"),
+                    (SCREEN_WIDTH!()/2. + 120.) as i32,
+                    (SCREEN_HEIGHT!()/2. + 100. + row as f32 * 22.) as i32,
+                    20, Color::WHITE
+                );
+            }
+        } else if let Some(screen) = &self.controls_screen {
+            screen.draw(&mut d, &self.controls[screen.slot].keymap);
+        } else if self.paused {
+            self.menu.draw(&mut d);
+        }
+
+        if let Some(live) = live {
+            d.draw_text(&live, SCREEN_WIDTH!() as i32 - 260, (SCREEN_HEIGHT!() - 35.) as i32, 20, Color::RED);
+        }
+
+        let notices = self.controls.iter().filter_map(|c| c.notice.as_ref());
+        for (row, (notice, _)) in notices.enumerate() {
+            d.draw_text(notice, 35, (SCREEN_HEIGHT!() - 35.) as i32 - row as i32 * 25, 20, Color::GRAY);
+        }
+    }
+
+    // Score, lives, weapons and effects of one player, with the first key of each action
+    // Remote players have no keymap here and go without the key hints
+    fn draw_player_hud(d: &mut RaylibDrawHandle, x: i32, scoring: &Scoring, player: Option<&Player>, keymap: Option<&Keymap>) {
+        d.draw_text(&format!("score: {}", scoring.total()), x, 10, 20, Color::WHITE);
+        d.draw_text(&format!("x{:.1}", scoring.multiplier), x + 185, 10, 20, Color::GOLD);
+        d.draw_rectangle(x + 235, 14, (60. * scoring.combo_timer / COMBO_WINDOW) as i32, 12, Color::GOLD);
+        if scoring.streak > 0 {
+            d.draw_text(&format!("streak: {}", scoring.streak), x + 305, 10, 20, Color::WHITE);
+        }
+        let Some(player) = player else {
+            d.draw_text("out", x, 75, 20, Color::GRAY);
+            return;
+        };
+        let key = |action: Action| keymap
+            .and_then(|k| k.bindings_for(action).find(|b| matches!(b, Binding::Key(_))))
+            .map_or(String::new(), |b| format!(" ({})", b.name()));
+        d.draw_text(&format!("lives: {}", player.lives), x, 75, 20, PLAYER_TINTS[player.slot]);
+        d.draw_text(&format!("weapon: {}{}", WEAPONS[player.weapon].name, key(Action::SwitchWeapon)), x, 95, 20, Color::WHITE);
+        d.draw_text(&format!("missiles: {}/{}{}", player.missiles, MISSILE_CAPACITY, key(Action::Missile)), x, 115, 20, Color::GOLD);
+        d.draw_text(&format!("bombs: {}{}", player.bombs, key(Action::Bomb)), x, 135, 20, Color::PINK);
+        for (i, (power_up, remaining)) in player.effects.iter().enumerate() {
+            d.draw_text(
+                &format!("{} {:.1}s", power_up.name(), remaining),
+                x, 160 + i as i32 * 20,
+                20, power_up.color()
+            );
+        }
+    }
+
+    fn run(&mut self) {
+        // Before the lobby, a versus game agrees on the ship's outline there
+        self.load_assets();
+        if !self.lobby() {
+            return;
+        }
+        self.setup();
+
+        while !self.rl.window_should_close() && !self.quit {
+            self.poll_inputs();
+            // START on a gamepad, the keyboard leaves with any key but the flight controls
+            if self.over && self.pressed(Action::Pause) {
+                break;
+            }
+            if self.over
+                && let Some(key) = self.rl.get_key_pressed()
+                && !self.controls.iter().any(|c| c.keymap.is_bound(key, &Action::GAMEPLAY))
+            {
+                break;
+            }
+            self.update();
+            self.send_snapshot();
+            self.draw();
+        }
+    }
+}
+
+impl Drop for Game {
+    fn drop(&mut self) {
+        self.textures.clear();
+    }
+}
+
+// The whole game as the binary runs it, the command line picks what it does
+pub fn run() {
+    // Asset tool: rebuild the collider outlines next to the sprites and exit
+    if std::env::args().any(|arg| arg == "--trace-hulls") {
+        if let Err(err) = hull::trace_assets("assets") {
+            eprintln!("Could not trace hulls: {err}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // Online play: `--host [port]` runs the game for others to join, `--join host[:port]` joins one
+    let args: Vec<String> = std::env::args().collect();
+    let value_of = |flag: &str| args.iter()
+        .position(|arg| arg == flag)
+        .map(|i| args.get(i + 1).filter(|value| !value.starts_with("--")));
+    if let Some(address) = value_of("--join") {
+        let address = address.map_or("127.0.0.1", String::as_str);
+        match Connection::connect(address) {
+            Ok(connection) => RemoteGame::new(connection, address).run(),
+            Err(err) => {
+                eprintln!("Could not join {address}: {err}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    // Watch a game that broadcasts, `--spectate [host[:port] | file] [--record file]`
+    if let Some(source) = value_of("--spectate") {
+        let source = source.map_or("127.0.0.1", String::as_str);
+        let record = value_of("--record").flatten().map(String::as_str);
+        match Spectator::open(source, record) {
+            Ok(mut spectator) => spectator.run(),
+            Err(err) => {
+                eprintln!("Could not watch {source}: {err}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    // Training environment check: steps per second without a window, `--env-bench [steps]`
+    if let Some(steps) = value_of("--env-bench") {
+        let steps = steps.and_then(|s| s.parse().ok()).unwrap_or(100_000);
+        println!("{:.0} steps per second over {steps} steps", run_benchmark(steps));
+        return;
+    }
+
+    let mut game = Game::new();
+    // Versus with rollback: `--peer port host[:port]` on both machines, each with its own port
+    if let Some(port) = value_of("--peer") {
+        let port = port.and_then(|p| p.parse().ok()).unwrap_or(DEFAULT_PORT);
+        let address = args.iter().skip_while(|arg| *arg != "--peer").nth(2).map_or("127.0.0.1", String::as_str);
+        match UdpTransport::bind(port, address) {
+            Ok(transport) => game.peer = Some(Session::new(transport, rand::random())),
+            Err(err) => {
+                eprintln!("Could not play against {address} from port {port}: {err}");
+                std::process::exit(1);
+            }
+        }
+    }
+    if let Some(port) = value_of("--broadcast") {
+        let port = port.and_then(|p| p.parse().ok()).unwrap_or(SPECTATOR_PORT);
+        match Broadcaster::bind(port) {
+            Ok(broadcast) => game.broadcast = Some(broadcast),
+            Err(err) => {
+                eprintln!("Could not broadcast on port {port}: {err}");
+                std::process::exit(1);
+            }
+        }
+    }
+    // Remote control, `--bot [port | stdio]` hands the first player to a program speaking
+    // JSON lines over a local socket or this process' stdin and stdout
+    if let Some(source) = value_of("--bot") {
+        match source.map(String::as_str) {
+            Some("stdio") => game.bot = Some(Bot::stdio()),
+            port => {
+                let port = port.and_then(|p| p.parse().ok()).unwrap_or(BOT_PORT);
+                match Bot::listen(port) {
+                    Ok(bot) => game.bot = Some(bot),
+                    Err(err) => {
+                        eprintln!("Could not wait for a bot on port {port}: {err}");
+                        std::process::exit(1);
+                    }
+                }
+            }
+        }
+    }
+    if let Some(port) = value_of("--host") {
+        let port = port.and_then(|p| p.parse().ok()).unwrap_or(DEFAULT_PORT);
+        match Host::bind(port) {
+            Ok(host) => game.host = Some(host),
+            Err(err) => {
+                eprintln!("Could not host on port {port}: {err}");
+                std::process::exit(1);
+            }
+        }
+    }
+    game.run();    
+}
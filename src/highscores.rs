@@ -0,0 +1,107 @@
+use std::fs;
+use std::io;
+
+// Best runs, kept in a plain text file next to the game with one `score wave players` entry
+// per line. Files from before co-op have no player count, those runs were single player.
+const HIGHSCORES_FILE: &str = "highscores.txt";
+const MAX_ENTRIES: usize = 10;
+
+#[derive(Debug, Clone, Copy)]
+pub struct HighScore {
+    pub score: usize,
+    pub wave: u32,
+    // Co-op runs are scored as a team
+    pub players: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct HighScores {
+    pub entries: Vec<HighScore>,
+}
+
+impl HighScores {
+    // A missing or unreadable file just means no high scores yet
+    pub fn load() -> Self {
+        let entries = fs::read_to_string(HIGHSCORES_FILE)
+            .map(|text| {
+                text.lines()
+                    .filter_map(|line| {
+                        let mut parts = line.split_whitespace();
+                        Some(HighScore {
+                            score: parts.next()?.parse().ok()?,
+                            wave: parts.next()?.parse().ok()?,
+                            players: parts.next().map_or(Some(1), |p| p.parse().ok())?,
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let mut highscores = Self { entries };
+        highscores.sort();
+        highscores
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let text: String = self.entries.iter()
+            .map(|e| format!("{} {} {}\n", e.score, e.wave, e.players))
+            .collect();
+        fs::write(HIGHSCORES_FILE, text)
+    }
+
+    // Record a finished run, returns its rank if it made the table. Ties go below the runs
+    // that got there first.
+    pub fn add(&mut self, score: usize, wave: u32, players: usize) -> Option<usize> {
+        let rank = self.entries.partition_point(|e| (e.score, e.wave) >= (score, wave));
+        if rank >= MAX_ENTRIES {
+            return None;
+        }
+        self.entries.insert(rank, HighScore { score, wave, players });
+        self.entries.truncate(MAX_ENTRIES);
+        Some(rank)
+    }
+
+    fn sort(&mut self) {
+        self.entries.sort_by(|a, b| b.score.cmp(&a.score).then(b.wave.cmp(&a.wave)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_ranks_by_score_then_wave() {
+        let mut highscores = HighScores::default();
+        assert_eq!(highscores.add(500, 3, 1), Some(0));
+        assert_eq!(highscores.add(900, 4, 1), Some(0));
+        assert_eq!(highscores.add(500, 5, 2), Some(1));
+        let scores: Vec<(usize, u32)> = highscores.entries.iter().map(|e| (e.score, e.wave)).collect();
+        assert_eq!(scores, [(900, 4), (500, 5), (500, 3)]);
+    }
+
+    #[test]
+    fn add_keeps_only_the_best_runs() {
+        let mut highscores = HighScores::default();
+        for score in 1..=MAX_ENTRIES {
+            highscores.add(score * 100, 1, 1);
+        }
+        assert_eq!(highscores.add(50, 1, 1), None);
+        assert_eq!(highscores.entries.len(), MAX_ENTRIES);
+        assert_eq!(highscores.add(450, 1, 1), Some(6));
+        assert_eq!(highscores.entries.len(), MAX_ENTRIES);
+        assert_eq!(highscores.entries.last().unwrap().score, 200);
+    }
+
+    #[test]
+    fn ties_rank_below_earlier_runs() {
+        let mut highscores = HighScores::default();
+        assert_eq!(highscores.add(500, 3, 1), Some(0));
+        assert_eq!(highscores.add(500, 3, 1), Some(1));
+        for _ in 2..MAX_ENTRIES {
+            highscores.add(500, 3, 1);
+        }
+        // Equal to the last run in a full table is not enough
+        assert_eq!(highscores.add(500, 3, 1), None);
+        assert_eq!(highscores.entries.len(), MAX_ENTRIES);
+    }
+}
@@ -0,0 +1,189 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use raylib::prelude::*;
+
+use crate::convex_hull;
+
+// Collider outlines traced from a sprite's alpha channel. The outline is a convex hull
+// normalized to the image size, cached next to the sprite as `<name>.hull` with one
+// `x y` pair per line so it can be checked in and tweaked by hand.
+
+// Pixels at least this opaque count as part of the ship
+const ALPHA_THRESHOLD: u8 = 128;
+// Outlines are simplified down to this many vertices
+const MAX_VERTICES: usize = 12;
+
+pub fn trace(image: &Image) -> Vec<Vector2> {
+    let (width, height) = (image.width(), image.height());
+    let colors = image.get_image_data();
+
+    let mut corners = vec![];
+    for y in 0..height {
+        for x in 0..width {
+            if colors[(y * width + x) as usize].a >= ALPHA_THRESHOLD {
+                let (x, y) = (x as f32, y as f32);
+                corners.extend([
+                    Vector2::new(x, y),
+                    Vector2::new(x + 1., y),
+                    Vector2::new(x + 1., y + 1.),
+                    Vector2::new(x, y + 1.),
+                ]);
+            }
+        }
+    }
+
+    simplify(convex_hull(&corners), MAX_VERTICES)
+        .into_iter()
+        .map(|p| Vector2::new(p.x / width as f32, p.y / height as f32))
+        .collect()
+}
+
+// Drop the vertex spanning the smallest triangle with its neighbours until the outline
+// is short enough. Every removal cuts a sliver off the hull, so it stays convex.
+fn simplify(mut points: Vec<Vector2>, max_vertices: usize) -> Vec<Vector2> {
+    while points.len() > max_vertices.max(3) {
+        let n = points.len();
+        let area = |k: usize| {
+            let (a, b, c) = (points[(k + n - 1) % n], points[k], points[(k + 1) % n]);
+            ((b.x - a.x) * (c.y - a.y) - (c.x - a.x) * (b.y - a.y)).abs()
+        };
+        let smallest = (0..n).min_by(|i, j| area(*i).total_cmp(&area(*j))).unwrap();
+        points.remove(smallest);
+    }
+    points
+}
+
+pub fn hull_path(sprite: &str) -> PathBuf {
+    Path::new(sprite).with_extension("hull")
+}
+
+pub fn save(path: &Path, points: &[Vector2]) -> io::Result<()> {
+    let mut out = String::from("# collider outline traced from sprite alpha, normalized to the image size\n");
+    for p in points {
+        out.push_str(&format!("{:.4} {:.4}\n", p.x, p.y));
+    }
+    fs::write(path, out)
+}
+
+pub fn load(path: &Path) -> io::Result<Vec<Vector2>> {
+    let invalid = |line: &str| io::Error::new(io::ErrorKind::InvalidData, format!("bad hull line: {line}"));
+    let mut points = vec![];
+    for line in fs::read_to_string(path)?.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut parts = line.split_whitespace().map(str::parse::<f32>);
+        match (parts.next(), parts.next()) {
+            (Some(Ok(x)), Some(Ok(y))) => points.push(Vector2::new(x, y)),
+            _ => return Err(invalid(line)),
+        }
+    }
+    if points.len() < 3 {
+        return Err(invalid("fewer than 3 points"));
+    }
+    Ok(points)
+}
+
+// Read the cached outline of a sprite, tracing and caching it when it is missing
+// or older than the sprite
+pub fn load_or_trace(sprite: &str) -> Option<Vec<Vector2>> {
+    let path = hull_path(sprite);
+    let modified = |p: &Path| fs::metadata(p).and_then(|m| m.modified()).ok();
+    let stale = match (modified(&path), modified(Path::new(sprite))) {
+        (Some(hull), Some(image)) => hull < image,
+        (Some(_), None) => false,
+        _ => true,
+    };
+    if !stale && let Ok(points) = load(&path) {
+        return Some(points);
+    }
+
+    let points = trace(&Image::load_image(sprite).ok()?);
+    if let Err(err) = save(&path, &points) {
+        eprintln!("Could not cache {}: {err}", path.display());
+    }
+    Some(points)
+}
+
+// `--trace-hulls`: regenerate the outline of every sprite in `dir`
+pub fn trace_assets(dir: &str) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|ext| ext != "png") {
+            continue;
+        }
+        let sprite = path.to_string_lossy();
+        let Ok(image) = Image::load_image(&sprite) else {
+            eprintln!("Skipping {sprite}: could not load image");
+            continue;
+        };
+        let points = trace(&image);
+        save(&hull_path(&sprite), &points)?;
+        println!("{}: {} vertices", hull_path(&sprite).display(), points.len());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Scratch file in the temp dir, removed again when the test is done with it
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            Self(std::env::temp_dir().join(format!("asteroids-{}-{name}.hull", std::process::id())))
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn saved_outline_loads_back() {
+        let file = TempFile::new("round-trip");
+        let points = vec![Vector2::new(0.5, 0.), Vector2::new(1., 0.75), Vector2::new(0.125, 1.)];
+        save(&file.0, &points).unwrap();
+        assert_eq!(load(&file.0).unwrap(), points);
+    }
+
+    #[test]
+    fn load_skips_comments_and_rejects_junk() {
+        let file = TempFile::new("junk");
+        fs::write(&file.0, "# tweaked by hand\n0 0\n\n  1 0\n1 1\n").unwrap();
+        assert_eq!(load(&file.0).unwrap().len(), 3);
+
+        fs::write(&file.0, "0 0\n1 zero\n1 1\n").unwrap();
+        assert_eq!(load(&file.0).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        fs::write(&file.0, "0 0\n1 1\n").unwrap();
+        assert_eq!(load(&file.0).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn simplify_drops_the_flattest_corners_first() {
+        // Octagon with one corner barely sticking out of the square around it
+        let points = vec![
+            Vector2::new(0., 0.),
+            Vector2::new(5., -0.1),
+            Vector2::new(10., 0.),
+            Vector2::new(10., 10.),
+            Vector2::new(0., 10.),
+        ];
+        let simple = simplify(points, 4);
+        assert_eq!(simple.len(), 4);
+        assert!(!simple.contains(&Vector2::new(5., -0.1)));
+        assert_eq!(simplify(simple.clone(), 1).len(), 3);
+    }
+
+    #[test]
+    fn hull_sits_next_to_its_sprite() {
+        assert_eq!(hull_path("assets/player.png"), Path::new("assets/player.hull"));
+    }
+}
@@ -0,0 +1,315 @@
+use std::collections::HashSet;
+
+use raylib::prelude::*;
+
+use crate::{Binding, Keymap, GAMEPAD_BUTTONS};
+
+// Everything the game reacts to, whichever device it came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    Fire,
+    SwitchWeapon,
+    Missile,
+    Bomb,
+    Pause,
+    Fullscreen,
+    MenuUp,
+    MenuDown,
+    Confirm,
+    Back,
+}
+
+impl Action {
+    pub const ALL: [Action; 14] = [
+        Self::MoveLeft, Self::MoveRight, Self::MoveUp, Self::MoveDown,
+        Self::Fire, Self::SwitchWeapon, Self::Missile, Self::Bomb,
+        Self::Pause, Self::Fullscreen,
+        Self::MenuUp, Self::MenuDown, Self::Confirm, Self::Back,
+    ];
+
+    // Used in flight, the exit filter on the game-over screen ignores these
+    pub const GAMEPLAY: [Action; 8] = [
+        Self::MoveLeft, Self::MoveRight, Self::MoveUp, Self::MoveDown,
+        Self::Fire, Self::SwitchWeapon, Self::Missile, Self::Bomb,
+    ];
+
+    // Name in the keymap file
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::MoveLeft => "move_left",
+            Self::MoveRight => "move_right",
+            Self::MoveUp => "move_up",
+            Self::MoveDown => "move_down",
+            Self::Fire => "fire",
+            Self::SwitchWeapon => "switch_weapon",
+            Self::Missile => "missile",
+            Self::Bomb => "bomb",
+            Self::Pause => "pause",
+            Self::Fullscreen => "fullscreen",
+            Self::MenuUp => "menu_up",
+            Self::MenuDown => "menu_down",
+            Self::Confirm => "confirm",
+            Self::Back => "back",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|a| a.name() == name)
+    }
+
+    // Name on the controls screen
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::MoveLeft => "Move left",
+            Self::MoveRight => "Move right",
+            Self::MoveUp => "Move up",
+            Self::MoveDown => "Move down",
+            Self::Fire => "Fire",
+            Self::SwitchWeapon => "Switch weapon",
+            Self::Missile => "Missile",
+            Self::Bomb => "Bomb",
+            Self::Pause => "Pause",
+            Self::Fullscreen => "Fullscreen",
+            Self::MenuUp => "Menu up",
+            Self::MenuDown => "Menu down",
+            Self::Confirm => "Confirm",
+            Self::Back => "Back",
+        }
+    }
+
+    fn is_menu(&self) -> bool {
+        matches!(self, Self::MenuUp | Self::MenuDown | Self::Confirm | Self::Back)
+    }
+
+    // Two actions clash when they can be wanted at the same time, flight controls may
+    // share keys with menu controls. Pause and fullscreen work everywhere.
+    pub fn clashes_with(&self, other: Action) -> bool {
+        let global = |a: &Action| matches!(a, Self::Pause | Self::Fullscreen);
+        global(self) || global(&other) || self.is_menu() == other.is_menu()
+    }
+}
+
+// raylib tracks this many gamepads
+const MAX_GAMEPADS: i32 = 4;
+// Seconds a connect or disconnect message stays up
+const NOTICE_DURATION: f32 = 3.;
+
+// What the player asked for this frame
+#[derive(Debug, Clone, Default)]
+pub struct Input {
+    // Desired direction, each axis in -1..=1, analog sticks give anything in between
+    pub movement: Vector2,
+    // Screen point to fly to, set by mouse and touch control
+    pub target: Option<Vector2>,
+    down: HashSet<Action>,
+    pressed: HashSet<Action>,
+}
+
+impl Input {
+    pub fn down(&self, action: Action) -> bool {
+        self.down.contains(&action)
+    }
+
+    // Went down this frame
+    pub fn pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action)
+    }
+
+    // Same keys held with nothing new pressed, for the extra ticks of a long frame
+    pub fn held(&self) -> Self {
+        Self { pressed: HashSet::new(), ..self.clone() }
+    }
+
+    // Gameplay presses of an earlier frame that was too short for a tick
+    pub fn carry(&mut self, earlier: &Input) {
+        self.pressed.extend(earlier.pressed.iter().filter(|action| Action::GAMEPLAY.contains(action)));
+    }
+
+    // Actions held by a program instead of a person, one not held on the tick before counts
+    // as pressed. The move actions give the direction.
+    pub fn from_held(held: &HashSet<Action>, before: &HashSet<Action>) -> Self {
+        let axis = |negative: Action, positive: Action| {
+            held.contains(&positive) as i32 as f32 - held.contains(&negative) as i32 as f32
+        };
+        Self {
+            movement: Vector2::new(axis(Action::MoveLeft, Action::MoveRight), axis(Action::MoveUp, Action::MoveDown)),
+            target: None,
+            down: held.clone(),
+            pressed: held.difference(before).copied().collect(),
+        }
+    }
+
+    // For input that does not come from this machine's devices
+    pub fn set(&mut self, action: Action, down: bool, pressed: bool) {
+        if down {
+            self.down.insert(action);
+        }
+        if pressed {
+            self.pressed.insert(action);
+        }
+    }
+}
+
+// Reads the keyboard and the active gamepad into `Input` every frame
+pub struct Controls {
+    pub keymap: Keymap,
+    // Keys the other player flies with, skipped for flying so one key never moves two
+    // ships. Menus still read them.
+    pub ignored_keys: Vec<KeyboardKey>,
+    gamepad: Option<i32>,
+    // Follow the mouse or touch position, clicking or touching fires
+    pointer: bool,
+    // A finger was on the screen last frame, touches have no pressed state of their own
+    touching: bool,
+    // Stick tilt below this fraction is ignored
+    deadzone: f32,
+    // Last connect or disconnect message and how long it stays up
+    pub notice: Option<(String, f32)>,
+}
+
+impl Controls {
+    pub fn new(keymap: Keymap, deadzone: f32, pointer: bool) -> Self {
+        Self { keymap, ignored_keys: vec![], gamepad: None, pointer, touching: false, deadzone, notice: None }
+    }
+
+    pub fn gamepad(&self) -> Option<i32> {
+        self.gamepad
+    }
+
+    fn is_ignored(&self, action: Action, key: KeyboardKey) -> bool {
+        Action::GAMEPLAY.contains(&action) && self.ignored_keys.contains(&key)
+    }
+
+    // `taken` is a gamepad another player already uses
+    pub fn poll(&mut self, rl: &RaylibHandle, delta_time: f32, taken: Option<i32>) -> Input {
+        self.detect_gamepad(rl, delta_time, taken);
+
+        let mut input = Input::default();
+        for (action, binding) in self.keymap.bindings() {
+            let (down, pressed) = match (binding, self.gamepad) {
+                (Binding::Key(key), _) if self.is_ignored(*action, *key) => (false, false),
+                (Binding::Key(key), _) => (rl.is_key_down(*key), rl.is_key_pressed(*key)),
+                (Binding::Button(button), Some(gamepad)) => (
+                    rl.is_gamepad_button_down(gamepad, *button),
+                    rl.is_gamepad_button_pressed(gamepad, *button),
+                ),
+                (Binding::Button(_), None) => (false, false),
+            };
+            if down {
+                input.down.insert(*action);
+            }
+            if pressed {
+                input.pressed.insert(*action);
+            }
+        }
+
+        if self.pointer {
+            self.poll_pointer(rl, &mut input);
+        }
+
+        let mut stick = Vector2::zero();
+        if let Some(gamepad) = self.gamepad {
+            stick = self.apply_deadzone(Vector2::new(
+                rl.get_gamepad_axis_movement(gamepad, GamepadAxis::GAMEPAD_AXIS_LEFT_X),
+                rl.get_gamepad_axis_movement(gamepad, GamepadAxis::GAMEPAD_AXIS_LEFT_Y),
+            ));
+        }
+
+        // Digital input wins over the stick on each axis
+        let axis = |negative: Action, positive: Action, analog: f32| {
+            match (input.down(negative), input.down(positive)) {
+                (true, false) => -1.,
+                (false, true) => 1.,
+                _ => analog,
+            }
+        };
+        input.movement = Vector2::new(
+            axis(Action::MoveLeft, Action::MoveRight, stick.x),
+            axis(Action::MoveUp, Action::MoveDown, stick.y),
+        );
+        input
+    }
+
+    // A touch wins over the mouse, the left mouse button or any finger fires
+    fn poll_pointer(&mut self, rl: &RaylibHandle, input: &mut Input) {
+        let touches = rl.get_touch_point_count();
+        let (target, down, pressed) = if touches > 0 {
+            (rl.get_touch_position(0), true, !self.touching)
+        } else {
+            (
+                rl.get_mouse_position(),
+                rl.is_mouse_button_down(MouseButton::MOUSE_BUTTON_LEFT),
+                rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT),
+            )
+        };
+        self.touching = touches > 0;
+        input.target = Some(target);
+        if down {
+            input.down.insert(Action::Fire);
+        }
+        if pressed {
+            input.pressed.insert(Action::Fire);
+        }
+    }
+
+    // Next key or gamepad button pressed, for rebinding
+    pub fn capture(&self, rl: &mut RaylibHandle) -> Option<Binding> {
+        if let Some(key) = rl.get_key_pressed() {
+            return Some(Binding::Key(key));
+        }
+        let gamepad = self.gamepad?;
+        GAMEPAD_BUTTONS.iter()
+            .find(|button| rl.is_gamepad_button_pressed(gamepad, **button))
+            .map(|button| Binding::Button(*button))
+    }
+
+    // Radial deadzone, rescaled so the usable range still starts at zero
+    fn apply_deadzone(&self, stick: Vector2) -> Vector2 {
+        let tilt = stick.length();
+        if tilt <= self.deadzone {
+            return Vector2::zero();
+        }
+        let scaled = ((tilt - self.deadzone) / (1. - self.deadzone)).min(1.);
+        stick / tilt * scaled
+    }
+
+    // Hot-plugging: drop the gamepad when it goes away and pick up the first free one plugged in
+    fn detect_gamepad(&mut self, rl: &RaylibHandle, delta_time: f32, taken: Option<i32>) {
+        if let Some((_, remaining)) = self.notice.as_mut() {
+            *remaining -= delta_time;
+            if *remaining <= 0. {
+                self.notice = None;
+            }
+        }
+        if let Some(gamepad) = self.gamepad && !rl.is_gamepad_available(gamepad) {
+            self.gamepad = None;
+            self.notice = Some(("Gamepad disconnected".to_string(), NOTICE_DURATION));
+        }
+        if self.gamepad.is_none()
+            && let Some(gamepad) = (0..MAX_GAMEPADS).find(|g| Some(*g) != taken && rl.is_gamepad_available(*g))
+        {
+            self.gamepad = Some(gamepad);
+            let name = rl.get_gamepad_name(gamepad).unwrap_or_else(|| "Gamepad".to_string());
+            self.notice = Some((format!("{name} connected"), NOTICE_DURATION));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shared_keyboard_only_takes_flying_keys() {
+        let mut first = Controls::new(Keymap::defaults(0), 0.2, false);
+        first.ignored_keys = Keymap::defaults(1).keys_for(&Action::GAMEPLAY);
+        assert!(first.is_ignored(Action::MoveUp, KeyboardKey::KEY_UP));
+        assert!(!first.is_ignored(Action::MenuUp, KeyboardKey::KEY_UP));
+        assert!(!first.is_ignored(Action::MenuDown, KeyboardKey::KEY_DOWN));
+        assert!(!first.is_ignored(Action::MoveUp, KeyboardKey::KEY_W));
+    }
+}
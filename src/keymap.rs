@@ -0,0 +1,313 @@
+use std::fs;
+use std::io;
+
+use raylib::prelude::*;
+
+use crate::Action;
+
+// Action to key and button bindings, saved next to the game with one action per line:
+// `fire = SPACE, pad:RIGHT_FACE_DOWN`. Keys go by their raylib name without the `KEY_`
+// prefix, gamepad buttons by theirs without `GAMEPAD_BUTTON_` and with a `pad:` prefix.
+// Each player slot has its own file.
+const KEYMAP_FILES: [&str; 2] = ["controls.cfg", "controls2.cfg"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Binding {
+    Key(KeyboardKey),
+    Button(GamepadButton),
+}
+
+// Every button raylib knows, there is no conversion from its number
+pub const GAMEPAD_BUTTONS: [GamepadButton; 17] = [
+    GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_UP,
+    GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_RIGHT,
+    GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_DOWN,
+    GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_LEFT,
+    GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_UP,
+    GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_RIGHT,
+    GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_DOWN,
+    GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_LEFT,
+    GamepadButton::GAMEPAD_BUTTON_LEFT_TRIGGER_1,
+    GamepadButton::GAMEPAD_BUTTON_LEFT_TRIGGER_2,
+    GamepadButton::GAMEPAD_BUTTON_RIGHT_TRIGGER_1,
+    GamepadButton::GAMEPAD_BUTTON_RIGHT_TRIGGER_2,
+    GamepadButton::GAMEPAD_BUTTON_MIDDLE_LEFT,
+    GamepadButton::GAMEPAD_BUTTON_MIDDLE,
+    GamepadButton::GAMEPAD_BUTTON_MIDDLE_RIGHT,
+    GamepadButton::GAMEPAD_BUTTON_LEFT_THUMB,
+    GamepadButton::GAMEPAD_BUTTON_RIGHT_THUMB,
+];
+
+// Highest raylib key code
+const MAX_KEY: i32 = 350;
+
+impl Binding {
+    pub fn name(&self) -> String {
+        match self {
+            Self::Key(key) => format!("{key:?}").trim_start_matches("KEY_").to_string(),
+            Self::Button(button) => format!("pad:{}", format!("{button:?}").trim_start_matches("GAMEPAD_BUTTON_")),
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        let name = name.to_uppercase();
+        match name.strip_prefix("PAD:") {
+            Some(button) => GAMEPAD_BUTTONS.iter()
+                .map(|b| Self::Button(*b))
+                .find(|b| b.name() == format!("pad:{button}")),
+            None => (0..=MAX_KEY)
+                .filter_map(key_from_i32)
+                .map(Self::Key)
+                .find(|k| k.name() == name),
+        }
+    }
+}
+
+// The second player flies with the arrows and the keys around them, menus are left to the
+// first player
+const SECOND_PLAYER_BINDINGS: &[(Action, Binding)] = {
+    use Binding::{Button, Key};
+    use GamepadButton::*;
+    use KeyboardKey::*;
+    &[
+        (Action::MoveLeft, Key(KEY_LEFT)),
+        (Action::MoveLeft, Button(GAMEPAD_BUTTON_LEFT_FACE_LEFT)),
+        (Action::MoveRight, Key(KEY_RIGHT)),
+        (Action::MoveRight, Button(GAMEPAD_BUTTON_LEFT_FACE_RIGHT)),
+        (Action::MoveUp, Key(KEY_UP)),
+        (Action::MoveUp, Button(GAMEPAD_BUTTON_LEFT_FACE_UP)),
+        (Action::MoveDown, Key(KEY_DOWN)),
+        (Action::MoveDown, Button(GAMEPAD_BUTTON_LEFT_FACE_DOWN)),
+        (Action::Fire, Key(KEY_RIGHT_CONTROL)),
+        (Action::Fire, Button(GAMEPAD_BUTTON_RIGHT_FACE_DOWN)),
+        (Action::Fire, Button(GAMEPAD_BUTTON_RIGHT_TRIGGER_2)),
+        (Action::SwitchWeapon, Key(KEY_RIGHT_SHIFT)),
+        (Action::SwitchWeapon, Button(GAMEPAD_BUTTON_RIGHT_TRIGGER_1)),
+        (Action::Missile, Key(KEY_SLASH)),
+        (Action::Missile, Button(GAMEPAD_BUTTON_RIGHT_FACE_LEFT)),
+        (Action::Bomb, Key(KEY_PERIOD)),
+        (Action::Bomb, Button(GAMEPAD_BUTTON_RIGHT_FACE_UP)),
+        (Action::Pause, Button(GAMEPAD_BUTTON_MIDDLE_RIGHT)),
+    ]
+};
+
+const DEFAULT_BINDINGS: &[(Action, Binding)] = {
+    use Binding::{Button, Key};
+    use GamepadButton::*;
+    use KeyboardKey::*;
+    &[
+        (Action::MoveLeft, Key(KEY_A)),
+        (Action::MoveLeft, Key(KEY_LEFT)),
+        (Action::MoveLeft, Button(GAMEPAD_BUTTON_LEFT_FACE_LEFT)),
+        (Action::MoveRight, Key(KEY_D)),
+        (Action::MoveRight, Key(KEY_RIGHT)),
+        (Action::MoveRight, Button(GAMEPAD_BUTTON_LEFT_FACE_RIGHT)),
+        (Action::MoveUp, Key(KEY_W)),
+        (Action::MoveUp, Key(KEY_UP)),
+        (Action::MoveUp, Button(GAMEPAD_BUTTON_LEFT_FACE_UP)),
+        (Action::MoveDown, Key(KEY_S)),
+        (Action::MoveDown, Key(KEY_DOWN)),
+        (Action::MoveDown, Button(GAMEPAD_BUTTON_LEFT_FACE_DOWN)),
+        (Action::Fire, Key(KEY_SPACE)),
+        (Action::Fire, Button(GAMEPAD_BUTTON_RIGHT_FACE_DOWN)),
+        (Action::Fire, Button(GAMEPAD_BUTTON_RIGHT_TRIGGER_2)),
+        (Action::SwitchWeapon, Key(KEY_Q)),
+        (Action::SwitchWeapon, Button(GAMEPAD_BUTTON_RIGHT_TRIGGER_1)),
+        (Action::Missile, Key(KEY_E)),
+        (Action::Missile, Button(GAMEPAD_BUTTON_RIGHT_FACE_LEFT)),
+        (Action::Bomb, Key(KEY_B)),
+        (Action::Bomb, Button(GAMEPAD_BUTTON_RIGHT_FACE_UP)),
+        (Action::Pause, Key(KEY_P)),
+        (Action::Pause, Key(KEY_PAUSE)),
+        (Action::Pause, Button(GAMEPAD_BUTTON_MIDDLE_RIGHT)),
+        (Action::Fullscreen, Key(KEY_F)),
+        (Action::MenuUp, Key(KEY_UP)),
+        (Action::MenuUp, Key(KEY_W)),
+        (Action::MenuUp, Button(GAMEPAD_BUTTON_LEFT_FACE_UP)),
+        (Action::MenuDown, Key(KEY_DOWN)),
+        (Action::MenuDown, Key(KEY_S)),
+        (Action::MenuDown, Button(GAMEPAD_BUTTON_LEFT_FACE_DOWN)),
+        (Action::Confirm, Key(KEY_ENTER)),
+        (Action::Confirm, Button(GAMEPAD_BUTTON_RIGHT_FACE_DOWN)),
+        (Action::Back, Key(KEY_BACKSPACE)),
+        (Action::Back, Button(GAMEPAD_BUTTON_RIGHT_FACE_RIGHT)),
+    ]
+};
+
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    // Player slot, picks the file and the defaults
+    slot: usize,
+    bindings: Vec<(Action, Binding)>,
+}
+
+impl Keymap {
+    pub fn defaults(slot: usize) -> Self {
+        let bindings = if slot == 0 { DEFAULT_BINDINGS } else { SECOND_PLAYER_BINDINGS };
+        Self { slot, bindings: bindings.to_vec() }
+    }
+
+    // Falls back to the defaults when the file is missing, bad lines are reported and
+    // skipped. Actions the file does not mention keep their default bindings.
+    pub fn load(slot: usize) -> Self {
+        match fs::read_to_string(KEYMAP_FILES[slot]) {
+            Ok(text) => Self::read(slot, &text),
+            Err(_) => Self::defaults(slot),
+        }
+    }
+
+    fn read(slot: usize, text: &str) -> Self {
+        let file = KEYMAP_FILES[slot];
+        let mut keymap = Self::defaults(slot);
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let parsed = line.split_once('=')
+                .ok_or_else(|| "expected `action = bindings`".to_string())
+                .and_then(|(action, bindings)| {
+                    let action = Action::parse(action.trim())
+                        .ok_or_else(|| format!("unknown action `{}`", action.trim()))?;
+                    let bindings = bindings.split(',')
+                        .map(str::trim)
+                        .filter(|name| !name.is_empty())
+                        .map(|name| Binding::parse(name).ok_or_else(|| format!("unknown key or button `{name}`")))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok((action, bindings))
+                });
+            match parsed {
+                Ok((action, bindings)) => {
+                    keymap.bindings.retain(|(a, _)| *a != action);
+                    keymap.bindings.extend(bindings.into_iter().map(|b| (action, b)));
+                }
+                Err(err) => eprintln!("{file}:{}: {err}", number + 1),
+            }
+        }
+        for (a, b, binding) in keymap.conflicts() {
+            eprintln!("{file}: {} is bound to both {} and {}", binding.name(), a.label(), b.label());
+        }
+        keymap
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let mut out = String::from("# action = keys and gamepad buttons, see the README for the names\n");
+        for action in Action::ALL {
+            let names: Vec<String> = self.bindings_for(action).map(|b| b.name()).collect();
+            out.push_str(&format!("{} = {}\n", action.name(), names.join(", ")));
+        }
+        fs::write(KEYMAP_FILES[self.slot], out)
+    }
+
+    pub fn bindings(&self) -> impl Iterator<Item = &(Action, Binding)> {
+        self.bindings.iter()
+    }
+
+    pub fn bindings_for(&self, action: Action) -> impl Iterator<Item = Binding> + '_ {
+        self.bindings.iter()
+            .filter(move |(a, _)| *a == action)
+            .map(|(_, b)| *b)
+    }
+
+    pub fn slot(&self) -> usize {
+        self.slot
+    }
+
+    // Keys that trigger any of `actions`
+    pub fn keys_for(&self, actions: &[Action]) -> Vec<KeyboardKey> {
+        self.bindings.iter()
+            .filter_map(|(a, b)| match b {
+                Binding::Key(key) if actions.contains(a) => Some(*key),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // Whether the key triggers any of `actions`
+    pub fn is_bound(&self, key: KeyboardKey, actions: &[Action]) -> bool {
+        self.bindings.iter().any(|(a, b)| *b == Binding::Key(key) && actions.contains(a))
+    }
+
+    // Make `binding` the only one of its device for `action`. Refused with the action
+    // already using it when both can be needed at the same time.
+    pub fn rebind(&mut self, action: Action, binding: Binding) -> Result<(), Action> {
+        if let Some((other, _)) = self.bindings.iter()
+            .find(|(a, b)| *b == binding && *a != action && a.clashes_with(action))
+        {
+            return Err(*other);
+        }
+        let same_device = |b: &Binding| matches!(
+            (b, binding),
+            (Binding::Key(_), Binding::Key(_)) | (Binding::Button(_), Binding::Button(_))
+        );
+        self.bindings.retain(|(a, b)| *a != action || !same_device(b));
+        self.bindings.push((action, binding));
+        Ok(())
+    }
+
+    // Bindings shared by two actions that can be needed at the same time
+    pub fn conflicts(&self) -> Vec<(Action, Action, Binding)> {
+        let mut conflicts = vec![];
+        for (i, (a, binding)) in self.bindings.iter().enumerate() {
+            for (b, other) in &self.bindings[i + 1..] {
+                if binding == other && a != b && a.clashes_with(*b) {
+                    conflicts.push((*a, *b, *binding));
+                }
+            }
+        }
+        conflicts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binding_names_round_trip() {
+        for binding in [Binding::Key(KeyboardKey::KEY_SPACE), Binding::Button(GamepadButton::GAMEPAD_BUTTON_RIGHT_TRIGGER_2)] {
+            assert_eq!(Binding::parse(&binding.name()), Some(binding));
+        }
+        assert_eq!(Binding::parse("pad:right_face_down"), Some(Binding::Button(GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_DOWN)));
+        assert_eq!(Binding::parse("SPACEBAR"), None);
+    }
+
+    #[test]
+    fn file_replaces_only_the_actions_it_names() {
+        let keymap = Keymap::read(0, "# mine\n\nfire = ENTER, pad:RIGHT_TRIGGER_1\nbomb = NOT_A_KEY\nwarp = W\n");
+        let fire: Vec<Binding> = keymap.bindings_for(Action::Fire).collect();
+        assert_eq!(fire, [Binding::Key(KeyboardKey::KEY_ENTER), Binding::Button(GamepadButton::GAMEPAD_BUTTON_RIGHT_TRIGGER_1)]);
+
+        // The bad line is skipped and bombs keep their defaults
+        let defaults = Keymap::defaults(0);
+        assert!(keymap.bindings_for(Action::Bomb).eq(defaults.bindings_for(Action::Bomb)));
+    }
+
+    #[test]
+    fn rebind_replaces_the_binding_on_the_same_device() {
+        let mut keymap = Keymap::defaults(0);
+        keymap.rebind(Action::Bomb, Binding::Key(KeyboardKey::KEY_G)).unwrap();
+        let bomb: Vec<Binding> = keymap.bindings_for(Action::Bomb).collect();
+        assert_eq!(bomb, [Binding::Button(GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_UP), Binding::Key(KeyboardKey::KEY_G)]);
+    }
+
+    #[test]
+    fn rebind_refuses_keys_in_use() {
+        let mut keymap = Keymap::defaults(0);
+        assert_eq!(keymap.rebind(Action::Bomb, Binding::Key(KeyboardKey::KEY_SPACE)), Err(Action::Fire));
+        assert!(keymap.is_bound(KeyboardKey::KEY_B, &[Action::Bomb]));
+
+        // Menus and flying never need the same key at once
+        keymap.rebind(Action::Confirm, Binding::Key(KeyboardKey::KEY_SPACE)).unwrap();
+        assert!(keymap.conflicts().is_empty());
+    }
+
+    #[test]
+    fn conflicts_in_the_file_are_found() {
+        assert!(Keymap::defaults(0).conflicts().is_empty());
+        assert!(Keymap::defaults(1).conflicts().is_empty());
+
+        let keymap = Keymap::read(0, "bomb = SPACE\n");
+        assert_eq!(keymap.conflicts(), [(Action::Fire, Action::Bomb, Binding::Key(KeyboardKey::KEY_SPACE))]);
+    }
+}
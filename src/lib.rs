@@ -0,0 +1,941 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use raylib::prelude::*;
+
+mod events;
+mod entities;
+mod collision;
+mod transformable;
+mod hull;
+mod waves;
+mod highscores;
+mod powerups;
+mod weapons;
+mod boss;
+mod config;
+mod scoring;
+mod input;
+mod keymap;
+mod menu;
+mod net;
+mod snapshot;
+mod remote;
+mod rollback;
+mod spectator;
+mod bot;
+mod env;
+mod game;
+
+use events::*;
+use entities::*;
+use collision::*;
+use transformable::*;
+use waves::*;
+use highscores::*;
+use powerups::*;
+use weapons::*;
+use boss::*;
+use config::*;
+use scoring::*;
+use input::*;
+use keymap::*;
+use menu::*;
+use net::*;
+use snapshot::*;
+use remote::*;
+use rollback::*;
+use spectator::*;
+use bot::*;
+use env::*;
+
+pub use env::{Env, EnvConfig};
+pub use game::run;
+pub use input::Action;
+
+
+static mut SCREEN_W: i32 = 1200;
+static mut SCREEN_H: i32 = 800;
+
+macro_rules! SCREEN_WIDTH {
+    () => { unsafe{ $crate::SCREEN_W as f32 }};
+    ($w:expr) => { unsafe{ $crate::SCREEN_W = $w; }}
+}
+macro_rules! SCREEN_HEIGHT {
+    () => { unsafe{ $crate::SCREEN_H as f32 }};
+    ($h:expr) => { unsafe{ $crate::SCREEN_H = $h; }}
+}
+pub(crate) use {SCREEN_HEIGHT, SCREEN_WIDTH};
+
+type Textures = HashMap<&'static str, Texture2D>;
+type Hulls = HashMap<&'static str, Vec<Vector2>>;
+
+// Sprites by the name entities draw them with
+const SPRITES: [(&str, &str); 1] = [("player", "assets/player.png")];
+
+// Every window draws with the same sprites
+fn load_textures(rl: &mut RaylibHandle, rt: &RaylibThread) -> Textures {
+    SPRITES.into_iter()
+        .map(|(name, filename)| (name, rl.load_texture(rt, filename).unwrap()))
+        .collect()
+}
+
+// How close something has to pass by the ship's edge to count as a near miss
+const NEAR_MISS_MARGIN: f32 = 25.;
+// Chance that a destroyed asteroid leaves a power-up behind
+const PICKUP_DROP_CHANCE: f64 = 0.1;
+// Rapid fire makes every weapon automatic and this many times faster
+const RAPID_FIRE_RATE: f32 = 2.;
+// Angle of the extra lazers from spread shot, in degrees
+const SPREAD_ANGLE: f32 = 15.;
+// How far past the screen edges asteroids, ships and pickups may go before they are gone,
+// more than anything spawns above the top edge
+const OFFSCREEN_MARGIN: f32 = 100.;
+// Length of one simulation step. The world only ever moves by whole ticks, so the same
+// seed and inputs play out the same on every machine.
+const TICK: f32 = 1. / 60.;
+
+
+// Entirely outside the screen grown by `margin` on every side
+fn offscreen(rect: &Rectangle, margin: f32) -> bool {
+    rect.x + rect.width < -margin
+        || rect.y + rect.height < -margin
+        || rect.x > SCREEN_WIDTH!() + margin
+        || rect.y > SCREEN_HEIGHT!() + margin
+}
+
+// Everything the simulation needs to go on from one tick to the next. Ordered maps and
+// the seeded generator keep it deterministic, and a clone is a full save state.
+#[derive(Clone)]
+pub struct World {
+    id_count: EntityId,
+    // Player ships by slot, a player that is out has no entity left
+    pub(crate) player_ids: Vec<EntityId>,
+    pub(crate) entities: BTreeMap<EntityId, Entity>,
+    drawables: Vec<EntityId>,
+    collidables: Vec<EntityId>,
+    enemy_count: u32,
+    pub(crate) waves: WaveDirector,
+    // Hazards that came close to a ship, with the slot of that ship. They pay out a near
+    // miss once they are out of reach again without having touched it.
+    passing: BTreeMap<EntityId, usize>,
+    // Hazards that already paid out a near miss or touched a ship
+    grazed: HashSet<EntityId>,
+    // One per player slot
+    pub(crate) scoring: Vec<Scoring>,
+    rng: StdRng,
+}
+
+impl World {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self {
+            id_count: 0,
+            player_ids: vec![],
+            entities: BTreeMap::new(),
+            drawables: vec![],
+            collidables: vec![],
+            enemy_count: 0,
+            waves: WaveDirector::new(),
+            passing: BTreeMap::new(),
+            grazed: HashSet::new(),
+            scoring: vec![],
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    fn new_id(&mut self) -> EntityId {
+        let id = self.id_count;
+        self.id_count += 1;
+        id
+    }
+
+    fn new_asteroid(&mut self, size: f32, speed: f32) {
+        let id = self.new_id();
+        let asteroid = Asteroid::new(id, size, speed, &mut self.rng);
+        self.add_asteroid(asteroid);
+    }
+
+    fn add_asteroid(&mut self, asteroid: Asteroid) {
+        let id = asteroid.id;
+        let entity = Entity::Enemy(Box::new(asteroid));
+        self.entities.insert(id, entity);
+        self.enemy_count += 1;
+        self.collidables.push(id);
+        self.drawables.push(id);
+    }
+
+    pub(crate) fn remove_entity(&mut self, id: EntityId) {
+        if let Some(Entity::Enemy(_) | Entity::EnemyShip(_) | Entity::Boss(_)) = self.entities.remove(&id) {
+            self.enemy_count -= 1;
+        }
+        self.collidables.retain(|e| *e != id);
+        self.drawables.retain(|e| *e != id);
+        self.passing.remove(&id);
+        self.grazed.remove(&id);
+    }
+
+    // A hazard ran into a ship, whether it did any harm or not. It can't be a near miss
+    // any more.
+    pub(crate) fn touch(&mut self, id: EntityId) {
+        self.passing.remove(&id);
+        self.grazed.insert(id);
+    }
+
+    pub(crate) fn player(&self, slot: usize) -> Option<&Player> {
+        match self.entities.get(self.player_ids.get(slot)?) {
+            Some(Entity::Player(player)) => Some(player),
+            _ => None,
+        }
+    }
+
+    fn player_mut(&mut self, slot: usize) -> Option<&mut Player> {
+        match self.entities.get_mut(self.player_ids.get(slot)?) {
+            Some(Entity::Player(player)) => Some(player),
+            _ => None,
+        }
+    }
+
+    fn players(&self) -> impl Iterator<Item = &Player> {
+        (0..self.player_ids.len()).filter_map(|slot| self.player(slot))
+    }
+
+    // The hit costs a life, returns the ship's id once the player is out of lives.
+    // Further hits while the ship is respawning are ignored.
+    fn hit_player(&mut self, slot: usize) -> Option<EntityId> {
+        let id = self.player_ids[slot];
+        let player = self.player_mut(slot)?;
+        if player.is_respawning() || player.lives == 0 {
+            return None;
+        }
+        player.lives -= 1;
+        if player.lives == 0 {
+            return Some(id);
+        }
+        player.respawn();
+        None
+    }
+
+    fn split_asteroid(&mut self, id: EntityId, impact: Vector2) {
+        let (fragments, center) = if let Some(Entity::Enemy(e)) = self.entities.get(&id) {
+            (e.split(impact, &mut self.rng), rect_center(&e.rect))
+        } else {
+            return;
+        };
+        self.remove_entity(id);
+        for mut fragment in fragments {
+            fragment.id = self.new_id();
+            self.add_asteroid(fragment);
+        }
+        if self.rng.random_bool(PICKUP_DROP_CHANCE) {
+            let power_up = PowerUp::random(&mut self.rng);
+            self.new_pickup(center, power_up);
+        }
+    }
+
+    // Asteroids, ships and pickups that drifted off the screen on any side are gone for this
+    // wave, lazers as soon as they leave it. Returns the owner of every player shot that
+    // missed.
+    fn cull_offscreen(&mut self) -> Vec<usize> {
+        let mut misses = vec![];
+        let gone: Vec<EntityId> = self.entities.iter()
+            .filter_map(|(id, e)| match e {
+                Entity::Enemy(a) if offscreen(&a.rect, OFFSCREEN_MARGIN) => Some(*id),
+                Entity::EnemyShip(s) if offscreen(&s.rect, OFFSCREEN_MARGIN) => Some(*id),
+                Entity::Pickup(p) if offscreen(&p.rect, OFFSCREEN_MARGIN) => Some(*id),
+                Entity::Projectile(l) if offscreen(&l.rect, 0.) => {
+                    if l.hit.is_empty() && !l.is_hostile() {
+                        misses.push(l.owner);
+                    }
+                    Some(*id)
+                }
+                Entity::Explosion(e) if e.age > EXPLOSION_DURATION => Some(*id),
+                Entity::Shockwave(s) if s.radius >= BOMB_RADIUS => Some(*id),
+                _ => None,
+            })
+            .collect();
+        for id in gone {
+            self.remove_entity(id);
+        }
+        misses
+    }
+
+    // Asteroids, ships and enemy shots passing close by a ship without touching it, each
+    // counts once for whoever it passed first. Returns the slot of the player for every one.
+    fn near_misses(&mut self) -> Vec<usize> {
+        let ships: Vec<(usize, Vector2, f32)> = self.players()
+            .map(|p| (p.slot, rect_center(&p.rect), p.rect.width / 2.))
+            .collect();
+        let hazards: BTreeMap<EntityId, Rectangle> = self.entities.iter()
+            .filter_map(|(id, e)| match e {
+                Entity::Enemy(a) => Some((*id, a.rect)),
+                Entity::EnemyShip(s) => Some((*id, s.rect)),
+                Entity::Projectile(l) if l.is_hostile() => Some((*id, l.rect)),
+                _ => None,
+            })
+            .collect();
+        let in_reach = |(_, ship, ship_radius): &(usize, Vector2, f32), rect: &Rectangle| {
+            let reach = ship_radius + rect.width.max(rect.height) / 2. + NEAR_MISS_MARGIN;
+            rect_center(rect).distance_to(*ship) < reach
+        };
+
+        let mut grazes = vec![];
+        for (id, slot) in std::mem::take(&mut self.passing) {
+            // Shot down on the way or the ship went out, nobody dodged anything
+            let (Some(rect), Some(ship)) = (hazards.get(&id), ships.iter().find(|s| s.0 == slot)) else {
+                continue;
+            };
+            if in_reach(ship, rect) {
+                self.passing.insert(id, slot);
+            } else {
+                grazes.push(slot);
+                self.grazed.insert(id);
+            }
+        }
+        for ship in &ships {
+            for (id, rect) in &hazards {
+                if !self.grazed.contains(id) && in_reach(ship, rect) {
+                    self.passing.entry(*id).or_insert(ship.0);
+                }
+            }
+        }
+        grazes
+    }
+
+    fn update_waves(&mut self, delta_time: f32) {
+        let spawns = self.waves.update(delta_time, self.enemy_count);
+        if spawns.started {
+            for entity in self.entities.values_mut() {
+                if let Entity::Player(p) = entity {
+                    p.missiles = MISSILE_CAPACITY;
+                }
+            }
+        }
+        for _ in 0..spawns.asteroids {
+            let size = self.waves.wave.random_size(&mut self.rng);
+            let speed = self.waves.wave.random_speed(&mut self.rng);
+            self.new_asteroid(size, speed);
+        }
+        // Ships take evenly spaced slots in two staggered rows
+        for i in 0..spawns.ships {
+            let behavior = [ShipBehavior::Formation, ShipBehavior::Strafe, ShipBehavior::Dive][i as usize % 3];
+            let anchor = Vector2::new(
+                SCREEN_WIDTH!() * (i + 1) as f32 / (spawns.ships + 1) as f32,
+                100. + (i % 2) as f32 * 60.,
+            );
+            self.new_ship(behavior, anchor);
+        }
+        if spawns.boss {
+            let id = self.new_id();
+            self.entities.insert(id, Entity::Boss(Box::new(Boss::new(id))));
+            self.enemy_count += 1;
+            self.collidables.push(id);
+            self.drawables.push(id);
+        }
+    }
+
+    fn new_ship(&mut self, behavior: ShipBehavior, anchor: Vector2) {
+        let id = self.new_id();
+        let ship = Ship::new(id, behavior, anchor, &mut self.rng);
+        let entity = Entity::EnemyShip(Box::new(ship));
+        self.entities.insert(id, entity);
+        self.enemy_count += 1;
+        self.collidables.push(id);
+        self.drawables.push(id);
+    }
+
+    // Let the ships and bosses know where the closest player is before they move
+    fn aim_enemies(&mut self) {
+        let ships: Vec<Vector2> = self.players().map(|p| rect_center(&p.rect)).collect();
+        let closest = |from: Vector2| ships.iter()
+            .copied()
+            .min_by(|a, b| a.distance_to(from).total_cmp(&b.distance_to(from)));
+        for entity in self.entities.values_mut() {
+            match entity {
+                Entity::EnemyShip(ship) => {
+                    if let Some(target) = closest(rect_center(&ship.rect)) {
+                        ship.target = target;
+                    }
+                }
+                Entity::Boss(boss) => {
+                    if let Some(target) = closest(rect_center(&boss.rect)) {
+                        boss.target = target;
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    // Every ship that is ready shoots once straight at the player, bosses follow their pattern
+    fn fire_enemies(&mut self) {
+        let mut shots = vec![];
+        for entity in self.entities.values_mut() {
+            match entity {
+                Entity::EnemyShip(ship) if ship.can_fire() => {
+                    ship.reload(&mut self.rng);
+                    let center = rect_center(&ship.rect);
+                    let aim = ship.target - center;
+                    let angle = aim.x.atan2(-aim.y).to_degrees();
+                    // Start the shot just past the nose
+                    let start = center + aim.normalized() * (ship.rect.height / 2. + ENEMY_SHOT.length / 2.);
+                    shots.push((start, angle));
+                }
+                Entity::Boss(boss) => shots.extend(boss.attack()),
+                _ => (),
+            }
+        }
+        for (center, angle) in shots {
+            self.new_enemy_shot(center, angle);
+        }
+    }
+
+    // Closest asteroid, ship or boss within `range`, the query homing missiles lock on with
+    fn nearest_enemy(&self, from: Vector2, range: f32) -> Option<(EntityId, Vector2)> {
+        self.entities.iter()
+            .filter_map(|(id, e)| match e {
+                Entity::Enemy(a) => Some((*id, rect_center(&a.rect))),
+                Entity::EnemyShip(s) => Some((*id, rect_center(&s.rect))),
+                Entity::Boss(b) => Some((*id, rect_center(&b.rect))),
+                _ => None,
+            })
+            .map(|(id, position)| (id, position, position.distance_to(from)))
+            .filter(|(_, _, distance)| *distance <= range)
+            .min_by(|a, b| a.2.total_cmp(&b.2))
+            .map(|(id, position, _)| (id, position))
+    }
+
+    // Point every homing missile at the enemy closest to it
+    fn guide_missiles(&mut self) {
+        let missiles: Vec<(EntityId, Vector2, f32)> = self.entities.iter()
+            .filter_map(|(id, e)| match e {
+                Entity::Projectile(l) => l.homing.map(|h| (*id, rect_center(&l.rect), h.range)),
+                _ => None,
+            })
+            .collect();
+        for (id, position, range) in missiles {
+            let aim = self.nearest_enemy(position, range).map(|(_, target)| target);
+            if let Some(Entity::Projectile(missile)) = self.entities.get_mut(&id) {
+                missile.aim = aim;
+            }
+        }
+    }
+
+    fn drop_bomb(&mut self, slot: usize) {
+        let Some(player) = self.player_mut(slot) else {
+            return;
+        };
+        if player.bombs == 0 {
+            return;
+        }
+        player.bombs -= 1;
+        let center = rect_center(&player.rect);
+        let id = self.new_id();
+        self.entities.insert(id, Entity::Shockwave(Box::new(Shockwave::new(center, slot))));
+        self.collidables.push(id);
+        self.drawables.push(id);
+    }
+
+    fn fire_missile(&mut self, slot: usize) {
+        let Some(player) = self.player_mut(slot) else {
+            return;
+        };
+        if player.missiles == 0 {
+            return;
+        }
+        player.missiles -= 1;
+        let rect = player.rect;
+        let id = self.new_id();
+        let muzzle = Vector2::new(rect.x + rect.width / 2., rect.y);
+        let missile = Lazer::new(id, muzzle, 0., &HOMING_MISSILE, 0, false)
+            .homing(HOMING)
+            .owned_by(slot);
+        self.entities.insert(id, Entity::Projectile(Box::new(missile)));
+        self.collidables.push(id);
+        self.drawables.push(id);
+    }
+
+    // Missiles that ran out of fuel blow up where they are, returns the owner and base points
+    // of every kill
+    fn detonate_spent_missiles(&mut self) -> Vec<(usize, usize)> {
+        let spent: Vec<(EntityId, usize, Vector2, Homing)> = self.entities.iter()
+            .filter_map(|(id, e)| match e {
+                Entity::Projectile(l) if l.is_spent() => l.homing.map(|h| (*id, l.owner, rect_center(&l.rect), h)),
+                _ => None,
+            })
+            .collect();
+        let mut kills = vec![];
+        for (id, owner, center, homing) in spent {
+            self.remove_entity(id);
+            let points = self.explode(center, homing.blast_radius, homing.blast_damage);
+            kills.extend(points.into_iter().map(|p| (owner, p)));
+        }
+        kills
+    }
+
+    // Deal `damage` to every enemy touching the blast, returns the base points of every kill
+    fn explode(&mut self, center: Vector2, radius: f32, damage: u32) -> Vec<usize> {
+        let id = self.new_id();
+        self.entities.insert(id, Entity::Explosion(Box::new(Explosion { center, radius, age: 0. })));
+        self.drawables.push(id);
+
+        let mut kills = vec![];
+        let mut split = vec![];
+        let mut destroyed = vec![];
+        for (id, entity) in self.entities.iter_mut() {
+            let in_blast = |rect: &Rectangle| rect_center(rect).distance_to(center) <= radius + rect.width / 2.;
+            match entity {
+                Entity::Enemy(a) => {
+                    if !in_blast(&a.rect) {
+                        continue;
+                    }
+                    kills.push(a.score());
+                    split.push(*id);
+                }
+                Entity::EnemyShip(s) => {
+                    if !in_blast(&s.rect) || !s.damage(damage) {
+                        continue;
+                    }
+                    kills.push(SHIP_SCORE);
+                    destroyed.push(*id);
+                }
+                Entity::Boss(b) => {
+                    // Back to front, destroyed parts are removed from the list
+                    let body = rect_center(&b.rect);
+                    for part in (0..b.parts.len()).rev() {
+                        if (body + b.parts[part].collider.offset).distance_to(center) > radius {
+                            continue;
+                        }
+                        match b.hit(part, damage) {
+                            BossHit::Damaged => (),
+                            BossHit::PartDestroyed => kills.push(BOSS_PART_SCORE),
+                            BossHit::Destroyed => {
+                                kills.push(BOSS_SCORE);
+                                destroyed.push(*id);
+                                break;
+                            }
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+        for id in split {
+            self.split_asteroid(id, center);
+        }
+        for id in destroyed {
+            self.remove_entity(id);
+        }
+        kills
+    }
+
+    // `center` is where the middle of the shot starts out
+    fn new_enemy_shot(&mut self, center: Vector2, angle: f32) {
+        let id = self.new_id();
+        // `Lazer::new` places the tail of the shot at the muzzle
+        let muzzle = Vector2::new(center.x, center.y + ENEMY_SHOT.length / 2.);
+        let lazer = Lazer::new(id, muzzle, angle, &ENEMY_SHOT, 1, false).hostile();
+        self.entities.insert(id, Entity::Projectile(Box::new(lazer)));
+        self.collidables.push(id);
+        self.drawables.push(id);
+    }
+
+    // Co-op ships start side by side
+    pub(crate) fn new_player(&mut self, players: usize, outline: Option<&[Vector2]>, bombs: u32, lives: u32) {
+        let id = self.new_id();
+        let slot = self.player_ids.len();
+        self.player_ids.push(id);
+        let spawn_x = SCREEN_WIDTH!() * (slot + 1) as f32 / (players + 1) as f32;
+        let player = Player::new(slot, spawn_x, outline, bombs, lives);
+        let entity = Entity::Player(Box::new(player));
+        self.entities.insert(id, entity);
+        self.collidables.push(id);
+        self.drawables.push(id);
+        self.scoring.push(Scoring::new());
+    }
+
+    fn new_lazer(&mut self, muzzle: Vector2, angle: f32, weapon: &Weapon, piercing: bool, owner: usize) {
+        let id = self.new_id();
+        let lazer = Lazer::new(id, muzzle, angle, &weapon.projectile, weapon.damage, piercing).owned_by(owner);
+        let entity = Entity::Projectile(Box::new(lazer));
+        self.entities.insert(id, entity);
+        self.collidables.push(id);
+        self.drawables.push(id);
+    }
+
+    fn new_pickup(&mut self, center: Vector2, power_up: PowerUp) {
+        let id = self.new_id();
+        let pickup = Pickup::new(id, center, power_up);
+        let entity = Entity::Pickup(Box::new(pickup));
+        self.entities.insert(id, entity);
+        self.collidables.push(id);
+        self.drawables.push(id);
+    }
+
+    // One tick of play, with each player's input by slot
+    pub(crate) fn step(&mut self, inputs: &[Input], delta_time: f32) {
+        self.control_players(inputs);
+        self.aim_enemies();
+        self.guide_missiles();
+        for entity in self.entities.values_mut() {
+            entity.update(inputs, delta_time);
+        }
+        self.fire_enemies();
+
+        self.check_collisions(delta_time);
+        if self.is_over() {
+            return;
+        }
+        for (slot, points) in self.detonate_spent_missiles() {
+            self.scoring[slot].kill(points);
+        }
+        for slot in self.near_misses() {
+            self.scoring[slot].near_miss();
+        }
+        for scoring in self.scoring.iter_mut() {
+            scoring.update(delta_time);
+        }
+
+        for slot in self.cull_offscreen() {
+            self.scoring[slot].miss();
+        }
+        self.update_waves(delta_time);
+    }
+
+    // Weapon switching and the trigger, steering happens in the player's own update
+    fn control_players(&mut self, inputs: &[Input]) {
+        for (slot, input) in inputs.iter().enumerate() {
+            if let Some(player) = self.player_mut(slot) {
+                if input.pressed(Action::SwitchWeapon) {
+                    player.weapon = (player.weapon + 1) % WEAPONS.len();
+                }
+                let weapon = &WEAPONS[player.weapon];
+                let (automatic, interval) = if player.effects.has(PowerUp::RapidFire) {
+                    (true, weapon.fire_interval / RAPID_FIRE_RATE)
+                } else {
+                    (weapon.automatic, weapon.fire_interval)
+                };
+                let trigger = if automatic {
+                    input.down(Action::Fire)
+                } else {
+                    input.pressed(Action::Fire)
+                };
+                if trigger && player.fire_cooldown <= 0. {
+                    player.fire_cooldown = interval;
+                    self.fire(slot);
+                }
+            }
+            if input.pressed(Action::Missile) {
+                self.fire_missile(slot);
+            }
+            if input.pressed(Action::Bomb) {
+                self.drop_bomb(slot);
+            }
+        }
+    }
+
+    fn check_collisions(&mut self, delta_time: f32) {
+        let world_ptr = self as *mut World;
+        
+        let mut destroyed = vec![];
+        let mut split_asteroids = vec![];
+        let mut explosions = vec![];
+        
+        let mut i = 0;
+        'outer: while i < self.collidables.len() {
+            let id1 = self.collidables[i];
+            if destroyed.contains(&id1) {
+                i += 1;
+                continue;
+            }
+            let mut j = i + 1;
+            while j < self.collidables.len() {
+                let id2 = self.collidables[j];
+                if destroyed.contains(&id2) {
+                    j += 1;
+                    continue;
+                }
+                let e1 = if let Some(e) = self.entities.get_mut(&id1) {
+                    let ptr = e as *mut Entity;
+                    // SAFETY: Safe unless we do not cause entities' reallocation
+                    unsafe { &mut *ptr }
+                } else {
+                    i += 1;
+                    continue 'outer;
+                };
+                let e2 = if let Some(e) = self.entities.get_mut(&id2) {
+                    let ptr = e as *mut Entity;
+                    // SAFETY: Safe unless we do not cause entities' reallocation
+                    unsafe { &mut *ptr }
+                } else {
+                    j += 1;
+                    continue;
+                };
+                if let Some(contact) = e1.check_collision(e2, delta_time) {
+                    let world = unsafe{ &mut *world_ptr };
+                    for event in e1.on_collision(e2, &contact, world) {
+                        match event {
+                            Event::EntityDestroyed(id) => {
+                                destroyed.push(id);
+                                if id == id1 {
+                                    i += 1;
+                                    continue 'outer;
+                                } 
+                            }
+                            Event::AsteroidDestroyed(id, impact) => {
+                                destroyed.push(id);
+                                split_asteroids.push((id, impact));
+                                if id == id1 {
+                                    i += 1;
+                                    continue 'outer;
+                                }
+                            }
+                            Event::ScoreIncreased(slot, points) => {
+                                self.scoring[slot].kill(points);
+                            }
+                            Event::Explosion(owner, center, radius, damage) => {
+                                explosions.push((owner, center, radius, damage));
+                            }
+                            Event::PlayerHit(slot) => {
+                                let world = unsafe{ &mut *world_ptr };
+                                if let Some(id) = world.hit_player(slot) {
+                                    destroyed.push(id);
+                                    if id == id1 {
+                                        i += 1;
+                                        continue 'outer;
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+                j += 1;
+            }
+            i += 1;
+        }
+        for (id, impact) in split_asteroids.drain(..) {
+            self.split_asteroid(id, impact);
+        }
+        for id in destroyed.drain(..) {
+            self.remove_entity(id);
+        }
+        for (owner, center, radius, damage) in explosions {
+            for points in self.explode(center, radius, damage) {
+                self.scoring[owner].kill(points);
+            }
+        }
+    }
+
+    // The run goes on while anyone still has a ship
+    pub fn is_over(&self) -> bool {
+        self.players().next().is_none()
+    }
+
+    // Fingerprint of the simulated state, two machines that agree on it are in sync. Debug
+    // output covers every field, health, effects and cooldowns included, and prints floats
+    // exactly.
+    pub fn checksum(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.id_count.hash(&mut hasher);
+        self.enemy_count.hash(&mut hasher);
+        for (id, entity) in &self.entities {
+            id.hash(&mut hasher);
+            format!("{entity:?}").hash(&mut hasher);
+        }
+        format!("{:?}", self.waves).hash(&mut hasher);
+        format!("{:?}", self.scoring).hash(&mut hasher);
+        self.passing.hash(&mut hasher);
+        let mut grazed: Vec<_> = self.grazed.iter().collect();
+        grazed.sort();
+        grazed.hash(&mut hasher);
+        // Where the generator is, without moving it on
+        self.rng.clone().random::<u64>().hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn fire(&mut self, slot: usize) {
+        let Some(player) = self.player(slot) else {
+            return;
+        };
+        let rect = player.rect;
+        let weapon = WEAPONS[player.weapon];
+        let piercing = player.effects.has(PowerUp::Piercing);
+        let mut extra_angles = vec![0.];
+        if player.effects.has(PowerUp::SpreadShot) {
+            extra_angles.extend([-SPREAD_ANGLE, SPREAD_ANGLE]);
+        }
+        for extra in extra_angles {
+            for (muzzle, angle) in weapon.shots() {
+                let muzzle = Vector2::new(rect.x + rect.width * muzzle, rect.y);
+                self.new_lazer(muzzle, angle + extra, &weapon, piercing, slot);
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // World with one ship and nothing else going on yet
+    fn world() -> World {
+        let mut world = World::new(1);
+        world.new_player(1, None, 3, 3);
+        world
+    }
+
+    // Asteroid parked on top of the first player's ship
+    fn asteroid_on_ship(world: &mut World, size: f32) -> EntityId {
+        let center = rect_center(&world.player(0).unwrap().rect);
+        let id = world.new_id();
+        let mut asteroid = Asteroid::new(id, size, 0., &mut world.rng);
+        asteroid.rect.x = center.x - size / 2.;
+        asteroid.rect.y = center.y - size / 2.;
+        world.add_asteroid(asteroid);
+        id
+    }
+
+    #[test]
+    fn asteroids_knocked_off_any_side_are_culled() {
+        let mut world = world();
+        let left = asteroid_on_ship(&mut world, 30.);
+        let top = asteroid_on_ship(&mut world, 30.);
+        let near = asteroid_on_ship(&mut world, 30.);
+        for (id, x, y) in [(left, -200., 300.), (top, 600., -200.), (near, -50., 300.)] {
+            if let Some(Entity::Enemy(a)) = world.entities.get_mut(&id) {
+                a.rect.x = x;
+                a.rect.y = y;
+            }
+        }
+        world.cull_offscreen();
+        assert!(!world.entities.contains_key(&left));
+        assert!(!world.entities.contains_key(&top));
+        assert!(world.entities.contains_key(&near));
+        assert_eq!(world.enemy_count, 1);
+    }
+
+    #[test]
+    fn fresh_spawns_above_the_screen_are_kept() {
+        let mut world = world();
+        world.new_asteroid(40., 200.);
+        world.new_ship(ShipBehavior::Strafe, Vector2::new(300., 100.));
+        world.cull_offscreen();
+        assert_eq!(world.enemy_count, 2);
+    }
+
+    #[test]
+    fn shield_removes_a_rammed_asteroid_without_fragments() {
+        let mut world = world();
+        world.player_mut(0).unwrap().effects.add(PowerUp::Shield);
+        let id = asteroid_on_ship(&mut world, 60.);
+        world.check_collisions(TICK);
+
+        assert!(!world.entities.contains_key(&id));
+        assert_eq!(world.enemy_count, 0);
+        let player = world.player(0).unwrap();
+        assert_eq!(player.lives, 3);
+        assert!(!player.effects.has(PowerUp::Shield));
+    }
+
+    #[test]
+    fn popped_shield_covers_the_next_hit() {
+        let mut world = world();
+        world.player_mut(0).unwrap().effects.add(PowerUp::Shield);
+        asteroid_on_ship(&mut world, 60.);
+        asteroid_on_ship(&mut world, 60.);
+        for _ in 0..5 {
+            world.check_collisions(TICK);
+        }
+        assert_eq!(world.player(0).unwrap().lives, 3);
+    }
+
+    fn move_asteroid(world: &mut World, id: EntityId, offset: Vector2) {
+        let center = rect_center(&world.player(0).unwrap().rect);
+        if let Some(Entity::Enemy(a)) = world.entities.get_mut(&id) {
+            a.rect.x = center.x + offset.x - a.rect.width / 2.;
+            a.rect.y = center.y + offset.y - a.rect.height / 2.;
+        }
+    }
+
+    #[test]
+    fn near_miss_pays_once_the_hazard_is_past() {
+        let mut world = world();
+        let id = asteroid_on_ship(&mut world, 30.);
+        let ship_radius = world.player(0).unwrap().rect.width / 2.;
+        move_asteroid(&mut world, id, Vector2::new(ship_radius + 15. + 10., 0.));
+
+        // Still close, it could yet hit
+        assert!(world.near_misses().is_empty());
+        assert!(world.near_misses().is_empty());
+        move_asteroid(&mut world, id, Vector2::new(300., 0.));
+        assert_eq!(world.near_misses(), [0]);
+        // Only once, even when it comes back
+        move_asteroid(&mut world, id, Vector2::new(ship_radius + 25., 0.));
+        assert!(world.near_misses().is_empty());
+        move_asteroid(&mut world, id, Vector2::new(300., 0.));
+        assert!(world.near_misses().is_empty());
+    }
+
+    #[test]
+    fn hazard_that_touched_the_ship_is_no_near_miss() {
+        let mut world = world();
+        let id = asteroid_on_ship(&mut world, 30.);
+        let ship_radius = world.player(0).unwrap().rect.width / 2.;
+        move_asteroid(&mut world, id, Vector2::new(ship_radius + 25., 0.));
+        assert!(world.near_misses().is_empty());
+        world.touch(id);
+        move_asteroid(&mut world, id, Vector2::new(300., 0.));
+        assert!(world.near_misses().is_empty());
+    }
+
+    #[test]
+    fn hazard_destroyed_while_close_is_no_near_miss() {
+        let mut world = world();
+        let id = asteroid_on_ship(&mut world, 30.);
+        let ship_radius = world.player(0).unwrap().rect.width / 2.;
+        move_asteroid(&mut world, id, Vector2::new(ship_radius + 25., 0.));
+        assert!(world.near_misses().is_empty());
+        world.remove_entity(id);
+        assert!(world.near_misses().is_empty());
+    }
+
+    #[test]
+    fn missiles_only_come_back_with_the_next_wave() {
+        let mut world = world();
+        let id = world.player_ids[0];
+        if let Some(Entity::Player(p)) = world.entities.get_mut(&id) {
+            p.missiles = 0;
+        }
+        for _ in 0..60 * 30 {
+            world.entities.get_mut(&id).unwrap().update(&[Input::default()], TICK);
+        }
+        assert_eq!(world.player(0).unwrap().missiles, 0);
+
+        world.waves.state = WaveState::Break(TICK / 2.);
+        world.update_waves(TICK);
+        assert_eq!(world.player(0).unwrap().missiles, MISSILE_CAPACITY);
+    }
+
+    #[test]
+    fn checksum_sees_more_than_positions() {
+        let world = world();
+        assert_eq!(world.checksum(), world.clone().checksum());
+
+        let mut cooled = world.clone();
+        let id = cooled.player_ids[0];
+        if let Some(Entity::Player(p)) = cooled.entities.get_mut(&id) {
+            p.fire_cooldown += 0.1;
+        }
+        assert_ne!(cooled.checksum(), world.checksum());
+
+        let mut shielded = world.clone();
+        if let Some(Entity::Player(p)) = shielded.entities.get_mut(&id) {
+            p.effects.add(PowerUp::Shield);
+        }
+        assert_ne!(shielded.checksum(), world.checksum());
+
+        let mut drawn = world.clone();
+        drawn.rng.random::<u32>();
+        assert_ne!(drawn.checksum(), world.checksum());
+    }
+}
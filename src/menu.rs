@@ -0,0 +1,151 @@
+use raylib::prelude::*;
+
+use crate::{Action, Binding, Input, Keymap, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuItem {
+    Resume,
+    // Rebinding screen for a player slot
+    Controls(usize),
+    Quit,
+}
+
+impl MenuItem {
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Resume => "Resume",
+            Self::Controls(0) => "Controls",
+            Self::Controls(_) => "Player 2 controls",
+            Self::Quit => "Quit",
+        }
+    }
+}
+
+// Vertical list of items, moved through with the menu actions
+pub struct Menu {
+    pub title: &'static str,
+    pub items: Vec<MenuItem>,
+    pub selected: usize,
+}
+
+impl Menu {
+    pub fn pause(players: usize) -> Self {
+        let mut items = vec![MenuItem::Resume];
+        items.extend((0..players).map(MenuItem::Controls));
+        items.push(MenuItem::Quit);
+        Self {
+            title: "GAME PAUSED",
+            items,
+            selected: 0,
+        }
+    }
+
+    // Returns the item picked this frame
+    pub fn navigate(&mut self, input: &Input) -> Option<MenuItem> {
+        let count = self.items.len();
+        if input.pressed(Action::MenuUp) {
+            self.selected = (self.selected + count - 1) % count;
+        }
+        if input.pressed(Action::MenuDown) {
+            self.selected = (self.selected + 1) % count;
+        }
+        input.pressed(Action::Confirm).then(|| self.items[self.selected])
+    }
+
+    pub fn draw(&self, d: &mut RaylibDrawHandle) {
+        let x = (SCREEN_WIDTH!()/2. - 180.) as i32;
+        let y = SCREEN_HEIGHT!()/2.;
+        d.draw_text(self.title, x, y as i32, 40, Color::WHITE);
+        for (i, item) in self.items.iter().enumerate() {
+            let (marker, color) = if i == self.selected { ("> ", Color::GOLD) } else { ("  ", Color::WHITE) };
+            d.draw_text(
+                &format!("{marker}{}", item.label()),
+                x, (y + 50. + i as f32 * 35.) as i32,
+                30, color
+            );
+        }
+    }
+}
+
+// Rows below the actions on the controls screen
+const RESET_ROW: usize = Action::ALL.len();
+const BACK_ROW: usize = RESET_ROW + 1;
+
+// Lists every action with its bindings. Confirming an action waits for the next key or
+// gamepad button and binds it in place of the action's binding on that device.
+pub struct ControlsScreen {
+    // Player slot whose keymap is edited
+    pub slot: usize,
+    selected: usize,
+    // Waiting for a key or button for this action
+    pub capturing: Option<Action>,
+    message: Option<String>,
+}
+
+impl ControlsScreen {
+    pub fn new(slot: usize) -> Self {
+        Self { slot, selected: 0, capturing: None, message: None }
+    }
+
+    // `captured` is the key or button pressed this frame while capturing. Returns true
+    // once the player leaves the screen.
+    pub fn update(&mut self, input: &Input, keymap: &mut Keymap, captured: Option<Binding>) -> bool {
+        if let Some(action) = self.capturing {
+            if let Some(binding) = captured {
+                self.capturing = None;
+                self.message = match keymap.rebind(action, binding) {
+                    Ok(()) => None,
+                    Err(other) => Some(format!("{} is already used by {}", binding.name(), other.label())),
+                };
+            }
+            return false;
+        }
+
+        let count = BACK_ROW + 1;
+        if input.pressed(Action::MenuUp) {
+            self.selected = (self.selected + count - 1) % count;
+        }
+        if input.pressed(Action::MenuDown) {
+            self.selected = (self.selected + 1) % count;
+        }
+        if input.pressed(Action::Back) {
+            return true;
+        }
+        if !input.pressed(Action::Confirm) {
+            return false;
+        }
+        self.message = None;
+        match self.selected {
+            RESET_ROW => *keymap = Keymap::defaults(keymap.slot()),
+            BACK_ROW => return true,
+            row => self.capturing = Some(Action::ALL[row]),
+        }
+        false
+    }
+
+    pub fn draw(&self, d: &mut RaylibDrawHandle, keymap: &Keymap) {
+        let x = (SCREEN_WIDTH!()/2. - 300.) as i32;
+        let y = SCREEN_HEIGHT!()/2. - 280.;
+        let title = if keymap.slot() == 0 { "CONTROLS" } else { "PLAYER 2 CONTROLS" };
+        d.draw_text(title, x, y as i32, 40, Color::WHITE);
+
+        let row_y = |row: usize| (y + 55. + row as f32 * 26.) as i32;
+        for (row, action) in Action::ALL.iter().enumerate() {
+            let color = if row == self.selected { Color::GOLD } else { Color::WHITE };
+            let bindings = if self.capturing == Some(*action) {
+                "press a key or button...".to_string()
+            } else {
+                keymap.bindings_for(*action).map(|b| b.name()).collect::<Vec<_>>().join(", ")
+            };
+            d.draw_text(action.label(), x, row_y(row), 20, color);
+            d.draw_text(&bindings, x + 200, row_y(row), 20, color);
+        }
+        for (row, label) in [(RESET_ROW, "Reset to defaults"), (BACK_ROW, "Back")] {
+            let color = if row == self.selected { Color::GOLD } else { Color::WHITE };
+            d.draw_text(label, x, row_y(row) + 10, 20, color);
+        }
+        if let Some(message) = &self.message {
+            d.draw_text(message, x, row_y(BACK_ROW + 1) + 20, 20, Color::RED);
+        }
+    }
+}
@@ -0,0 +1,637 @@
+use std::collections::VecDeque;
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+
+use raylib::prelude::*;
+
+use crate::{Action, Input, Snapshot, PLAYER_TINTS, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+// Online play: the host runs the only `World` and every other instance is a client that
+// sends what its player pressed and draws the snapshots it gets back. Everything goes over
+// UDP, each datagram is one message starting with `MAGIC` and a message tag.
+pub const DEFAULT_PORT: u16 = 7777;
+const MAGIC: u32 = 0x4153_5452;
+// Nothing heard for this long and the other side is gone
+const TIMEOUT: Duration = Duration::from_secs(5);
+// How often a client repeats its join request until the game starts. The host answers
+// every one, which keeps both sides from timing out while the lobby is open.
+const JOIN_INTERVAL: Duration = Duration::from_millis(500);
+// Snapshots kept on both sides to encode and decode deltas against
+const HISTORY: usize = 64;
+// Clients draw this far behind the newest snapshot so there is one to interpolate towards
+const INTERPOLATION_DELAY: f32 = 0.1;
+// Largest UDP payload
+const MAX_DATAGRAM: usize = 65507;
+
+// Little endian encoding of the message fields
+#[derive(Default)]
+pub struct Writer {
+    pub bytes: Vec<u8>,
+}
+
+impl Writer {
+    pub fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.bytes.extend(value.to_le_bytes());
+    }
+
+    pub fn f32(&mut self, value: f32) {
+        self.bytes.extend(value.to_le_bytes());
+    }
+
+    pub fn vector(&mut self, value: Vector2) {
+        self.f32(value.x);
+        self.f32(value.y);
+    }
+
+    pub fn color(&mut self, value: Color) {
+        self.bytes.extend([value.r, value.g, value.b, value.a]);
+    }
+}
+
+// Every read fails on a truncated message
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let (head, rest) = self.bytes.split_first_chunk::<N>()?;
+        self.bytes = rest;
+        Some(*head)
+    }
+
+    pub fn u8(&mut self) -> Option<u8> {
+        self.take::<1>().map(|[b]| b)
+    }
+
+    pub fn u32(&mut self) -> Option<u32> {
+        self.take().map(u32::from_le_bytes)
+    }
+
+    pub fn f32(&mut self) -> Option<f32> {
+        self.take().map(f32::from_le_bytes)
+    }
+
+    pub fn vector(&mut self) -> Option<Vector2> {
+        Some(Vector2::new(self.f32()?, self.f32()?))
+    }
+
+    pub fn color(&mut self) -> Option<Color> {
+        self.take().map(|[r, g, b, a]| Color::new(r, g, b, a))
+    }
+
+    pub fn rest(&self) -> &'a [u8] {
+        self.bytes
+    }
+}
+
+// Flight controls of a client for one frame. Presses are sent as running counts per
+// action, so a press still arrives when the datagram carrying it got lost.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NetInput {
+    movement: Vector2,
+    target: Option<Vector2>,
+    // Bit per `Action::GAMEPLAY` entry
+    down: u8,
+    presses: [u8; Action::GAMEPLAY.len()],
+}
+
+impl NetInput {
+    fn encode(&self, w: &mut Writer) {
+        w.vector(self.movement);
+        match self.target {
+            Some(target) => {
+                w.u8(1);
+                w.vector(target);
+            }
+            None => w.u8(0),
+        }
+        w.u8(self.down);
+        self.presses.iter().for_each(|p| w.u8(*p));
+    }
+
+    fn decode(r: &mut Reader) -> Option<Self> {
+        let (movement, target) = decode_controls(r)?;
+        let down = r.u8()?;
+        let mut presses = [0; Action::GAMEPLAY.len()];
+        for press in presses.iter_mut() {
+            *press = r.u8()?;
+        }
+        Some(Self { movement, target, down, presses })
+    }
+}
+
+// Stick direction and optional pointer target of a remote player. They come from the
+// other side, so anything but finite numbers drops the message.
+pub fn decode_controls(r: &mut Reader) -> Option<(Vector2, Option<Vector2>)> {
+    let movement = r.vector()?;
+    let target = match r.u8()? {
+        0 => None,
+        _ => Some(r.vector()?),
+    };
+    clamp_controls(movement, target)
+}
+
+// Keeps movement to the stick's range and the target to the screen, None when either is
+// not a finite number
+pub fn clamp_controls(movement: Vector2, target: Option<Vector2>) -> Option<(Vector2, Option<Vector2>)> {
+    let finite = |v: Vector2| v.x.is_finite() && v.y.is_finite();
+    if !finite(movement) || !target.is_none_or(finite) {
+        return None;
+    }
+    let movement = Vector2::new(movement.x.clamp(-1., 1.), movement.y.clamp(-1., 1.));
+    let target = target.map(|t| Vector2::new(t.x.clamp(0., SCREEN_WIDTH!()), t.y.clamp(0., SCREEN_HEIGHT!())));
+    Some((movement, target))
+}
+
+pub enum Message {
+    // Client asks for a seat in the lobby
+    Join,
+    // Host gave the client this player slot
+    Welcome(u8),
+    // No seat left or the game already started
+    Full,
+    // Lobby closed, the game is on
+    Start,
+    // Client input with its sequence number and the newest snapshot tick it has
+    Input(u32, u32, NetInput),
+    // Delta encoded snapshot, decoded against the client's history
+    Snapshot(Vec<u8>),
+    Leave,
+}
+
+impl Message {
+    fn encode(&self) -> Vec<u8> {
+        let mut w = Writer::default();
+        w.u32(MAGIC);
+        match self {
+            Self::Join => w.u8(0),
+            Self::Welcome(slot) => {
+                w.u8(1);
+                w.u8(*slot);
+            }
+            Self::Full => w.u8(2),
+            Self::Start => w.u8(3),
+            Self::Input(sequence, ack, input) => {
+                w.u8(4);
+                w.u32(*sequence);
+                w.u32(*ack);
+                input.encode(&mut w);
+            }
+            Self::Snapshot(payload) => {
+                w.u8(5);
+                w.bytes.extend(payload);
+            }
+            Self::Leave => w.u8(6),
+        }
+        w.bytes
+    }
+
+    // Anything that is not one of our messages is dropped
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        let mut r = Reader::new(bytes);
+        if r.u32()? != MAGIC {
+            return None;
+        }
+        Some(match r.u8()? {
+            0 => Self::Join,
+            1 => Self::Welcome(r.u8()?),
+            2 => Self::Full,
+            3 => Self::Start,
+            4 => Self::Input(r.u32()?, r.u32()?, NetInput::decode(&mut r)?),
+            5 => Self::Snapshot(r.rest().to_vec()),
+            6 => Self::Leave,
+            _ => return None,
+        })
+    }
+}
+
+fn send(socket: &UdpSocket, to: SocketAddr, message: &Message) {
+    let bytes = message.encode();
+    if bytes.len() > MAX_DATAGRAM {
+        eprintln!("Dropped a {} byte message, too big for one datagram", bytes.len());
+        return;
+    }
+    // Lost datagrams are part of the deal, so is a full send buffer
+    let _ = socket.send_to(&bytes, to);
+}
+
+// Everything waiting on a non-blocking socket
+fn receive(socket: &UdpSocket) -> Vec<(SocketAddr, Message)> {
+    let mut buffer = vec![0; MAX_DATAGRAM];
+    let mut messages = vec![];
+    while let Ok((len, from)) = socket.recv_from(&mut buffer) {
+        if let Some(message) = Message::decode(&buffer[..len]) {
+            messages.push((from, message));
+        }
+    }
+    messages
+}
+
+// A client as the host sees it
+struct Peer {
+    addr: SocketAddr,
+    slot: usize,
+    last_heard: Instant,
+    // Newest input sequence applied, older datagrams arriving late are ignored
+    sequence: u32,
+    // Newest snapshot the client has, the base for its next delta
+    ack: u32,
+    input: NetInput,
+    // Press counts already turned into presses
+    seen_presses: [u8; Action::GAMEPLAY.len()],
+    pressed: [bool; Action::GAMEPLAY.len()],
+}
+
+pub struct Host {
+    socket: UdpSocket,
+    peers: Vec<Peer>,
+    // Still taking players
+    open: bool,
+    history: VecDeque<Snapshot>,
+    // Slots of peers that left or timed out since the last call to `departed`
+    departed: Vec<usize>,
+}
+
+impl Host {
+    pub fn bind(port: u16) -> io::Result<Self> {
+        let socket = UdpSocket::bind(("0.0.0.0", port))?;
+        socket.set_nonblocking(true)?;
+        Ok(Self { socket, peers: vec![], open: true, history: VecDeque::new(), departed: vec![] })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.socket.local_addr().map_or(0, |addr| addr.port())
+    }
+
+    // The host's own player included
+    pub fn players(&self) -> usize {
+        1 + self.peers.len()
+    }
+
+    pub fn receive(&mut self) {
+        for (from, message) in receive(&self.socket) {
+            let peer = self.peers.iter_mut().find(|p| p.addr == from);
+            match (message, peer) {
+                // Welcome got lost or the client is still waiting in the lobby, send it again
+                (Message::Join, Some(peer)) => {
+                    peer.last_heard = Instant::now();
+                    send(&self.socket, from, &Message::Welcome(peer.slot as u8));
+                }
+                (Message::Join, None) => {
+                    // Lowest free slot, a seat given up in the lobby goes to the next one to
+                    // join. Nobody joins once the game started, so a slot is never reused in play.
+                    let free = (1..PLAYER_TINTS.len()).find(|slot| self.peers.iter().all(|p| p.slot != *slot));
+                    let Some(slot) = free.filter(|_| self.open) else {
+                        send(&self.socket, from, &Message::Full);
+                        continue;
+                    };
+                    self.peers.push(Peer {
+                        addr: from,
+                        slot,
+                        last_heard: Instant::now(),
+                        sequence: 0,
+                        ack: 0,
+                        input: NetInput::default(),
+                        seen_presses: [0; Action::GAMEPLAY.len()],
+                        pressed: [false; Action::GAMEPLAY.len()],
+                    });
+                    send(&self.socket, from, &Message::Welcome(slot as u8));
+                }
+                (Message::Input(sequence, ack, input), Some(peer)) => {
+                    peer.last_heard = Instant::now();
+                    peer.ack = peer.ack.max(ack);
+                    if sequence <= peer.sequence {
+                        continue;
+                    }
+                    peer.sequence = sequence;
+                    for (i, count) in input.presses.iter().enumerate() {
+                        if *count != peer.seen_presses[i] {
+                            peer.pressed[i] = true;
+                        }
+                    }
+                    peer.seen_presses = input.presses;
+                    peer.input = input;
+                }
+                (Message::Leave, Some(_)) => self.drop_peer(from),
+                _ => (),
+            }
+        }
+        let silent: Vec<SocketAddr> = self.peers.iter()
+            .filter(|p| p.last_heard.elapsed() > TIMEOUT)
+            .map(|p| p.addr)
+            .collect();
+        for addr in silent {
+            self.drop_peer(addr);
+        }
+    }
+
+    fn drop_peer(&mut self, addr: SocketAddr) {
+        if let Some(i) = self.peers.iter().position(|p| p.addr == addr) {
+            let peer = self.peers.remove(i);
+            self.departed.push(peer.slot);
+        }
+    }
+
+    // Closes the lobby
+    pub fn start(&mut self) {
+        self.open = false;
+        for peer in &self.peers {
+            send(&self.socket, peer.addr, &Message::Start);
+        }
+    }
+
+    // What every client asked for since the last frame, by player slot
+    pub fn inputs(&mut self) -> Vec<(usize, Input)> {
+        self.peers.iter_mut()
+            .map(|peer| {
+                let mut input = Input::default();
+                input.movement = peer.input.movement;
+                input.target = peer.input.target;
+                for (i, action) in Action::GAMEPLAY.into_iter().enumerate() {
+                    input.set(action, peer.input.down & (1 << i) != 0, peer.pressed[i]);
+                }
+                peer.pressed = [false; Action::GAMEPLAY.len()];
+                (peer.slot, input)
+            })
+            .collect()
+    }
+
+    pub fn departed(&mut self) -> Vec<usize> {
+        std::mem::take(&mut self.departed)
+    }
+
+    // Each client gets the snapshot as a delta against the newest one it confirmed,
+    // or in full when that one is too old
+    pub fn send(&mut self, snapshot: Snapshot) {
+        for peer in &self.peers {
+            let base = self.history.iter().find(|s| s.tick == peer.ack);
+            let mut w = Writer::default();
+            snapshot.encode(base, &mut w);
+            send(&self.socket, peer.addr, &Message::Snapshot(w.bytes));
+        }
+        self.history.push_back(snapshot);
+        if self.history.len() > HISTORY {
+            self.history.pop_front();
+        }
+    }
+}
+
+impl Drop for Host {
+    fn drop(&mut self) {
+        for peer in &self.peers {
+            send(&self.socket, peer.addr, &Message::Leave);
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConnectionState {
+    Joining,
+    // Got a player slot, waiting for the host to start
+    Lobby(usize),
+    Playing(usize),
+    Rejected,
+    Lost,
+}
+
+// The client's end
+pub struct Connection {
+    socket: UdpSocket,
+    host: SocketAddr,
+    pub state: ConnectionState,
+    // Decoded snapshots, oldest first
+    history: VecDeque<Snapshot>,
+    received_at: Instant,
+    last_heard: Instant,
+    last_join: Option<Instant>,
+    sequence: u32,
+    presses: [u8; Action::GAMEPLAY.len()],
+}
+
+impl Connection {
+    // `address` is `host:port`, the port can be left out
+    pub fn connect(address: &str) -> io::Result<Self> {
+        let address = if address.contains(':') { address.to_string() } else { format!("{address}:{DEFAULT_PORT}") };
+        let host = address.to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no address for {address}")))?;
+        let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            socket,
+            host,
+            state: ConnectionState::Joining,
+            history: VecDeque::new(),
+            received_at: Instant::now(),
+            last_heard: Instant::now(),
+            last_join: None,
+            sequence: 0,
+            presses: [0; Action::GAMEPLAY.len()],
+        })
+    }
+
+    pub fn update(&mut self) {
+        let joining = matches!(self.state, ConnectionState::Joining | ConnectionState::Lobby(_));
+        if joining && self.last_join.is_none_or(|at| at.elapsed() > JOIN_INTERVAL) {
+            send(&self.socket, self.host, &Message::Join);
+            self.last_join = Some(Instant::now());
+        }
+        for (from, message) in receive(&self.socket) {
+            if from != self.host {
+                continue;
+            }
+            self.last_heard = Instant::now();
+            match message {
+                Message::Welcome(slot) if self.state == ConnectionState::Joining => {
+                    self.state = ConnectionState::Lobby(slot as usize);
+                }
+                Message::Full if self.state == ConnectionState::Joining => self.state = ConnectionState::Rejected,
+                Message::Start => {
+                    if let ConnectionState::Lobby(slot) = self.state {
+                        self.state = ConnectionState::Playing(slot);
+                    }
+                }
+                Message::Snapshot(payload) => self.add_snapshot(&payload),
+                Message::Leave => self.state = ConnectionState::Lost,
+                _ => (),
+            }
+        }
+        let waiting = matches!(self.state, ConnectionState::Lobby(_) | ConnectionState::Playing(_));
+        if waiting && self.last_heard.elapsed() > TIMEOUT {
+            self.state = ConnectionState::Lost;
+        }
+    }
+
+    fn add_snapshot(&mut self, payload: &[u8]) {
+        // The first snapshot also means the game started, in case `Start` got lost
+        if let ConnectionState::Lobby(slot) = self.state {
+            self.state = ConnectionState::Playing(slot);
+        }
+        let Some(snapshot) = Snapshot::decode(&mut Reader::new(payload), |tick| self.history.iter().find(|s| s.tick == tick)) else {
+            return;
+        };
+        if self.history.back().is_some_and(|newest| newest.tick >= snapshot.tick) {
+            return;
+        }
+        self.history.push_back(snapshot);
+        self.received_at = Instant::now();
+        if self.history.len() > HISTORY {
+            self.history.pop_front();
+        }
+    }
+
+    pub fn send_input(&mut self, input: &Input) {
+        if !matches!(self.state, ConnectionState::Playing(_)) {
+            return;
+        }
+        let mut down = 0;
+        for (i, action) in Action::GAMEPLAY.into_iter().enumerate() {
+            if input.down(action) {
+                down |= 1 << i;
+            }
+            if input.pressed(action) {
+                self.presses[i] = self.presses[i].wrapping_add(1);
+            }
+        }
+        self.sequence += 1;
+        let ack = self.history.back().map_or(0, |s| s.tick);
+        let input = NetInput { movement: input.movement, target: input.target, down, presses: self.presses };
+        send(&self.socket, self.host, &Message::Input(self.sequence, ack, input));
+    }
+
+    pub fn latest(&self) -> Option<&Snapshot> {
+        self.history.back()
+    }
+
+    // The two snapshots around the moment to draw and how far along between them it is
+    pub fn interpolation(&self) -> Option<(&Snapshot, &Snapshot, f32)> {
+        let latest = self.history.back()?;
+        let time = latest.time + self.received_at.elapsed().as_secs_f32() - INTERPOLATION_DELAY;
+        let after = self.history.iter().position(|s| s.time >= time).unwrap_or(self.history.len() - 1);
+        let from = &self.history[after.saturating_sub(1)];
+        let to = &self.history[after];
+        let span = to.time - from.time;
+        let t = if span > 0. { ((time - from.time) / span).clamp(0., 1.) } else { 1. };
+        Some((from, to, t))
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        send(&self.socket, self.host, &Message::Leave);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input_bytes(movement: Vector2, target: Option<Vector2>) -> Vec<u8> {
+        let input = NetInput { movement, target, down: 0b101, presses: [3; Action::GAMEPLAY.len()] };
+        Message::Input(7, 5, input).encode()
+    }
+
+    #[test]
+    fn messages_round_trip() {
+        assert!(matches!(Message::decode(&Message::Join.encode()), Some(Message::Join)));
+        assert!(matches!(Message::decode(&Message::Welcome(2).encode()), Some(Message::Welcome(2))));
+        assert!(matches!(Message::decode(&Message::Full.encode()), Some(Message::Full)));
+        assert!(matches!(Message::decode(&Message::Start.encode()), Some(Message::Start)));
+        assert!(matches!(Message::decode(&Message::Leave.encode()), Some(Message::Leave)));
+        let Some(Message::Snapshot(payload)) = Message::decode(&Message::Snapshot(vec![1, 2, 3]).encode()) else {
+            panic!("snapshot did not come back");
+        };
+        assert_eq!(payload, [1, 2, 3]);
+
+        let bytes = input_bytes(Vector2::new(0.5, -1.), Some(Vector2::new(300., 200.)));
+        let Some(Message::Input(7, 5, input)) = Message::decode(&bytes) else {
+            panic!("input did not come back");
+        };
+        assert_eq!(input.movement, Vector2::new(0.5, -1.));
+        assert_eq!(input.target, Some(Vector2::new(300., 200.)));
+        assert_eq!(input.down, 0b101);
+        assert_eq!(input.presses, [3; Action::GAMEPLAY.len()]);
+    }
+
+    #[test]
+    fn foreign_and_truncated_datagrams_are_dropped() {
+        assert!(Message::decode(b"hello").is_none());
+        assert!(Message::decode(&[]).is_none());
+        let bytes = input_bytes(Vector2::zero(), None);
+        assert!(Message::decode(&bytes[..bytes.len() - 1]).is_none());
+        let mut unknown = Message::Join.encode();
+        unknown[4] = 99;
+        assert!(Message::decode(&unknown).is_none());
+    }
+
+    #[test]
+    fn input_that_is_not_a_number_is_dropped() {
+        for (movement, target) in [
+            (Vector2::new(f32::NAN, 0.), None),
+            (Vector2::new(0., f32::INFINITY), None),
+            (Vector2::zero(), Some(Vector2::new(f32::NAN, 10.))),
+            (Vector2::zero(), Some(Vector2::new(10., f32::NEG_INFINITY))),
+        ] {
+            assert!(Message::decode(&input_bytes(movement, target)).is_none());
+        }
+    }
+
+    #[test]
+    fn input_out_of_range_is_clamped() {
+        let bytes = input_bytes(Vector2::new(5., -3.), Some(Vector2::new(-50., 1e9)));
+        let Some(Message::Input(_, _, input)) = Message::decode(&bytes) else {
+            panic!("input did not come back");
+        };
+        assert_eq!(input.movement, Vector2::new(1., -1.));
+        assert_eq!(input.target, Some(Vector2::new(0., SCREEN_HEIGHT!())));
+    }
+
+    #[test]
+    fn long_lobby_keeps_its_client() {
+        let mut host = Host::bind(0).unwrap();
+        let mut client = Connection::connect(&format!("127.0.0.1:{}", host.port())).unwrap();
+        let until = Instant::now() + TIMEOUT + Duration::from_secs(1);
+        while Instant::now() < until {
+            client.update();
+            host.receive();
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        assert_eq!(host.players(), 2);
+        assert!(host.departed().is_empty());
+        assert_eq!(client.state, ConnectionState::Lobby(1));
+    }
+
+    #[test]
+    fn seat_left_in_the_lobby_goes_to_the_next_client() {
+        let mut host = Host::bind(0).unwrap();
+        let address = format!("127.0.0.1:{}", host.port());
+        let settle = |host: &mut Host, client: &mut Connection| {
+            for _ in 0..20 {
+                client.update();
+                host.receive();
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        };
+
+        let mut first = Connection::connect(&address).unwrap();
+        settle(&mut host, &mut first);
+        assert_eq!(first.state, ConnectionState::Lobby(1));
+        drop(first);
+
+        let mut second = Connection::connect(&address).unwrap();
+        settle(&mut host, &mut second);
+        assert_eq!(host.departed(), [1]);
+        assert_eq!(second.state, ConnectionState::Lobby(1));
+        assert_eq!(host.players(), 2);
+    }
+}
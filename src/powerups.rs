@@ -0,0 +1,137 @@
+use rand::*;
+use raylib::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PowerUp {
+    // Absorbs one asteroid hit
+    Shield,
+    // Hold fire to keep shooting
+    RapidFire,
+    // Extra lazers fanned out to the sides
+    SpreadShot,
+    // Lazers keep going after a hit
+    Piercing,
+    // One more smart bomb, not a timed effect
+    Bomb,
+}
+
+impl PowerUp {
+    pub const ALL: [PowerUp; 5] = [Self::Shield, Self::RapidFire, Self::SpreadShot, Self::Piercing, Self::Bomb];
+    // The ones that last a while, a bomb is used up on pickup
+    pub const TIMED: [PowerUp; 4] = [Self::Shield, Self::RapidFire, Self::SpreadShot, Self::Piercing];
+
+    pub fn random(rng: &mut impl Rng) -> Self {
+        Self::ALL[rng.random_range(0..Self::ALL.len())]
+    }
+
+    // Seconds the effect lasts once collected, None for those that are not timed
+    pub fn duration(&self) -> Option<f32> {
+        match self {
+            Self::Shield => Some(15.),
+            Self::RapidFire => Some(8.),
+            Self::SpreadShot => Some(10.),
+            Self::Piercing => Some(8.),
+            Self::Bomb => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Shield => "SHIELD",
+            Self::RapidFire => "RAPID FIRE",
+            Self::SpreadShot => "SPREAD",
+            Self::Piercing => "PIERCING",
+            Self::Bomb => "BOMB",
+        }
+    }
+
+    // Letter drawn on the pickup
+    pub fn symbol(&self) -> &'static str {
+        &self.name()[..1]
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            Self::Shield => Color::SKYBLUE,
+            Self::RapidFire => Color::ORANGE,
+            Self::SpreadShot => Color::LIME,
+            Self::Piercing => Color::VIOLET,
+            Self::Bomb => Color::PINK,
+        }
+    }
+}
+
+// Timed power-ups active on a player
+#[derive(Debug, Clone, Default)]
+pub struct Effects {
+    active: Vec<(PowerUp, f32)>,
+}
+
+impl Effects {
+    // Collecting an effect that is already active restarts its timer, power-ups without
+    // one are not effects
+    pub fn add(&mut self, power_up: PowerUp) {
+        let Some(duration) = power_up.duration() else {
+            return;
+        };
+        self.active.retain(|(p, _)| *p != power_up);
+        self.active.push((power_up, duration));
+    }
+
+    pub fn has(&self, power_up: PowerUp) -> bool {
+        self.active.iter().any(|(p, _)| *p == power_up)
+    }
+
+    // Use up an effect early, returns whether it was active
+    pub fn consume(&mut self, power_up: PowerUp) -> bool {
+        let had = self.has(power_up);
+        self.active.retain(|(p, _)| *p != power_up);
+        had
+    }
+
+    pub fn update(&mut self, delta_time: f32) {
+        for (_, remaining) in self.active.iter_mut() {
+            *remaining -= delta_time;
+        }
+        self.active.retain(|(_, remaining)| *remaining > 0.);
+    }
+
+    // Active effects with their remaining time in seconds
+    pub fn iter(&self) -> impl Iterator<Item = &(PowerUp, f32)> {
+        self.active.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn effects_run_out_and_restart_when_collected_again() {
+        let mut effects = Effects::default();
+        effects.add(PowerUp::RapidFire);
+        effects.update(5.);
+        effects.add(PowerUp::RapidFire);
+        effects.update(5.);
+        assert!(effects.has(PowerUp::RapidFire));
+        effects.update(5.);
+        assert!(!effects.has(PowerUp::RapidFire));
+    }
+
+    #[test]
+    fn bombs_are_not_timed() {
+        assert!(PowerUp::TIMED.iter().all(|p| p.duration().is_some()));
+        assert_eq!(PowerUp::Bomb.duration(), None);
+        let mut effects = Effects::default();
+        effects.add(PowerUp::Bomb);
+        assert_eq!(effects.iter().count(), 0);
+    }
+
+    #[test]
+    fn consume_ends_an_effect_early() {
+        let mut effects = Effects::default();
+        effects.add(PowerUp::Shield);
+        assert!(effects.consume(PowerUp::Shield));
+        assert!(!effects.consume(PowerUp::Shield));
+    }
+}
@@ -0,0 +1,164 @@
+use raylib::prelude::*;
+
+use crate::{
+    load_textures, star_field, Action, Binding, Config, Connection, ConnectionState, Controls, Entity, Keymap,
+    NetPlayer, Snapshot, Textures, MISSILE_CAPACITY, PLAYER_TINTS, SCREEN_HEIGHT, SCREEN_WIDTH, WEAPONS,
+};
+
+// Window of a player who joined someone else's game: sends the local controls to the host
+// and draws the snapshots that come back. Nothing is simulated here but the stars.
+pub struct RemoteGame {
+    rl: RaylibHandle,
+    rt: RaylibThread,
+    textures: Textures,
+    controls: Controls,
+    connection: Connection,
+    stars: Vec<Entity>,
+    // Shown while joining
+    address: String,
+}
+
+impl RemoteGame {
+    pub fn new(connection: Connection, address: &str) -> Self {
+        let (mut rl, rt) = raylib::init()
+            .title("Asteroids")
+            .size(SCREEN_WIDTH!() as i32, SCREEN_HEIGHT!() as i32)
+            .vsync()
+            .build();
+        rl.set_target_fps(60);
+        let config = Config::load();
+
+        let textures = load_textures(&mut rl, &rt);
+
+        Self {
+            rl, rt,
+            textures,
+            controls: Controls::new(Keymap::load(0), config.gamepad_deadzone, config.pointer_controls),
+            connection,
+            stars: star_field(),
+            address: address.to_string(),
+        }
+    }
+
+    pub fn run(&mut self) {
+        while !self.rl.window_should_close() {
+            let delta_time = self.rl.get_frame_time();
+            let input = self.controls.poll(&self.rl, delta_time, None);
+            self.connection.update();
+            self.connection.send_input(&input);
+
+            let over = self.connection.latest().is_some_and(|s| s.over);
+            let finished = over || matches!(self.connection.state, ConnectionState::Lost | ConnectionState::Rejected);
+            if finished && (input.pressed(Action::Pause) || input.pressed(Action::Confirm)) {
+                break;
+            }
+            if input.pressed(Action::Fullscreen) {
+                self.rl.toggle_fullscreen();
+            }
+            for star in self.stars.iter_mut() {
+                star.update(&[], delta_time);
+            }
+            self.draw();
+        }
+    }
+
+    fn draw(&mut self) {
+        let time = self.rl.get_time() as f32;
+        let mut d = self.rl.begin_drawing(&self.rt);
+        d.clear_background(Color::BLACK);
+        for star in &self.stars {
+            star.draw(&mut d, &self.textures);
+        }
+
+        if let Some((from, to, t)) = self.connection.interpolation() {
+            for entity in from.interpolate(to, t) {
+                entity.draw(&mut d, &self.textures, time);
+            }
+        }
+
+        let status = match self.connection.state {
+            ConnectionState::Joining => Some(format!("Joining {}...", self.address)),
+            ConnectionState::Lobby(slot) => Some(format!("Joined as player {}, waiting for the host to start", slot + 1)),
+            ConnectionState::Rejected => Some("The game is full or already running".to_string()),
+            ConnectionState::Lost => Some("Lost the connection to the host".to_string()),
+            ConnectionState::Playing(_) => None,
+        };
+        if let Some(status) = status {
+            d.draw_text(&status, (SCREEN_WIDTH!()/2. - 300.) as i32, (SCREEN_HEIGHT!()/2.) as i32, 30, Color::WHITE);
+        }
+
+        let Some(snapshot) = self.connection.latest() else {
+            return;
+        };
+        // Key hints only make sense for the player at this machine
+        let local = match self.connection.state {
+            ConnectionState::Playing(slot) => Some((slot, &self.controls.keymap)),
+            _ => None,
+        };
+        draw_snapshot_hud(&mut d, snapshot, local, "Press ENTER or START to exit");
+    }
+}
+
+impl Drop for RemoteGame {
+    fn drop(&mut self) {
+        self.textures.clear();
+    }
+}
+
+// Scores, wave, boss health and the final result as the host shows them. `local` is the
+// slot played at this machine and its keymap, for the key hints.
+pub fn draw_snapshot_hud(d: &mut RaylibDrawHandle, snapshot: &Snapshot, local: Option<(usize, &Keymap)>, exit_hint: &str) {
+    for (slot, player) in snapshot.players.iter().enumerate() {
+        let x = if slot == 0 { 35 } else { SCREEN_WIDTH!() as i32 - 420 };
+        let keymap = local.filter(|(local, _)| *local == slot).map(|(_, keymap)| keymap);
+        draw_remote_hud(d, x, slot, player, keymap);
+    }
+    d.draw_text(&format!("wave: {}", snapshot.wave), 35, 30, 20, Color::WHITE);
+    d.draw_fps(35, 50);
+
+    if let Some(health) = snapshot.boss_health {
+        let bar = Rectangle::new(SCREEN_WIDTH!() / 2. - 200., 20., 400., 14.);
+        d.draw_rectangle_rec(Rectangle { width: bar.width * health, ..bar }, Color::RED);
+        d.draw_rectangle_lines_ex(bar, 2., Color::WHITE);
+    }
+
+    if snapshot.over {
+        let total: u32 = snapshot.players.iter().map(|p| p.score).sum();
+        let winner = snapshot.players.iter().enumerate().max_by_key(|(_, p)| p.score).map_or(0, |(slot, _)| slot);
+        let title = if snapshot.versus {
+            format!("PLAYER {} WINS", winner + 1)
+        } else {
+            format!("TEAM SCORE: {total}")
+        };
+        d.draw_text(
+            &title,
+            (SCREEN_WIDTH!()/2. - 180.) as i32,
+            (SCREEN_HEIGHT!()/2.) as i32,
+            40, Color::WHITE
+        );
+        d.draw_text(
+            exit_hint,
+            (SCREEN_WIDTH!()/2. - 180.) as i32,
+            (SCREEN_HEIGHT!()/2. + 40.) as i32,
+            30, Color::WHITE
+        );
+    }
+}
+
+// Same panel as the host draws, from the snapshot
+fn draw_remote_hud(d: &mut RaylibDrawHandle, x: i32, slot: usize, player: &NetPlayer, keymap: Option<&Keymap>) {
+    d.draw_text(&format!("score: {}", player.score), x, 10, 20, Color::WHITE);
+    d.draw_text(&format!("x{:.1}", player.multiplier), x + 185, 10, 20, Color::GOLD);
+    let Some(ship) = player.ship else {
+        d.draw_text("out", x, 75, 20, Color::GRAY);
+        return;
+    };
+    let key = |action: Action| keymap
+        .and_then(|k| k.bindings_for(action).find(|b| matches!(b, Binding::Key(_))))
+        .map_or(String::new(), |b| format!(" ({})", b.name()));
+    let weapon = WEAPONS.get(ship.weapon as usize).map_or("?", |w| w.name);
+    d.draw_text(&format!("lives: {}", ship.lives), x, 75, 20, PLAYER_TINTS[slot.min(PLAYER_TINTS.len() - 1)]);
+    d.draw_text(&format!("weapon: {weapon}{}", key(Action::SwitchWeapon)), x, 95, 20, Color::WHITE);
+    d.draw_text(&format!("missiles: {}/{}{}", ship.missiles, MISSILE_CAPACITY, key(Action::Missile)), x, 115, 20, Color::GOLD);
+    d.draw_text(&format!("bombs: {}{}", ship.bombs, key(Action::Bomb)), x, 135, 20, Color::PINK);
+}
@@ -0,0 +1,681 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+
+use raylib::prelude::*;
+
+use crate::{clamp_controls, decode_controls, Action, Input, Reader, World, Writer, DEFAULT_PORT, TICK};
+
+// Peer to peer versus with rollback: both machines run the whole `World`, send each other
+// nothing but their inputs and guess the other player's input until it arrives. A wrong
+// guess puts the world back to the save state of that tick and plays it again.
+const MAGIC: u32 = 0x4153_5250;
+// Ticks the local input is held back before it takes effect, hides that much latency
+// without rolling back at all
+pub const INPUT_DELAY: u32 = 2;
+// Furthest the simulation may run past the last input from the peer before it waits
+pub const MAX_ROLLBACK: u32 = 8;
+// Frames without a word from the peer before it counts as gone
+const TIMEOUT_FRAMES: u32 = 5 * 60;
+// Ticks ahead of the peer after which this side skips a frame to let it catch up
+const MAX_ADVANTAGE: i32 = 1;
+// Frames between two of those skips, so the game slows down evenly instead of hitching
+const WAIT_INTERVAL: u32 = 20;
+
+// Where the datagrams of a session go, a socket or the simulated link of the test
+pub trait Transport {
+    fn send(&mut self, bytes: &[u8]);
+    fn receive(&mut self) -> Option<Vec<u8>>;
+}
+
+pub struct UdpTransport {
+    socket: UdpSocket,
+    peer: SocketAddr,
+}
+
+impl UdpTransport {
+    // `peer` is `host[:port]`, the port defaults to the one of online play
+    pub fn bind(port: u16, peer: &str) -> io::Result<Self> {
+        let peer = if peer.contains(':') { peer.to_string() } else { format!("{peer}:{DEFAULT_PORT}") };
+        let peer = peer.to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no address for {peer}")))?;
+        let socket = UdpSocket::bind(("0.0.0.0", port))?;
+        socket.set_nonblocking(true)?;
+        Ok(Self { socket, peer })
+    }
+
+    pub fn peer(&self) -> SocketAddr {
+        self.peer
+    }
+}
+
+impl Transport for UdpTransport {
+    fn send(&mut self, bytes: &[u8]) {
+        // Lost datagrams are made up for by the next ones
+        let _ = self.socket.send_to(bytes, self.peer);
+    }
+
+    fn receive(&mut self) -> Option<Vec<u8>> {
+        let mut buffer = [0; 8192];
+        while let Ok((len, from)) = self.socket.recv_from(&mut buffer) {
+            if from == self.peer {
+                return Some(buffer[..len].to_vec());
+            }
+        }
+        None
+    }
+}
+
+// The part of an `Input` the simulation reads, what goes over the wire
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PeerInput {
+    movement: Vector2,
+    target: Option<Vector2>,
+    // Bit per `Action::GAMEPLAY` entry
+    down: u8,
+    pressed: u8,
+}
+
+impl PeerInput {
+    // Kept to what the peer decodes, or the two sides would simulate different inputs. A
+    // target past the edge of a bigger fullscreen window gets clamped on the way over.
+    fn from_input(input: &Input) -> Self {
+        let (movement, target) = clamp_controls(input.movement, input.target).unwrap_or_default();
+        let bits = |test: &dyn Fn(Action) -> bool| Action::GAMEPLAY.iter()
+            .enumerate()
+            .filter(|(_, action)| test(**action))
+            .fold(0, |bits, (i, _)| bits | 1 << i);
+        Self {
+            movement,
+            target,
+            down: bits(&|action| input.down(action)),
+            pressed: bits(&|action| input.pressed(action)),
+        }
+    }
+
+    fn to_input(self) -> Input {
+        let mut input = Input::default();
+        input.movement = self.movement;
+        input.target = self.target;
+        for (i, action) in Action::GAMEPLAY.into_iter().enumerate() {
+            input.set(action, self.down & 1 << i != 0, self.pressed & 1 << i != 0);
+        }
+        input
+    }
+
+    // The guess for a tick the peer's input has not arrived for: the same keys held,
+    // nothing new pressed
+    fn predicted(self) -> Self {
+        Self { pressed: 0, ..self }
+    }
+
+    fn encode(&self, w: &mut Writer) {
+        w.vector(self.movement);
+        match self.target {
+            Some(target) => {
+                w.u8(1);
+                w.vector(target);
+            }
+            None => w.u8(0),
+        }
+        w.u8(self.down);
+        w.u8(self.pressed);
+    }
+
+    fn decode(r: &mut Reader) -> Option<Self> {
+        let (movement, target) = decode_controls(r)?;
+        Some(Self { movement, target, down: r.u8()?, pressed: r.u8()? })
+    }
+}
+
+// What each side builds its versus world from. Both bring their own from their settings and
+// assets, the one of the first slot is played.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Rules {
+    pub lives: u32,
+    pub bombs: u32,
+    // Collider outline of the ship
+    pub outline: Option<Vec<Vector2>>,
+}
+
+impl Rules {
+    fn encode(&self, w: &mut Writer) {
+        w.u32(self.lives);
+        w.u32(self.bombs);
+        let outline = self.outline.as_deref().unwrap_or_default();
+        w.u8(outline.len().min(u8::MAX as usize) as u8);
+        outline.iter().take(u8::MAX as usize).for_each(|point| w.vector(*point));
+    }
+
+    // An outline has at least three points, all of them finite
+    fn decode(r: &mut Reader) -> Option<Self> {
+        let (lives, bombs) = (r.u32()?.max(1), r.u32()?);
+        let outline = (0..r.u8()?).map(|_| r.vector()).collect::<Option<Vec<_>>>()?;
+        if outline.iter().any(|p| !p.x.is_finite() || !p.y.is_finite()) || (1..3).contains(&outline.len()) {
+            return None;
+        }
+        Some(Self { lives, bombs, outline: Some(outline).filter(|o| !o.is_empty()) })
+    }
+}
+
+enum Packet {
+    // Sender's random number and rules, who plays which slot, the seed and the rules
+    // played follow from the two
+    Hello(u32, Rules),
+    // Sender's number and tick, how far ahead of us it thinks it is, the first of our
+    // ticks it is missing input for, then its inputs from a tick on
+    Inputs { nonce: u32, tick: u32, advantage: i32, ack: u32, start: u32, inputs: Vec<PeerInput> },
+    Leave,
+}
+
+impl Packet {
+    fn encode(&self) -> Vec<u8> {
+        let mut w = Writer::default();
+        w.u32(MAGIC);
+        match self {
+            Self::Hello(nonce, rules) => {
+                w.u8(0);
+                w.u32(*nonce);
+                rules.encode(&mut w);
+            }
+            Self::Inputs { nonce, tick, advantage, ack, start, inputs } => {
+                w.u8(1);
+                w.u32(*nonce);
+                w.u32(*tick);
+                w.u32(*advantage as u32);
+                w.u32(*ack);
+                w.u32(*start);
+                w.u8(inputs.len() as u8);
+                inputs.iter().for_each(|input| input.encode(&mut w));
+            }
+            Self::Leave => w.u8(2),
+        }
+        w.bytes
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        let mut r = Reader::new(bytes);
+        if r.u32()? != MAGIC {
+            return None;
+        }
+        match r.u8()? {
+            0 => Some(Self::Hello(r.u32()?, Rules::decode(&mut r)?)),
+            1 => {
+                let (nonce, tick, advantage, ack, start) = (r.u32()?, r.u32()?, r.u32()? as i32, r.u32()?, r.u32()?);
+                let mut inputs = vec![];
+                for _ in 0..r.u8()? {
+                    inputs.push(PeerInput::decode(&mut r)?);
+                }
+                Some(Self::Inputs { nonce, tick, advantage, ack, start, inputs })
+            }
+            2 => Some(Self::Leave),
+            _ => None,
+        }
+    }
+}
+
+// How often a session had to go back, for the test and anyone tuning the delays
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RollbackStats {
+    pub rollbacks: u32,
+    pub resimulated: u32,
+    pub deepest: u32,
+    // Frames spent waiting for the peer, too far ahead or letting it catch up
+    pub stalls: u32,
+}
+
+pub struct Session<T: Transport> {
+    transport: T,
+    nonce: u32,
+    peer_nonce: Option<u32>,
+    peer_rules: Option<Rules>,
+    // Ours once the handshake is done, sent again to a peer still asking for them
+    rules: Option<Rules>,
+    // Next tick to simulate
+    tick: u32,
+    // Our inputs by tick, kept until the peer has them and no replay needs them
+    local: BTreeMap<u32, PeerInput>,
+    // The peer's inputs by tick as they arrive
+    remote: BTreeMap<u32, PeerInput>,
+    // Peer input every tick not yet settled ran with, and the world before it
+    guessed: BTreeMap<u32, (PeerInput, World)>,
+    // Peer input is known for every tick before this one
+    confirmed: u32,
+    // The peer has our input for every tick before this one
+    acked: u32,
+    // Newest tick and frame advantage the peer told us about
+    peer_tick: u32,
+    peer_advantage: i32,
+    frames_since_wait: u32,
+    silent_frames: u32,
+    left: bool,
+    pub stats: RollbackStats,
+    // World checksum before every settled tick, for spotting desyncs
+    checksums: Option<Vec<u64>>,
+}
+
+impl<T: Transport> Session<T> {
+    pub fn new(transport: T, nonce: u32) -> Self {
+        // Nobody acts during the input delay at the start, both sides know that much
+        let idle: BTreeMap<u32, PeerInput> = (0..INPUT_DELAY).map(|tick| (tick, PeerInput::default())).collect();
+        Self {
+            transport,
+            nonce,
+            peer_nonce: None,
+            peer_rules: None,
+            rules: None,
+            tick: 0,
+            local: idle.clone(),
+            remote: idle,
+            guessed: BTreeMap::new(),
+            confirmed: INPUT_DELAY,
+            acked: 0,
+            peer_tick: 0,
+            peer_advantage: 0,
+            frames_since_wait: 0,
+            silent_frames: 0,
+            left: false,
+            stats: RollbackStats::default(),
+            checksums: None,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn recording_checksums(mut self) -> Self {
+        self.checksums = Some(vec![]);
+        self
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    #[cfg(test)]
+    pub fn checksums(&self) -> &[u64] {
+        self.checksums.as_deref().unwrap_or_default()
+    }
+
+    // Lower number plays the first slot, equal numbers would start over with new ones
+    pub fn slot(&self) -> usize {
+        self.peer_nonce.map_or(0, |peer| (self.nonce > peer) as usize)
+    }
+
+    // Call every frame with our rules until it gives the seed and the rules both sides
+    // start their world from
+    pub fn handshake(&mut self, rules: &Rules) -> Option<(u64, Rules)> {
+        self.poll();
+        if self.peer_nonce == Some(self.nonce) {
+            self.nonce = rand::random();
+            self.peer_nonce = None;
+            self.peer_rules = None;
+        }
+        self.transport.send(&Packet::Hello(self.nonce, rules.clone()).encode());
+        let peer = self.peer_nonce?;
+        let theirs = self.peer_rules.clone()?;
+        self.rules = Some(rules.clone());
+        let seed = (self.nonce.min(peer) as u64) << 32 | self.nonce.max(peer) as u64;
+        Some((seed, if self.slot() == 0 { rules.clone() } else { theirs }))
+    }
+
+    // Every tick simulated so far has the peer's real input behind it
+    pub fn is_settled(&self) -> bool {
+        self.confirmed >= self.tick
+    }
+
+    pub fn is_lost(&self) -> bool {
+        self.left || self.silent_frames > TIMEOUT_FRAMES
+    }
+
+    // Reads what the peer sent and answers with our unacknowledged inputs
+    pub fn poll(&mut self) {
+        self.silent_frames += 1;
+        while let Some(bytes) = self.transport.receive() {
+            let Some(packet) = Packet::decode(&bytes) else {
+                continue;
+            };
+            self.silent_frames = 0;
+            match packet {
+                Packet::Hello(nonce, rules) => {
+                    self.peer_nonce = Some(nonce);
+                    self.peer_rules = Some(rules);
+                    // Our hello got lost and the peer is still waiting for it
+                    if let Some(rules) = &self.rules {
+                        self.transport.send(&Packet::Hello(self.nonce, rules.clone()).encode());
+                    }
+                }
+                Packet::Inputs { nonce, tick, advantage, ack, start, inputs } => {
+                    self.peer_nonce = Some(nonce);
+                    self.peer_tick = self.peer_tick.max(tick);
+                    self.peer_advantage = advantage;
+                    self.acked = self.acked.max(ack);
+                    for (tick, input) in (start..).zip(inputs) {
+                        if tick >= self.confirmed {
+                            self.remote.insert(tick, input);
+                        }
+                    }
+                }
+                Packet::Leave => self.left = true,
+            }
+        }
+        while self.remote.contains_key(&self.confirmed) {
+            self.confirmed += 1;
+        }
+        // A replay goes back at most to the oldest guess
+        let oldest = self.guessed.keys().next().map_or(self.tick, |tick| *tick);
+        let needed = self.acked.min(oldest);
+        self.local.retain(|tick, _| *tick >= needed);
+        if self.peer_nonce.is_some() {
+            self.send_inputs();
+        }
+    }
+
+    fn send_inputs(&mut self) {
+        let unacked = self.local.range(self.acked..);
+        let start = unacked.clone().next().map_or(self.acked, |(tick, _)| *tick);
+        let packet = Packet::Inputs {
+            nonce: self.nonce,
+            tick: self.tick,
+            advantage: self.advantage(),
+            ack: self.confirmed,
+            start,
+            inputs: unacked.map(|(_, input)| *input).take(u8::MAX as usize).collect(),
+        };
+        self.transport.send(&packet.encode());
+    }
+
+    // Ticks we are ahead of the peer as far as we can tell, latency makes both sides
+    // look ahead by the same amount
+    fn advantage(&self) -> i32 {
+        self.tick as i32 - self.peer_tick as i32
+    }
+
+    // One tick with our input, after replaying whatever the peer's input proved wrong.
+    // False when the session waits for the peer this frame instead.
+    pub fn advance(&mut self, world: &mut World, input: &Input) -> bool {
+        self.poll();
+        self.rollback(world);
+
+        self.frames_since_wait += 1;
+        let too_far = self.tick >= self.confirmed + MAX_ROLLBACK;
+        let ahead = (self.advantage() - self.peer_advantage) / 2 > MAX_ADVANTAGE
+            && self.frames_since_wait > WAIT_INTERVAL;
+        if too_far || ahead {
+            self.frames_since_wait = 0;
+            self.stats.stalls += 1;
+            return false;
+        }
+
+        self.local.insert(self.tick + INPUT_DELAY, PeerInput::from_input(input));
+        self.simulate(world);
+        self.settle();
+        true
+    }
+
+    // Back to the first tick that ran on a wrong guess and forward again to where we were
+    fn rollback(&mut self, world: &mut World) {
+        let wrong = self.guessed.iter()
+            .find(|(tick, (guess, _))| self.remote.get(tick).is_some_and(|real| real != guess))
+            .map(|(tick, (_, saved))| (*tick, saved.clone()));
+        let Some((from, saved)) = wrong else {
+            return;
+        };
+        *world = saved;
+        let until = self.tick;
+        self.tick = from;
+        while self.tick < until {
+            self.simulate(world);
+        }
+        self.stats.rollbacks += 1;
+        self.stats.resimulated += until - from;
+        self.stats.deepest = self.stats.deepest.max(until - from);
+    }
+
+    fn simulate(&mut self, world: &mut World) {
+        let local = self.local[&self.tick];
+        let remote = match self.remote.get(&self.tick) {
+            Some(input) => *input,
+            None => self.remote.range(..self.tick).next_back().map_or_else(PeerInput::default, |(_, i)| i.predicted()),
+        };
+        self.guessed.insert(self.tick, (remote, world.clone()));
+        let mut inputs = [local.to_input(), remote.to_input()];
+        if self.slot() == 1 {
+            inputs.reverse();
+        }
+        world.step(&inputs, TICK);
+        self.tick += 1;
+    }
+
+    // After a rollback every guess up to the peer's input is right, those ticks are final
+    // and their save states can go
+    fn settle(&mut self) {
+        while let Some(entry) = self.guessed.first_entry()
+            && *entry.key() < self.confirmed
+        {
+            let (_, saved) = entry.remove();
+            if let Some(checksums) = self.checksums.as_mut() {
+                checksums.push(saved.checksum());
+            }
+        }
+        let oldest = self.confirmed.min(self.tick).saturating_sub(1);
+        self.remote.retain(|tick, _| *tick >= oldest);
+    }
+}
+
+impl<T: Transport> Drop for Session<T> {
+    fn drop(&mut self) {
+        self.transport.send(&Packet::Leave.encode());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
+
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::SCREEN_WIDTH;
+
+    // Datagrams in flight with the frame they arrive on
+    type Wire = Rc<RefCell<Vec<(u32, Vec<u8>)>>>;
+
+    // One end of an in-process link that delays, reorders and drops datagrams, for testing
+    // sessions against bad networks without a network
+    struct LossyLink {
+        clock: Rc<Cell<u32>>,
+        outbox: Wire,
+        inbox: Wire,
+        // Delay in frames, give or take the jitter, and the share of datagrams lost
+        latency: u32,
+        jitter: u32,
+        loss: f64,
+        rng: StdRng,
+    }
+
+    impl LossyLink {
+        // Both ends of a link, the clock is advanced by whoever runs the peers
+        fn pair(clock: Rc<Cell<u32>>, latency: u32, jitter: u32, loss: f64, seed: u64) -> (Self, Self) {
+            let (a, b): (Wire, Wire) = (Rc::default(), Rc::default());
+            let end = |outbox: &Wire, inbox: &Wire, seed| Self {
+                clock: clock.clone(),
+                outbox: outbox.clone(),
+                inbox: inbox.clone(),
+                latency,
+                jitter,
+                loss,
+                rng: StdRng::seed_from_u64(seed),
+            };
+            (end(&a, &b, seed), end(&b, &a, seed + 1))
+        }
+    }
+
+    impl Transport for LossyLink {
+        fn send(&mut self, bytes: &[u8]) {
+            if self.rng.random_bool(self.loss) {
+                return;
+            }
+            let delay = self.latency + self.rng.random_range(0..=self.jitter);
+            self.outbox.borrow_mut().push((self.clock.get() + delay, bytes.to_vec()));
+        }
+
+        fn receive(&mut self) -> Option<Vec<u8>> {
+            let mut inbox = self.inbox.borrow_mut();
+            let due = inbox.iter().position(|(at, _)| *at <= self.clock.get())?;
+            Some(inbox.remove(due).1)
+        }
+    }
+
+    // Two sessions in one process over a link with the given latency in milliseconds, jitter
+    // and loss. Both run a scripted game and have to agree on every confirmed tick.
+    fn run_test(latency_ms: u32, loss: f64, ticks: u32) -> Result<RollbackStats, String> {
+        let frame_ms = (TICK * 1000.) as u32;
+        let latency = latency_ms / frame_ms.max(1);
+        let clock = Rc::new(Cell::new(0));
+        let (link_a, link_b) = LossyLink::pair(clock.clone(), latency, latency / 2 + 1, loss, 1);
+        // Different settings on both sides, the first slot's are played
+        let mut peers = [
+            (Session::new(link_a, 1).recording_checksums(), None, StdRng::seed_from_u64(10), Rules { lives: 3, bombs: 3, outline: None }),
+            (Session::new(link_b, 2).recording_checksums(), None, StdRng::seed_from_u64(20), Rules { lives: 5, bombs: 1, outline: Some(square()) }),
+        ];
+
+        let mut frames = 0;
+        while peers.iter().any(|(session, _, _, _)| session.tick < ticks) {
+            frames += 1;
+            if frames > ticks * 20 {
+                return Err("the peers stopped making progress".to_string());
+            }
+            clock.set(clock.get() + 1);
+            for (session, world, rng, rules) in peers.iter_mut() {
+                let Some(world) = world else {
+                    if let Some((seed, rules)) = session.handshake(rules) {
+                        let mut fresh = World::new(seed);
+                        for _ in 0..2 {
+                            fresh.new_player(2, rules.outline.as_deref(), rules.bombs, rules.lives);
+                        }
+                        *world = Some(fresh);
+                    }
+                    continue;
+                };
+                if session.tick < ticks {
+                    session.advance(world, &scripted_input(rng));
+                } else {
+                    session.poll();
+                }
+                if session.is_lost() {
+                    return Err("a peer timed out".to_string());
+                }
+            }
+        }
+        // Let the last inputs arrive and the final guesses settle
+        for _ in 0..(latency * 4 + 60) {
+            clock.set(clock.get() + 1);
+            for (session, world, _, _) in peers.iter_mut() {
+                session.poll();
+                if let Some(world) = world {
+                    session.rollback(world);
+                    session.settle();
+                }
+            }
+        }
+
+        let [(a, _, _, _), (b, _, _, _)] = &peers;
+        let (a, b) = (a.checksums(), b.checksums());
+        if let Some(tick) = a.iter().zip(b).position(|(a, b)| a != b) {
+            return Err(format!("desync at tick {tick}"));
+        }
+        if a.len().min(b.len()) < ticks as usize {
+            return Err(format!("only {} of {ticks} ticks were confirmed", a.len().min(b.len())));
+        }
+        Ok(peers[0].0.stats)
+    }
+
+    fn square() -> Vec<Vector2> {
+        vec![Vector2::new(0., 0.), Vector2::new(1., 0.), Vector2::new(1., 1.), Vector2::new(0., 1.)]
+    }
+
+    // Random flying and shooting, new keys every few ticks like a player would
+    fn scripted_input(rng: &mut StdRng) -> Input {
+        let mut input = Input::default();
+        input.movement = Vector2::new(rng.random_range(-1..=1) as f32, rng.random_range(-1..=1) as f32);
+        input.set(Action::Fire, rng.random_bool(0.5), rng.random_bool(0.1));
+        for action in [Action::SwitchWeapon, Action::Missile, Action::Bomb] {
+            input.set(action, false, rng.random_bool(0.005));
+        }
+        input
+    }
+
+    #[test]
+    fn peers_agree_on_a_clean_link() {
+        let stats = run_test(0, 0., 60 * 10).unwrap();
+        assert_eq!(stats.rollbacks, 0);
+    }
+
+    #[test]
+    fn peers_agree_over_a_slow_lossy_link() {
+        for (latency_ms, loss) in [(50, 0.05), (150, 0.2), (250, 0.3)] {
+            let stats = run_test(latency_ms, loss, 60 * 10)
+                .unwrap_or_else(|err| panic!("{latency_ms} ms and {loss} loss: {err}"));
+            assert!(stats.rollbacks > 0);
+            assert!(stats.deepest <= MAX_ROLLBACK);
+        }
+    }
+
+    #[test]
+    fn input_that_is_not_a_number_is_dropped() {
+        let input = PeerInput { movement: Vector2::new(f32::NAN, 0.), ..PeerInput::default() };
+        let mut w = Writer::default();
+        input.encode(&mut w);
+        assert!(PeerInput::decode(&mut Reader::new(&w.bytes)).is_none());
+
+        let input = PeerInput { movement: Vector2::new(4., 0.), target: Some(Vector2::new(-1., 5.)), down: 1, pressed: 1 };
+        let mut w = Writer::default();
+        input.encode(&mut w);
+        let decoded = PeerInput::decode(&mut Reader::new(&w.bytes)).unwrap();
+        assert_eq!(decoded, PeerInput { movement: Vector2::new(1., 0.), target: Some(Vector2::new(0., 5.)), ..input });
+
+        // Our own input is simulated as the peer will see it
+        let mut local = Input::default();
+        local.target = Some(Vector2::new(SCREEN_WIDTH!() + 300., 100.));
+        let input = PeerInput::from_input(&local);
+        let mut w = Writer::default();
+        input.encode(&mut w);
+        assert_eq!(input.target, Some(Vector2::new(SCREEN_WIDTH!(), 100.)));
+        assert_eq!(PeerInput::decode(&mut Reader::new(&w.bytes)), Some(input));
+
+        local.movement = Vector2::new(f32::NAN, 0.);
+        assert_eq!(PeerInput::from_input(&local), PeerInput::default());
+    }
+
+    #[test]
+    fn both_sides_play_the_first_slots_rules() {
+        let clock = Rc::new(Cell::new(0));
+        let (link_a, link_b) = LossyLink::pair(clock.clone(), 1, 0, 0., 1);
+        let mut a = Session::new(link_a, 7);
+        let mut b = Session::new(link_b, 3);
+        let ours = Rules { lives: 3, bombs: 3, outline: None };
+        let theirs = Rules { lives: 1, bombs: 9, outline: Some(square()) };
+
+        let mut agreed = (None, None);
+        for _ in 0..10 {
+            clock.set(clock.get() + 1);
+            agreed.0 = agreed.0.or_else(|| a.handshake(&ours));
+            agreed.1 = agreed.1.or_else(|| b.handshake(&theirs));
+        }
+        assert_eq!(agreed.0, Some((3 << 32 | 7, theirs.clone())));
+        assert_eq!(agreed.0, agreed.1);
+    }
+
+    #[test]
+    fn hello_with_a_broken_outline_is_dropped() {
+        let hello = |outline| Packet::Hello(1, Rules { lives: 0, bombs: 2, outline }).encode();
+        let Some(Packet::Hello(1, rules)) = Packet::decode(&hello(Some(square()))) else {
+            panic!("hello did not come back");
+        };
+        assert_eq!(rules, Rules { lives: 1, bombs: 2, outline: Some(square()) });
+
+        assert!(Packet::decode(&hello(Some(vec![Vector2::new(f32::NAN, 0.); 3]))).is_none());
+        assert!(Packet::decode(&hello(Some(square()[..2].to_vec()))).is_none());
+    }
+}
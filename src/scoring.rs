@@ -0,0 +1,173 @@
+// Kills are worth their base points times the combo multiplier. Every kill bumps the
+// multiplier and restarts the combo timer, once the timer runs out the multiplier
+// drains back towards 1. Kills without a missed shot in between build a streak that
+// pays a bonus every few kills, and letting something scrape past the ship pays too.
+
+// Seconds after a kill before the multiplier starts to drain
+pub const COMBO_WINDOW: f32 = 2.;
+const COMBO_STEP: f32 = 0.1;
+const MAX_MULTIPLIER: f32 = 8.;
+// Multiplier lost per second once the combo timer ran out
+const COMBO_DECAY: f32 = 0.5;
+// A streak bonus is paid every this many kills, growing with the streak
+const STREAK_STEP: u32 = 10;
+const STREAK_BONUS: usize = 250;
+const NEAR_MISS_POINTS: usize = 50;
+
+// Where the points of a run came from, shown on the game-over screen
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Breakdown {
+    pub kills: usize,
+    pub combo: usize,
+    pub streak: usize,
+    pub near_misses: usize,
+}
+
+impl Breakdown {
+    pub fn total(&self) -> usize {
+        self.kills + self.combo + self.streak + self.near_misses
+    }
+}
+
+// Co-op runs show the team's breakdown
+impl std::ops::Add for Breakdown {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            kills: self.kills + other.kills,
+            combo: self.combo + other.combo,
+            streak: self.streak + other.streak,
+            near_misses: self.near_misses + other.near_misses,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Scoring {
+    pub multiplier: f32,
+    // Seconds left before the multiplier starts to drain
+    pub combo_timer: f32,
+    // Kills since the last missed shot
+    pub streak: u32,
+    pub breakdown: Breakdown,
+}
+
+impl Default for Scoring {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scoring {
+    pub fn new() -> Self {
+        Self {
+            multiplier: 1.,
+            combo_timer: 0.,
+            streak: 0,
+            breakdown: Breakdown::default(),
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.breakdown.total()
+    }
+
+    // `points` are the base points of whatever was destroyed
+    pub fn kill(&mut self, points: usize) {
+        let awarded = (points as f32 * self.multiplier).round() as usize;
+        self.breakdown.kills += points;
+        self.breakdown.combo += awarded.saturating_sub(points);
+        self.multiplier = (self.multiplier + COMBO_STEP).min(MAX_MULTIPLIER);
+        self.combo_timer = COMBO_WINDOW;
+
+        self.streak += 1;
+        if self.streak.is_multiple_of(STREAK_STEP) {
+            self.breakdown.streak += STREAK_BONUS * (self.streak / STREAK_STEP) as usize;
+        }
+    }
+
+    // A player shot left the screen without hitting anything
+    pub fn miss(&mut self) {
+        self.streak = 0;
+    }
+
+    pub fn near_miss(&mut self) {
+        self.breakdown.near_misses += NEAR_MISS_POINTS;
+    }
+
+    pub fn update(&mut self, delta_time: f32) {
+        if self.combo_timer > 0. {
+            self.combo_timer = (self.combo_timer - delta_time).max(0.);
+        } else {
+            self.multiplier = (self.multiplier - COMBO_DECAY * delta_time).max(1.);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kills_build_up_the_combo() {
+        let mut scoring = Scoring::new();
+        scoring.kill(100);
+        scoring.kill(100);
+        assert_eq!(scoring.breakdown.kills, 200);
+        assert_eq!(scoring.breakdown.combo, 10);
+        assert_eq!(scoring.total(), 210);
+        assert!((scoring.multiplier - 1.2).abs() < 1e-5);
+    }
+
+    #[test]
+    fn multiplier_drains_after_the_window() {
+        let mut scoring = Scoring::new();
+        for _ in 0..5 {
+            scoring.kill(10);
+        }
+        let multiplier = scoring.multiplier;
+
+        scoring.update(COMBO_WINDOW);
+        assert_eq!(scoring.multiplier, multiplier);
+        scoring.update(0.5);
+        assert!(scoring.multiplier < multiplier);
+        scoring.update(10.);
+        assert_eq!(scoring.multiplier, 1.);
+    }
+
+    #[test]
+    fn multiplier_is_capped() {
+        let mut scoring = Scoring::new();
+        for _ in 0..200 {
+            scoring.kill(1);
+        }
+        assert_eq!(scoring.multiplier, MAX_MULTIPLIER);
+    }
+
+    #[test]
+    fn streaks_pay_until_a_shot_misses() {
+        let mut scoring = Scoring::new();
+        for _ in 0..STREAK_STEP * 2 {
+            scoring.kill(0);
+        }
+        assert_eq!(scoring.breakdown.streak, STREAK_BONUS * 3);
+
+        scoring.miss();
+        for _ in 0..STREAK_STEP - 1 {
+            scoring.kill(0);
+        }
+        assert_eq!(scoring.breakdown.streak, STREAK_BONUS * 3);
+    }
+
+    #[test]
+    fn near_misses_and_team_totals() {
+        let mut first = Scoring::new();
+        first.near_miss();
+        let mut second = Scoring::new();
+        second.kill(100);
+        let team = first.breakdown + second.breakdown;
+        assert_eq!(team.near_misses, NEAR_MISS_POINTS);
+        assert_eq!(team.total(), first.total() + second.total());
+    }
+}
@@ -0,0 +1,514 @@
+use std::collections::BTreeMap;
+
+use raylib::prelude::*;
+
+use crate::{
+    Circle, Entity, PowerUp, Reader, Shape, Textures, World, Writer, BOMB_RADIUS, PLAYER_SPRITE_OVERHANG,
+    PLAYER_TINTS,
+};
+
+// What a client needs to draw one entity. The look only changes now and then and is
+// left out of a delta while it stays the same, the pose is sent whenever it moved.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Look {
+    Player { slot: u8, size: Vector2, shield: bool, hidden: bool },
+    // Outlines are around the center and unrotated
+    Asteroid { outline: Vec<Vector2>, color: Color },
+    Ship { outline: Vec<Vector2>, color: Color },
+    Boss { parts: Vec<NetPart> },
+    Shot { missile: bool, width: f32, length: f32, color: Color },
+    Pickup { power_up: PowerUp, radius: f32 },
+    Explosion { radius: f32 },
+    Shockwave,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetPart {
+    pub core: bool,
+    pub turret: bool,
+    pub color: Color,
+    // Around the boss center
+    pub shape: NetShape,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NetShape {
+    Circle(Vector2, f32),
+    Polygon(Vec<Vector2>),
+}
+
+impl From<Shape> for NetShape {
+    fn from(shape: Shape) -> Self {
+        match shape {
+            Shape::Circle(Circle { center, radius }) => Self::Circle(center, radius),
+            Shape::Polygon(points) => Self::Polygon(points),
+        }
+    }
+}
+
+// Center and heading in degrees, `value` is whatever else moves: the age of an
+// explosion from 0 to 1, the radius of a shockwave
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pose {
+    pub position: Vector2,
+    pub angle: f32,
+    pub value: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetEntity {
+    pub look: Look,
+    pub pose: Pose,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct NetPlayer {
+    pub score: u32,
+    pub multiplier: f32,
+    // None once the player is out
+    pub ship: Option<NetShip>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct NetShip {
+    pub lives: u8,
+    pub weapon: u8,
+    pub missiles: u8,
+    pub bombs: u8,
+}
+
+// Everything a client sees of one host tick
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub tick: u32,
+    // Host clock in seconds, what clients interpolate on
+    pub time: f32,
+    pub wave: u32,
+    pub over: bool,
+    pub versus: bool,
+    // Health of the boss from 0 to 1 while there is one
+    pub boss_health: Option<f32>,
+    pub players: Vec<NetPlayer>,
+    pub entities: BTreeMap<u32, NetEntity>,
+}
+
+impl Snapshot {
+    pub fn capture(tick: u32, time: f32, world: &World, over: bool, versus: bool) -> Self {
+        let players = world.scoring.iter()
+            .enumerate()
+            .map(|(slot, scoring)| NetPlayer {
+                score: scoring.total() as u32,
+                multiplier: scoring.multiplier,
+                ship: world.player(slot).map(|p| NetShip {
+                    lives: p.lives.min(u8::MAX as u32) as u8,
+                    weapon: p.weapon as u8,
+                    missiles: p.missiles as u8,
+                    bombs: p.bombs.min(u8::MAX as u32) as u8,
+                }),
+            })
+            .collect();
+        let boss_health = world.entities.values().find_map(|e| match e {
+            Entity::Boss(boss) => Some(boss.health()),
+            _ => None,
+        });
+        Self {
+            tick,
+            time,
+            wave: world.waves.wave.number,
+            over,
+            versus,
+            boss_health,
+            players,
+            entities: world.entities.iter()
+                .filter_map(|(id, e)| Some((*id as u32, e.net_state()?)))
+                .collect(),
+        }
+    }
+
+    // Only what changed since `base` goes in, `base` being a snapshot the client has
+    pub fn encode(&self, base: Option<&Snapshot>, w: &mut Writer) {
+        w.u32(self.tick);
+        w.u32(base.map_or(0, |b| b.tick));
+        w.f32(self.time);
+        w.u32(self.wave);
+        w.u8(self.over as u8 | (self.versus as u8) << 1);
+        w.f32(self.boss_health.unwrap_or(-1.));
+        w.u8(self.players.len() as u8);
+        for player in &self.players {
+            w.u32(player.score);
+            w.f32(player.multiplier);
+            match player.ship {
+                Some(ship) => {
+                    w.u8(1);
+                    w.bytes.extend([ship.lives, ship.weapon, ship.missiles, ship.bombs]);
+                }
+                None => w.u8(0),
+            }
+        }
+
+        let empty = BTreeMap::new();
+        let before = base.map_or(&empty, |b| &b.entities);
+        let removed: Vec<u32> = before.keys().filter(|id| !self.entities.contains_key(id)).copied().collect();
+        w.u32(removed.len() as u32);
+        removed.iter().for_each(|id| w.u32(*id));
+
+        let changed: Vec<(&u32, &NetEntity, bool)> = self.entities.iter()
+            .filter_map(|(id, entity)| match before.get(id) {
+                Some(old) if old == entity => None,
+                Some(old) if old.look == entity.look => Some((id, entity, false)),
+                _ => Some((id, entity, true)),
+            })
+            .collect();
+        w.u32(changed.len() as u32);
+        for (id, entity, with_look) in changed {
+            w.u32(*id);
+            w.u8(with_look as u8);
+            if with_look {
+                entity.look.encode(w);
+            }
+            w.vector(entity.pose.position);
+            w.f32(entity.pose.angle);
+            w.f32(entity.pose.value);
+        }
+    }
+
+    // `history` finds the snapshot the delta was made against, None when it is not there
+    pub fn decode<'a>(r: &mut Reader, history: impl Fn(u32) -> Option<&'a Snapshot>) -> Option<Self> {
+        let tick = r.u32()?;
+        let base = match r.u32()? {
+            0 => None,
+            base => Some(history(base)?),
+        };
+        let time = r.f32()?;
+        let wave = r.u32()?;
+        let flags = r.u8()?;
+        let (over, versus) = (flags & 1 != 0, flags & 2 != 0);
+        let boss_health = Some(r.f32()?).filter(|h| *h >= 0.);
+        let mut players = vec![];
+        for _ in 0..r.u8()? {
+            let score = r.u32()?;
+            let multiplier = r.f32()?;
+            let ship = match r.u8()? {
+                0 => None,
+                _ => Some(NetShip { lives: r.u8()?, weapon: r.u8()?, missiles: r.u8()?, bombs: r.u8()? }),
+            };
+            players.push(NetPlayer { score, multiplier, ship });
+        }
+
+        let mut entities = base.map_or_else(BTreeMap::new, |b| b.entities.clone());
+        for _ in 0..r.u32()? {
+            entities.remove(&r.u32()?);
+        }
+        for _ in 0..r.u32()? {
+            let id = r.u32()?;
+            let look = match r.u8()? {
+                0 => entities.get(&id)?.look.clone(),
+                _ => Look::decode(r)?,
+            };
+            let pose = Pose { position: r.vector()?, angle: r.f32()?, value: r.f32()? };
+            entities.insert(id, NetEntity { look, pose });
+        }
+        Some(Self { tick, time, wave, over, versus, boss_health, players, entities })
+    }
+
+    // Entities of `to` with the poses of those also in `self` blended by `t`
+    pub fn interpolate(&self, to: &Snapshot, t: f32) -> Vec<NetEntity> {
+        to.entities.iter()
+            .map(|(id, entity)| match self.entities.get(id) {
+                Some(from) => NetEntity { look: entity.look.clone(), pose: from.pose.lerp(&entity.pose, t) },
+                None => entity.clone(),
+            })
+            .collect()
+    }
+}
+
+impl Pose {
+    fn lerp(&self, to: &Pose, t: f32) -> Self {
+        // Shortest way round, in -180..180
+        let turn = (to.angle - self.angle + 540.).rem_euclid(360.) - 180.;
+        Self {
+            position: self.position.lerp(to.position, t),
+            angle: self.angle + turn * t,
+            value: self.value + (to.value - self.value) * t,
+        }
+    }
+}
+
+fn encode_points(points: &[Vector2], w: &mut Writer) {
+    w.u8(points.len() as u8);
+    points.iter().for_each(|p| w.vector(*p));
+}
+
+fn decode_points(r: &mut Reader) -> Option<Vec<Vector2>> {
+    (0..r.u8()?).map(|_| r.vector()).collect()
+}
+
+impl Look {
+    fn encode(&self, w: &mut Writer) {
+        match self {
+            Self::Player { slot, size, shield, hidden } => {
+                w.u8(0);
+                w.u8(*slot);
+                w.vector(*size);
+                w.u8(*shield as u8 | (*hidden as u8) << 1);
+            }
+            Self::Asteroid { outline, color } => {
+                w.u8(1);
+                w.color(*color);
+                encode_points(outline, w);
+            }
+            Self::Ship { outline, color } => {
+                w.u8(2);
+                w.color(*color);
+                encode_points(outline, w);
+            }
+            Self::Boss { parts } => {
+                w.u8(3);
+                w.u8(parts.len() as u8);
+                for part in parts {
+                    w.u8(part.core as u8 | (part.turret as u8) << 1);
+                    w.color(part.color);
+                    match &part.shape {
+                        NetShape::Circle(center, radius) => {
+                            w.u8(0);
+                            w.vector(*center);
+                            w.f32(*radius);
+                        }
+                        NetShape::Polygon(points) => {
+                            w.u8(1);
+                            encode_points(points, w);
+                        }
+                    }
+                }
+            }
+            Self::Shot { missile, width, length, color } => {
+                w.u8(4);
+                w.u8(*missile as u8);
+                w.f32(*width);
+                w.f32(*length);
+                w.color(*color);
+            }
+            Self::Pickup { power_up, radius } => {
+                w.u8(5);
+                w.u8(PowerUp::ALL.iter().position(|p| p == power_up).unwrap_or(0) as u8);
+                w.f32(*radius);
+            }
+            Self::Explosion { radius } => {
+                w.u8(6);
+                w.f32(*radius);
+            }
+            Self::Shockwave => w.u8(7),
+        }
+    }
+
+    fn decode(r: &mut Reader) -> Option<Self> {
+        Some(match r.u8()? {
+            0 => {
+                let slot = r.u8()?.min(PLAYER_TINTS.len() as u8 - 1);
+                let size = r.vector()?;
+                let flags = r.u8()?;
+                Self::Player { slot, size, shield: flags & 1 != 0, hidden: flags & 2 != 0 }
+            }
+            1 => Self::Asteroid { color: r.color()?, outline: decode_points(r)? },
+            2 => Self::Ship { color: r.color()?, outline: decode_points(r)? },
+            3 => {
+                let mut parts = vec![];
+                for _ in 0..r.u8()? {
+                    let flags = r.u8()?;
+                    let color = r.color()?;
+                    let shape = match r.u8()? {
+                        0 => NetShape::Circle(r.vector()?, r.f32()?),
+                        _ => NetShape::Polygon(decode_points(r)?),
+                    };
+                    parts.push(NetPart { core: flags & 1 != 0, turret: flags & 2 != 0, color, shape });
+                }
+                Self::Boss { parts }
+            }
+            4 => Self::Shot { missile: r.u8()? != 0, width: r.f32()?, length: r.f32()?, color: r.color()? },
+            5 => Self::Pickup { power_up: *PowerUp::ALL.get(r.u8()? as usize)?, radius: r.f32()? },
+            6 => Self::Explosion { radius: r.f32()? },
+            7 => Self::Shockwave,
+            _ => return None,
+        })
+    }
+}
+
+impl NetEntity {
+    // Looks like `Entity::draw` from what made it over the wire. `time` drives the
+    // boss core's pulse.
+    pub fn draw(&self, d: &mut RaylibDrawHandle, textures: &Textures, time: f32) {
+        let Pose { position, angle, value } = self.pose;
+        let place = |p: &Vector2| position + p.rotated(angle.to_radians());
+        match &self.look {
+            Look::Player { hidden: true, .. } => (),
+            Look::Player { slot, size, shield, .. } => {
+                d.draw_texture_pro(
+                    textures.get("player").unwrap(),
+                    Rectangle::new(0., 0., 40., 40.),
+                    Rectangle::new(position.x - size.x / 2., position.y - size.y / 2., size.x, size.y + PLAYER_SPRITE_OVERHANG),
+                    Vector2::zero(),
+                    0., PLAYER_TINTS[*slot as usize]
+                );
+                if *shield {
+                    d.draw_circle_lines(position.x as i32, position.y as i32 + (PLAYER_SPRITE_OVERHANG / 2.) as i32, size.x, PowerUp::Shield.color());
+                }
+            }
+            Look::Asteroid { outline, color } => {
+                let points: Vec<Vector2> = outline.iter().map(place).collect();
+                for k in 0..points.len() {
+                    d.draw_line_ex(points[k], points[(k + 1) % points.len()], 2., color);
+                }
+            }
+            Look::Ship { outline, color } => {
+                if let [a, b, c] = outline.as_slice() {
+                    d.draw_triangle_lines(place(a), place(b), place(c), color);
+                }
+                let height = outline.iter().map(|p| p.y).fold(0., f32::max) * 2.;
+                let width = outline.iter().map(|p| p.x).fold(0., f32::max) * 2.;
+                d.draw_circle_v(position - Vector2::new(0., height / 6.), width / 8., color);
+            }
+            Look::Boss { parts } => {
+                for part in parts {
+                    match &part.shape {
+                        NetShape::Circle(center, radius) => {
+                            let center = position + *center;
+                            if part.core {
+                                d.draw_circle_v(center, radius * (0.8 + 0.1 * (time * 6.).sin()), part.color);
+                            }
+                            d.draw_circle_lines(center.x as i32, center.y as i32, *radius, part.color);
+                            if part.turret {
+                                let barrel = Vector2::new(0., -1.).rotated(angle.to_radians()) * (radius + 8.);
+                                d.draw_line_ex(center, center + barrel, 3., part.color);
+                            }
+                        }
+                        NetShape::Polygon(points) => {
+                            for k in 0..points.len() {
+                                let (a, b) = (points[k], points[(k + 1) % points.len()]);
+                                d.draw_line_ex(position + a, position + b, 2., part.color);
+                            }
+                        }
+                    }
+                }
+            }
+            Look::Shot { missile, width, length, color } => {
+                d.draw_rectangle_pro(
+                    Rectangle::new(position.x, position.y, *width, *length),
+                    Vector2::new(width / 2., length / 2.),
+                    angle, color
+                );
+                if *missile {
+                    let tail = position - Vector2::new(0., -1.).rotated(angle.to_radians()) * (length / 2. + 3.);
+                    d.draw_circle_v(tail, width / 2., Color::ORANGE);
+                }
+            }
+            Look::Pickup { power_up, radius } => {
+                let color = power_up.color();
+                d.draw_circle_lines(position.x as i32, position.y as i32, *radius, color);
+                d.draw_text(power_up.symbol(), position.x as i32 - 5, position.y as i32 - 9, 20, color);
+            }
+            Look::Explosion { radius } => {
+                let color = Color::ORANGE.alpha(1. - value);
+                d.draw_circle_v(position, radius * value, Color::YELLOW.alpha((1. - value) * 0.4));
+                d.draw_circle_lines(position.x as i32, position.y as i32, radius * value, color);
+            }
+            Look::Shockwave => {
+                let fade = 1. - value / BOMB_RADIUS;
+                d.draw_ring(position, (value - 12.).max(0.), value, 0., 360., 64, Color::PINK.alpha(fade));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entity(look: Look, x: f32) -> NetEntity {
+        NetEntity { look, pose: Pose { position: Vector2::new(x, 100.), angle: 30., value: 0.5 } }
+    }
+
+    fn looks() -> Vec<Look> {
+        let outline = vec![Vector2::new(-10., -5.), Vector2::new(10., -5.), Vector2::new(0., 12.)];
+        vec![
+            Look::Player { slot: 1, size: Vector2::new(40., 50.), shield: true, hidden: false },
+            Look::Asteroid { outline: outline.clone(), color: Color::new(10, 20, 30, 255) },
+            Look::Ship { outline: outline.clone(), color: Color::RED },
+            Look::Boss {
+                parts: vec![
+                    NetPart { core: true, turret: false, color: Color::GOLD, shape: NetShape::Circle(Vector2::new(0., 5.), 20.) },
+                    NetPart { core: false, turret: true, color: Color::GRAY, shape: NetShape::Polygon(outline) },
+                ],
+            },
+            Look::Shot { missile: true, width: 4., length: 20., color: Color::GREEN },
+            Look::Pickup { power_up: PowerUp::Piercing, radius: 12. },
+            Look::Explosion { radius: 30. },
+            Look::Shockwave,
+        ]
+    }
+
+    fn snapshot(tick: u32, entities: BTreeMap<u32, NetEntity>) -> Snapshot {
+        Snapshot {
+            tick,
+            time: tick as f32 / 60.,
+            wave: 3,
+            over: false,
+            versus: true,
+            boss_health: Some(0.25),
+            players: vec![
+                NetPlayer { score: 1200, multiplier: 1.5, ship: Some(NetShip { lives: 2, weapon: 1, missiles: 4, bombs: 3 }) },
+                NetPlayer { score: 80, multiplier: 1., ship: None },
+            ],
+            entities,
+        }
+    }
+
+    fn assert_same(a: &Snapshot, b: &Snapshot) {
+        assert_eq!((a.tick, a.time, a.wave, a.over, a.versus), (b.tick, b.time, b.wave, b.over, b.versus));
+        assert_eq!(a.boss_health, b.boss_health);
+        assert_eq!(a.players, b.players);
+        assert_eq!(a.entities, b.entities);
+    }
+
+    #[test]
+    fn every_look_round_trips() {
+        for look in looks() {
+            let mut w = Writer::default();
+            look.encode(&mut w);
+            assert_eq!(Look::decode(&mut Reader::new(&w.bytes)), Some(look));
+        }
+    }
+
+    #[test]
+    fn full_snapshot_round_trips() {
+        let entities = looks().into_iter().enumerate().map(|(i, look)| (i as u32 + 1, entity(look, i as f32))).collect();
+        let snapshot = snapshot(10, entities);
+        let mut w = Writer::default();
+        snapshot.encode(None, &mut w);
+        let decoded = Snapshot::decode(&mut Reader::new(&w.bytes), |_| None).unwrap();
+        assert_same(&decoded, &snapshot);
+    }
+
+    #[test]
+    fn delta_against_a_base_round_trips() {
+        let looks = looks();
+        let base = snapshot(10, (0..4).map(|i| (i + 1, entity(looks[i as usize].clone(), 0.))).collect());
+        let mut entities = base.entities.clone();
+        // One gone, one moved, one changed its look, one new, one left alone
+        entities.remove(&1);
+        entities.get_mut(&2).unwrap().pose.position.x = 50.;
+        entities.get_mut(&3).unwrap().look = Look::Ship { outline: vec![], color: Color::BLUE };
+        entities.insert(9, entity(Look::Shockwave, 5.));
+        let next = snapshot(12, entities);
+
+        let mut delta = Writer::default();
+        next.encode(Some(&base), &mut delta);
+        let mut full = Writer::default();
+        next.encode(None, &mut full);
+        assert!(delta.bytes.len() < full.bytes.len());
+
+        let decoded = Snapshot::decode(&mut Reader::new(&delta.bytes), |tick| (tick == 10).then_some(&base)).unwrap();
+        assert_same(&decoded, &next);
+        // Without the base it can't be read
+        assert!(Snapshot::decode(&mut Reader::new(&delta.bytes), |_| None).is_none());
+    }
+}
@@ -0,0 +1,488 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+use raylib::prelude::*;
+
+use crate::{draw_snapshot_hud, load_textures, star_field, Entity, Reader, Snapshot, Textures, Writer, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+// Spectator stream: a running game sends what happens to any number of viewers over TCP,
+// who draw it and have no way to act on the game. The layout of the stream is described
+// in docs/spectator.md, a saved stream plays back like a live one.
+pub const SPECTATOR_PORT: u16 = 7878;
+// A viewer this far behind is dropped instead of holding its backlog forever
+const MAX_BACKLOG: usize = 4 << 20;
+// Seconds an event stays in the viewer's ticker
+const TICKER_TIME: f32 = 4.;
+
+// What happened between two frames, worked out by comparing their snapshots
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StreamEvent {
+    // Player slot and the points it just made
+    Score(u8, u32),
+    // Player slot hit and the lives it has left
+    Hit(u8, u8),
+    // Player slot out of lives
+    Out(u8),
+    Wave(u32),
+    Over,
+}
+
+impl StreamEvent {
+    pub fn between(before: &Snapshot, after: &Snapshot) -> Vec<Self> {
+        let mut events = vec![];
+        if after.wave != before.wave {
+            events.push(Self::Wave(after.wave));
+        }
+        for (slot, (old, new)) in before.players.iter().zip(&after.players).enumerate() {
+            let slot = slot as u8;
+            if new.score > old.score {
+                events.push(Self::Score(slot, new.score - old.score));
+            }
+            match (old.ship, new.ship) {
+                (Some(_), None) => events.push(Self::Out(slot)),
+                (Some(old), Some(new)) if new.lives < old.lives => events.push(Self::Hit(slot, new.lives)),
+                _ => (),
+            }
+        }
+        if after.over && !before.over {
+            events.push(Self::Over);
+        }
+        events
+    }
+
+    fn encode(&self, w: &mut Writer) {
+        match self {
+            Self::Score(slot, points) => {
+                w.u8(0);
+                w.u8(*slot);
+                w.u32(*points);
+            }
+            Self::Hit(slot, lives) => {
+                w.u8(1);
+                w.u8(*slot);
+                w.u8(*lives);
+            }
+            Self::Out(slot) => {
+                w.u8(2);
+                w.u8(*slot);
+            }
+            Self::Wave(wave) => {
+                w.u8(3);
+                w.u32(*wave);
+            }
+            Self::Over => w.u8(4),
+        }
+    }
+
+    fn decode(r: &mut Reader) -> Option<Self> {
+        Some(match r.u8()? {
+            0 => Self::Score(r.u8()?, r.u32()?),
+            1 => Self::Hit(r.u8()?, r.u8()?),
+            2 => Self::Out(r.u8()?),
+            3 => Self::Wave(r.u32()?),
+            4 => Self::Over,
+            _ => return None,
+        })
+    }
+
+    // Scores are too frequent for the ticker
+    fn describe(&self) -> Option<String> {
+        match self {
+            Self::Score(..) => None,
+            Self::Hit(slot, lives) => Some(format!("player {} hit, {lives} left", slot + 1)),
+            Self::Out(slot) => Some(format!("player {} is out", slot + 1)),
+            Self::Wave(wave) => Some(format!("wave {wave}")),
+            Self::Over => Some("game over".to_string()),
+        }
+    }
+}
+
+// One frame of the stream: length, then the snapshot against the previous frame and the events
+fn frame(snapshot: &Snapshot, base: Option<&Snapshot>, events: &[StreamEvent]) -> Vec<u8> {
+    let mut payload = Writer::default();
+    snapshot.encode(base, &mut payload);
+    payload.u8(events.len().min(u8::MAX as usize) as u8);
+    events.iter().take(u8::MAX as usize).for_each(|event| event.encode(&mut payload));
+    let mut w = Writer::default();
+    w.u32(payload.bytes.len() as u32);
+    w.bytes.extend(payload.bytes);
+    w.bytes
+}
+
+struct Viewer {
+    stream: TcpStream,
+    // Written but not yet taken by the socket
+    backlog: Vec<u8>,
+    // Has not had a frame yet, the first one is a full snapshot
+    fresh: bool,
+}
+
+impl Viewer {
+    // False once the viewer is gone or too slow to keep
+    fn flush(&mut self) -> bool {
+        while !self.backlog.is_empty() {
+            match self.stream.write(&self.backlog) {
+                Ok(0) => return false,
+                Ok(written) => {
+                    self.backlog.drain(..written);
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(_) => return false,
+            }
+        }
+        self.backlog.len() <= MAX_BACKLOG
+    }
+}
+
+// Game side of the stream, nothing the viewers send is ever read
+pub struct Broadcaster {
+    listener: TcpListener,
+    viewers: Vec<Viewer>,
+    previous: Option<Snapshot>,
+}
+
+impl Broadcaster {
+    pub fn bind(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        listener.set_nonblocking(true)?;
+        Ok(Self { listener, viewers: vec![], previous: None })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.listener.local_addr().map_or(0, |a| a.port())
+    }
+
+    pub fn viewers(&self) -> usize {
+        self.viewers.len()
+    }
+
+    pub fn send(&mut self, snapshot: Snapshot) {
+        while let Ok((stream, _)) = self.listener.accept() {
+            if stream.set_nonblocking(true).is_ok() {
+                let _ = stream.set_nodelay(true);
+                self.viewers.push(Viewer { stream, backlog: vec![], fresh: true });
+            }
+        }
+        if !self.viewers.is_empty() {
+            let events = self.previous.as_ref().map_or(vec![], |p| StreamEvent::between(p, &snapshot));
+            let delta = frame(&snapshot, self.previous.as_ref(), &events);
+            let full = self.viewers.iter().any(|v| v.fresh).then(|| frame(&snapshot, None, &events));
+            for viewer in self.viewers.iter_mut() {
+                let bytes = if viewer.fresh { full.as_ref().unwrap_or(&delta) } else { &delta };
+                viewer.backlog.extend(bytes);
+                viewer.fresh = false;
+            }
+            self.viewers.retain_mut(Viewer::flush);
+        }
+        self.previous = Some(snapshot);
+    }
+}
+
+// The stream as it arrives, cut into frames and decoded
+#[derive(Default)]
+struct Feed {
+    // Received bytes not yet decoded
+    buffer: Vec<u8>,
+    // Decoded frames waiting for their turn, and the newest one deltas refer to
+    queue: VecDeque<(Snapshot, Vec<StreamEvent>)>,
+    decoded: Option<Snapshot>,
+    // Nothing more is coming, the other side hung up or the stream broke
+    ended: bool,
+}
+
+impl Feed {
+    // Every whole frame in the buffer, or only up to one waiting in the queue
+    fn decode(&mut self, one_ahead: bool) {
+        while (!one_ahead || self.queue.is_empty())
+            && let Some(len) = self.buffer.get(..4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+            && self.buffer.len() >= 4 + len
+        {
+            let mut r = Reader::new(&self.buffer[4..4 + len]);
+            let base = self.decoded.as_ref();
+            let snapshot = Snapshot::decode(&mut r, |tick| base.filter(|b| b.tick == tick));
+            let events = (0..r.u8().unwrap_or(0)).map_while(|_| StreamEvent::decode(&mut r)).collect();
+            self.buffer.drain(..4 + len);
+            // A broken frame leaves nothing to decode the deltas after it against
+            let Some(snapshot) = snapshot else {
+                self.ended = true;
+                self.buffer.clear();
+                return;
+            };
+            self.decoded = Some(snapshot.clone());
+            self.queue.push_back((snapshot, events));
+        }
+    }
+}
+
+enum Source {
+    Live(TcpStream),
+    // Saved stream, frames are shown at the pace they were sent. Host time of the first
+    // frame and local time it was shown at.
+    Recording(Option<(f32, f32)>),
+}
+
+// Viewer window, draws the stream and can save it for later
+pub struct Spectator {
+    rl: RaylibHandle,
+    rt: RaylibThread,
+    textures: Textures,
+    stars: Vec<Entity>,
+    source: Source,
+    feed: Feed,
+    record: Option<File>,
+    shown: Option<Snapshot>,
+    ticker: Vec<(String, f32)>,
+    // Shown in the corner, where the stream comes from
+    name: String,
+}
+
+impl Spectator {
+    // `source` is a saved stream if such a file exists, or else `host[:port]` of a game
+    pub fn open(source: &str, record: Option<&str>) -> io::Result<Self> {
+        let (source, buffer, name) = if std::path::Path::new(source).is_file() {
+            let mut buffer = vec![];
+            File::open(source)?.read_to_end(&mut buffer)?;
+            (Source::Recording(None), buffer, format!("REPLAY {source}"))
+        } else {
+            let address = if source.contains(':') { source.to_string() } else { format!("{source}:{SPECTATOR_PORT}") };
+            let stream = TcpStream::connect(&address)?;
+            stream.set_nonblocking(true)?;
+            (Source::Live(stream), vec![], format!("LIVE {address}"))
+        };
+        let record = record.map(File::create).transpose()?;
+
+        let (mut rl, rt) = raylib::init()
+            .title("Asteroids")
+            .size(SCREEN_WIDTH!() as i32, SCREEN_HEIGHT!() as i32)
+            .vsync()
+            .build();
+        rl.set_target_fps(60);
+        let textures = load_textures(&mut rl, &rt);
+
+        Ok(Self {
+            rl, rt,
+            textures,
+            stars: star_field(),
+            source,
+            feed: Feed { buffer, ..Feed::default() },
+            record,
+            shown: None,
+            ticker: vec![],
+            name,
+        })
+    }
+
+    pub fn run(&mut self) {
+        while !self.rl.window_should_close() {
+            let over = self.shown.as_ref().is_some_and(|s| s.over);
+            if (self.feed.ended || over) && self.rl.is_key_pressed(KeyboardKey::KEY_ENTER) {
+                break;
+            }
+            let delta_time = self.rl.get_frame_time();
+            self.receive();
+            self.decode();
+            self.show(self.rl.get_time() as f32);
+            for star in self.stars.iter_mut() {
+                star.update(&[], delta_time);
+            }
+            for (_, age) in self.ticker.iter_mut() {
+                *age += delta_time;
+            }
+            self.ticker.retain(|(_, age)| *age < TICKER_TIME);
+            self.draw();
+        }
+    }
+
+    fn receive(&mut self) {
+        let Source::Live(stream) = &mut self.source else {
+            return;
+        };
+        let mut chunk = [0; 16384];
+        loop {
+            match stream.read(&mut chunk) {
+                Ok(0) => {
+                    self.feed.ended = true;
+                    break;
+                }
+                Ok(len) => {
+                    self.feed.buffer.extend_from_slice(&chunk[..len]);
+                    if let Some(file) = self.record.as_mut()
+                        && let Err(err) = file.write_all(&chunk[..len])
+                    {
+                        eprintln!("Could not record the stream: {err}");
+                        self.record = None;
+                    }
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(_) => {
+                    self.feed.ended = true;
+                    break;
+                }
+            }
+        }
+    }
+
+    // Recordings are decoded one frame ahead of what is shown, live streams as they come
+    fn decode(&mut self) {
+        let recording = matches!(self.source, Source::Recording(_));
+        self.feed.decode(recording);
+        if recording && self.feed.buffer.is_empty() && self.feed.queue.is_empty() {
+            self.feed.ended = true;
+        }
+    }
+
+    // Live frames are shown as they come, recorded ones when their time is up
+    fn show(&mut self, now: f32) {
+        while let Some((snapshot, _)) = self.feed.queue.front() {
+            if let Source::Recording(start) = &mut self.source {
+                let (first, shown_at) = *start.get_or_insert((snapshot.time, now));
+                if snapshot.time - first > now - shown_at {
+                    break;
+                }
+            }
+            let Some((snapshot, events)) = self.feed.queue.pop_front() else {
+                break;
+            };
+            self.ticker.extend(events.iter().filter_map(StreamEvent::describe).map(|line| (line, 0.)));
+            self.shown = Some(snapshot);
+        }
+    }
+
+    fn draw(&mut self) {
+        let time = self.rl.get_time() as f32;
+        let mut d = self.rl.begin_drawing(&self.rt);
+        d.clear_background(Color::BLACK);
+        for star in &self.stars {
+            star.draw(&mut d, &self.textures);
+        }
+        d.draw_text(&self.name, 35, (SCREEN_HEIGHT!() - 35.) as i32, 20, Color::RED);
+        for (row, (line, age)) in self.ticker.iter().rev().enumerate() {
+            let fade = 1. - age / TICKER_TIME;
+            d.draw_text(line, 35, (SCREEN_HEIGHT!() - 65.) as i32 - row as i32 * 22, 20, Color::WHITE.alpha(fade));
+        }
+
+        let Some(snapshot) = &self.shown else {
+            let status = if self.feed.ended { "Nothing to show" } else { "Waiting for the game..." };
+            d.draw_text(status, (SCREEN_WIDTH!()/2. - 200.) as i32, (SCREEN_HEIGHT!()/2.) as i32, 30, Color::WHITE);
+            return;
+        };
+        for entity in snapshot.entities.values() {
+            entity.draw(&mut d, &self.textures, time);
+        }
+        draw_snapshot_hud(&mut d, snapshot, None, "Press ENTER to exit");
+        if self.feed.ended && !snapshot.over {
+            d.draw_text(
+                "The stream has ended, press ENTER to exit",
+                (SCREEN_WIDTH!()/2. - 300.) as i32,
+                (SCREEN_HEIGHT!()/2.) as i32,
+                30, Color::WHITE
+            );
+        }
+    }
+}
+
+impl Drop for Spectator {
+    fn drop(&mut self) {
+        self.textures.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Look, NetEntity, NetPlayer, NetShip, Pose};
+
+    fn ship(lives: u8) -> Option<NetShip> {
+        Some(NetShip { lives, weapon: 0, missiles: 2, bombs: 1 })
+    }
+
+    fn snapshot(tick: u32, players: Vec<NetPlayer>) -> Snapshot {
+        let pose = Pose { position: Vector2::new(tick as f32 * 10., 50.), angle: 0., value: 0. };
+        Snapshot {
+            tick,
+            time: tick as f32 / 60.,
+            wave: 1,
+            over: false,
+            versus: false,
+            boss_health: None,
+            players,
+            entities: [(1, NetEntity { look: Look::Shockwave, pose })].into(),
+        }
+    }
+
+    fn player(score: u32, ship: Option<NetShip>) -> NetPlayer {
+        NetPlayer { score, multiplier: 1., ship }
+    }
+
+    #[test]
+    fn frames_decode_in_full_and_as_deltas() {
+        let first = snapshot(1, vec![player(0, ship(3))]);
+        let second = snapshot(2, vec![player(100, ship(3))]);
+        let events = StreamEvent::between(&first, &second);
+        let mut feed = Feed::default();
+        feed.buffer.extend(frame(&first, None, &[]));
+        feed.buffer.extend(frame(&second, Some(&first), &events));
+        feed.decode(false);
+
+        assert!(!feed.ended && feed.buffer.is_empty());
+        let decoded: Vec<_> = feed.queue.iter().map(|(s, e)| (s.tick, s.players.clone(), s.entities.clone(), e.clone())).collect();
+        assert_eq!(decoded, [
+            (1, first.players, first.entities, vec![]),
+            (2, second.players, second.entities, vec![StreamEvent::Score(0, 100)]),
+        ]);
+    }
+
+    #[test]
+    fn recordings_decode_one_frame_ahead() {
+        let mut feed = Feed::default();
+        feed.buffer.extend(frame(&snapshot(1, vec![]), None, &[]));
+        feed.buffer.extend(frame(&snapshot(2, vec![]), None, &[]));
+        feed.decode(true);
+        assert_eq!(feed.queue.len(), 1);
+        // Half a frame waits for the rest of it
+        let next = frame(&snapshot(3, vec![]), None, &[]);
+        feed.buffer.extend(&next[..next.len() / 2]);
+        feed.decode(false);
+        assert_eq!(feed.queue.len(), 2);
+        assert!(!feed.ended);
+    }
+
+    #[test]
+    fn broken_frame_ends_the_stream() {
+        let mut feed = Feed::default();
+        // A delta against a frame the viewer never had
+        let missing = snapshot(1, vec![]);
+        feed.buffer.extend(frame(&snapshot(2, vec![]), Some(&missing), &[]));
+        feed.buffer.extend(frame(&snapshot(3, vec![]), None, &[]));
+        feed.decode(false);
+        assert!(feed.ended);
+        assert!(feed.queue.is_empty() && feed.buffer.is_empty());
+    }
+
+    #[test]
+    fn events_come_from_the_difference() {
+        let before = snapshot(1, vec![player(100, ship(3)), player(50, ship(1)), player(0, ship(2))]);
+        let mut after = snapshot(2, vec![player(150, ship(2)), player(50, None), player(0, ship(2))]);
+        after.wave = 2;
+        after.over = true;
+        assert_eq!(StreamEvent::between(&before, &after), [
+            StreamEvent::Wave(2),
+            StreamEvent::Score(0, 50),
+            StreamEvent::Hit(0, 2),
+            StreamEvent::Out(1),
+            StreamEvent::Over,
+        ]);
+        assert!(StreamEvent::between(&after, &after).is_empty());
+    }
+
+    #[test]
+    fn events_round_trip() {
+        for event in [StreamEvent::Score(1, 70000), StreamEvent::Hit(0, 2), StreamEvent::Out(1), StreamEvent::Wave(12), StreamEvent::Over] {
+            let mut w = Writer::default();
+            event.encode(&mut w);
+            assert_eq!(StreamEvent::decode(&mut Reader::new(&w.bytes)), Some(event));
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use std::ops::Range;
+
+use rand::*;
+
+// Asteroid size classes by diameter, waves pick from them with per-wave weights
+pub const SMALL: Range<f32> = 12.0..18.;
+pub const MEDIUM: Range<f32> = 20.0..28.;
+pub const LARGE: Range<f32> = 30.0..40.;
+
+// Pause between waves, while the banner is up
+const BREAK_DURATION: f32 = 3.;
+const BOSS_EVERY: u32 = 5;
+// The next wave comes anyway once the field took this long to clear, so ships holding
+// their slots can't keep a wave going forever
+const CLEARING_TIMEOUT: f32 = 40.;
+
+#[derive(Debug, Clone)]
+pub struct Wave {
+    pub number: u32,
+    pub asteroid_count: u32,
+    // Enemy ships, all arriving together when the wave starts
+    pub ship_count: u32,
+    // Every fifth wave is a boss fight with a lighter escort
+    pub boss: bool,
+    // Relative weights of small, medium and large asteroids
+    pub size_mix: [f32; 3],
+    pub speed: Range<f32>,
+    // Seconds between two spawns
+    pub spawn_interval: f32,
+}
+
+impl Wave {
+    // Difficulty curve: more, bigger and faster asteroids arriving closer together,
+    // leveling off so late waves stay playable. Waves count from 1, a 0 plays like the first.
+    pub fn new(number: u32) -> Self {
+        let level = number.saturating_sub(1);
+        let n = level as f32;
+        let speed_scale = 1. + (0.08 * n).min(1.);
+        let boss = number > 0 && number.is_multiple_of(BOSS_EVERY);
+        let asteroid_count = 6 + 2 * level.min(20);
+        Self {
+            number,
+            asteroid_count: if boss { asteroid_count / 3 } else { asteroid_count },
+            ship_count: if boss { 0 } else { number.saturating_sub(2).min(8) },
+            boss,
+            size_mix: [
+                3.,
+                1. + 0.5 * n,
+                (0.4 * n).min(4.),
+            ],
+            speed: 200. * speed_scale..300. * speed_scale,
+            spawn_interval: (0.8 * 0.9f32.powf(n)).max(0.25),
+        }
+    }
+
+    pub fn random_size(&self, rng: &mut impl Rng) -> f32 {
+        let total: f32 = self.size_mix.iter().sum();
+        let mut pick = rng.random_range(0.0..total);
+        for (weight, class) in self.size_mix.iter().zip([SMALL, MEDIUM, LARGE]) {
+            if pick < *weight {
+                return rng.random_range(class);
+            }
+            pick -= weight;
+        }
+        rng.random_range(SMALL)
+    }
+
+    pub fn random_speed(&self, rng: &mut impl Rng) -> f32 {
+        rng.random_range(self.speed.clone())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WaveState {
+    // Banner is showing, the wave starts when the timer runs out
+    Break(f32),
+    // Asteroids left to spawn and time until the next one
+    Spawning(u32, f32),
+    // Everything spawned, waiting for the field to clear, with the seconds left until the
+    // next wave comes regardless
+    Clearing(f32),
+}
+
+// What to bring in this frame
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Spawns {
+    pub asteroids: u32,
+    pub ships: u32,
+    pub boss: bool,
+    // Set on the frame the banner goes away and the wave begins
+    pub started: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct WaveDirector {
+    pub wave: Wave,
+    pub state: WaveState,
+}
+
+impl Default for WaveDirector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WaveDirector {
+    pub fn new() -> Self {
+        Self {
+            wave: Wave::new(1),
+            state: WaveState::Break(BREAK_DURATION),
+        }
+    }
+
+    // Advance the wave timers, returns what to spawn this frame
+    pub fn update(&mut self, delta_time: f32, enemies_alive: u32) -> Spawns {
+        match self.state {
+            WaveState::Break(timer) => {
+                let timer = timer - delta_time;
+                if timer <= 0. {
+                    self.state = WaveState::Spawning(self.wave.asteroid_count, 0.);
+                    Spawns { asteroids: 0, ships: self.wave.ship_count, boss: self.wave.boss, started: true }
+                } else {
+                    self.state = WaveState::Break(timer);
+                    Spawns::default()
+                }
+            }
+            WaveState::Spawning(remaining, timer) => {
+                let mut timer = timer - delta_time;
+                let mut spawned = 0;
+                while timer <= 0. && spawned < remaining {
+                    spawned += 1;
+                    timer += self.wave.spawn_interval;
+                }
+                self.state = if spawned == remaining {
+                    WaveState::Clearing(CLEARING_TIMEOUT)
+                } else {
+                    WaveState::Spawning(remaining - spawned, timer)
+                };
+                Spawns { asteroids: spawned, ..Spawns::default() }
+            }
+            WaveState::Clearing(timer) => {
+                let timer = timer - delta_time;
+                if enemies_alive == 0 || timer <= 0. {
+                    self.wave = Wave::new(self.wave.number + 1);
+                    self.state = WaveState::Break(BREAK_DURATION);
+                } else {
+                    self.state = WaveState::Clearing(timer);
+                }
+                Spawns::default()
+            }
+        }
+    }
+
+    pub fn banner(&self) -> Option<String> {
+        match self.state {
+            WaveState::Break(_) if self.wave.boss => Some(format!("WAVE {} - BOSS", self.wave.number)),
+            WaveState::Break(_) => Some(format!("WAVE {}", self.wave.number)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Runs the director until it leaves its current state, returns everything it spawned
+    fn run_until_change(director: &mut WaveDirector, enemies_alive: u32) -> Spawns {
+        let mut total = Spawns::default();
+        let state = std::mem::discriminant(&director.state);
+        for _ in 0..10_000 {
+            let spawns = director.update(0.1, enemies_alive);
+            total.asteroids += spawns.asteroids;
+            total.ships += spawns.ships;
+            total.boss |= spawns.boss;
+            if std::mem::discriminant(&director.state) != state {
+                return total;
+            }
+        }
+        panic!("stuck in {:?}", director.state);
+    }
+
+    #[test]
+    fn wave_spawns_everything_then_waits_for_the_field_to_clear() {
+        let mut director = WaveDirector::new();
+        let wave = director.wave.clone();
+        run_until_change(&mut director, 0);
+        let spawned = run_until_change(&mut director, 0);
+        assert_eq!(spawned.asteroids, wave.asteroid_count);
+        assert!(matches!(director.state, WaveState::Clearing(_)));
+
+        director.update(0.1, 3);
+        assert!(matches!(director.state, WaveState::Clearing(_)));
+        director.update(0.1, 0);
+        assert_eq!(director.wave.number, 2);
+        assert!(matches!(director.state, WaveState::Break(_)));
+    }
+
+    #[test]
+    fn clearing_gives_up_on_enemies_that_never_leave() {
+        let mut director = WaveDirector::new();
+        run_until_change(&mut director, 0);
+        run_until_change(&mut director, 0);
+        run_until_change(&mut director, 5);
+        assert_eq!(director.wave.number, 2);
+    }
+
+    #[test]
+    fn every_fifth_wave_is_a_boss_wave() {
+        let bosses: Vec<u32> = (1..=15).filter(|n| Wave::new(*n).boss).collect();
+        assert_eq!(bosses, [5, 10, 15]);
+        assert_eq!(Wave::new(5).ship_count, 0);
+        assert!(Wave::new(9).asteroid_count > Wave::new(1).asteroid_count);
+    }
+
+    #[test]
+    fn wave_zero_plays_like_the_first() {
+        let (zero, first) = (Wave::new(0), Wave::new(1));
+        assert!(!zero.boss);
+        assert_eq!((zero.asteroid_count, zero.ship_count), (first.asteroid_count, first.ship_count));
+        assert_eq!(zero.speed, first.speed);
+    }
+
+    #[test]
+    fn boss_and_ships_arrive_when_the_wave_starts() {
+        let mut director = WaveDirector { wave: Wave::new(4), state: WaveState::Break(0.05) };
+        let spawns = director.update(0.1, 0);
+        assert_eq!(spawns.ships, Wave::new(4).ship_count);
+        assert!(!spawns.boss);
+        assert!(spawns.started);
+        assert!(!director.update(0.1, 0).started);
+
+        let mut director = WaveDirector { wave: Wave::new(5), state: WaveState::Break(0.05) };
+        assert!(director.update(0.1, 0).boss);
+    }
+}
@@ -0,0 +1,151 @@
+use raylib::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProjectileKind {
+    Lazer,
+    Beam,
+    Missile,
+}
+
+// Look and flight of a single shot
+#[derive(Debug, Clone, Copy)]
+pub struct ProjectileSpec {
+    pub kind: ProjectileKind,
+    pub width: f32,
+    pub length: f32,
+    pub speed: f32,
+    pub color: Color,
+}
+
+const LAZER: ProjectileSpec = ProjectileSpec {
+    kind: ProjectileKind::Lazer,
+    width: 2.,
+    length: 45.,
+    speed: 1100.,
+    color: Color::RED,
+};
+
+const BEAM: ProjectileSpec = ProjectileSpec {
+    kind: ProjectileKind::Beam,
+    width: 3.,
+    length: 90.,
+    speed: 2200.,
+    color: Color::SKYBLUE,
+};
+
+const MISSILE: ProjectileSpec = ProjectileSpec {
+    kind: ProjectileKind::Missile,
+    width: 6.,
+    length: 18.,
+    speed: 600.,
+    color: Color::LIGHTGRAY,
+};
+
+// Slow enough to dodge
+pub const ENEMY_SHOT: ProjectileSpec = ProjectileSpec {
+    kind: ProjectileKind::Lazer,
+    width: 4.,
+    length: 16.,
+    speed: 450.,
+    color: Color::ORANGE,
+};
+
+pub const HOMING_MISSILE: ProjectileSpec = ProjectileSpec {
+    kind: ProjectileKind::Missile,
+    width: 5.,
+    length: 16.,
+    speed: 520.,
+    color: Color::GOLD,
+};
+
+// Guidance and warhead of a homing missile
+#[derive(Debug, Clone, Copy)]
+pub struct Homing {
+    // Degrees per second the missile can turn
+    pub turn_rate: f32,
+    // Seconds of flight before it blows up on its own
+    pub fuel: f32,
+    // Targets further away than this are ignored
+    pub range: f32,
+    pub blast_radius: f32,
+    pub blast_damage: u32,
+}
+
+pub const HOMING: Homing = Homing {
+    turn_rate: 240.,
+    fuel: 2.5,
+    range: 700.,
+    blast_radius: 70.,
+    blast_damage: 4,
+};
+
+// Secondary weapon ammo, topped up at the start of every wave
+pub const MISSILE_CAPACITY: u32 = 6;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Weapon {
+    pub name: &'static str,
+    pub projectile: ProjectileSpec,
+    // Seconds between two shots
+    pub fire_interval: f32,
+    // Keeps firing while the button is held
+    pub automatic: bool,
+    // Where the shots leave the ship, as fractions of its width from the left edge
+    pub muzzles: &'static [f32],
+    // Angle in degrees between the outermost shots, fanned evenly across the muzzles
+    pub spread: f32,
+    // Hit points taken off a ship or a boss part per shot, asteroids break at the first hit
+    pub damage: u32,
+}
+
+pub const WEAPONS: [Weapon; 4] = [
+    Weapon {
+        name: "LAZER",
+        projectile: LAZER,
+        fire_interval: 0.15,
+        automatic: false,
+        muzzles: &[1. / 3., 0.5, 2. / 3.],
+        spread: 0.,
+        damage: 1,
+    },
+    Weapon {
+        name: "SPREAD",
+        projectile: LAZER,
+        fire_interval: 0.3,
+        automatic: false,
+        muzzles: &[0.5; 5],
+        spread: 40.,
+        damage: 1,
+    },
+    Weapon {
+        name: "BEAM",
+        projectile: BEAM,
+        fire_interval: 0.06,
+        automatic: true,
+        muzzles: &[0.5],
+        spread: 0.,
+        damage: 1,
+    },
+    Weapon {
+        name: "MISSILE",
+        projectile: MISSILE,
+        fire_interval: 0.6,
+        automatic: false,
+        muzzles: &[0.15, 0.85],
+        spread: 0.,
+        damage: 4,
+    },
+];
+
+impl Weapon {
+    // Muzzle offset and heading in degrees from straight up for every shot of a volley
+    pub fn shots(&self) -> impl Iterator<Item = (f32, f32)> + '_ {
+        let last = self.muzzles.len().saturating_sub(1).max(1) as f32;
+        self.muzzles.iter()
+            .enumerate()
+            .map(move |(i, muzzle)| {
+                let angle = if self.muzzles.len() > 1 { (i as f32 / last - 0.5) * self.spread } else { 0. };
+                (*muzzle, angle)
+            })
+    }
+}